@@ -0,0 +1,29 @@
+-- ==========================================
+--  DOCUMENT WATCHES TABLE (Subscriptions)
+-- ==========================================
+--
+-- A user "watching" a document gets a notification whenever a new
+-- version of that document is uploaded.
+
+CREATE TABLE IF NOT EXISTS document_watches (
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    document_id UUID NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, document_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_watches_document_id ON document_watches (document_id);
+
+-- ==========================================
+--  NOTIFICATIONS TABLE
+-- ==========================================
+CREATE TABLE IF NOT EXISTS notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4 (),
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    document_id UUID REFERENCES documents (id) ON DELETE SET NULL,
+    document_version INTEGER,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_notifications_user_created ON notifications (user_id, created_at DESC);
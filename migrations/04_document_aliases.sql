@@ -0,0 +1,17 @@
+-- ==========================================
+--  DOCUMENT ALIASES TABLE (Short Links)
+-- ==========================================
+--
+-- An alias is a short opaque slug that resolves to a document's latest
+-- version, e.g. GET /d/Xk3pQ9aZ2b. Aliases are weak references: they are
+-- removed with the document and may expire.
+
+CREATE TABLE IF NOT EXISTS document_aliases (
+    slug VARCHAR(32) PRIMARY KEY,
+    document_id UUID NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+    created_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_aliases_document_id ON document_aliases (document_id);
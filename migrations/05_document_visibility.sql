@@ -0,0 +1,15 @@
+-- ==========================================
+--  DOCUMENT OWNERSHIP & VISIBILITY
+-- ==========================================
+--
+-- `created_by` records the user who created the document (the owner).
+-- `is_public` documents can be downloaded without an API key via
+-- GET /public/documents/{id}/content.
+
+ALTER TABLE documents
+    ADD COLUMN IF NOT EXISTS created_by UUID REFERENCES users (id) ON DELETE SET NULL;
+
+ALTER TABLE documents
+    ADD COLUMN IF NOT EXISTS is_public BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_documents_created_by ON documents (created_by);
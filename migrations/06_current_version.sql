@@ -0,0 +1,12 @@
+-- ==========================================
+--  PROMOTED ("CURRENT") VERSION
+-- ==========================================
+--
+-- `current_version` pins the version served when a download names no
+-- version, independent of the highest version number. NULL (the default)
+-- means "latest uploaded". Set via POST /documents/{id}/promote/{version}.
+
+ALTER TABLE documents
+    ADD COLUMN IF NOT EXISTS current_version INT;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'PROMOTE_VERSION';
@@ -0,0 +1,9 @@
+-- ==========================================
+--  ADMIN PASSWORD RESETS
+-- ==========================================
+--
+-- Audited by POST /admin/users/{id}/reset-password. The password itself is
+-- never written to the audit trail. `users.password` now holds argon2 PHC
+-- hashes; plain-text rows from before still log in until they are reset.
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'RESET_PASSWORD';
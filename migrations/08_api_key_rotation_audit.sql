@@ -0,0 +1,8 @@
+-- ==========================================
+--  API KEY ROTATION
+-- ==========================================
+--
+-- Audited by POST /auth/rotate-key and POST /users/{id}/rotate-key. Neither
+-- the old nor the new key is written to the audit trail.
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'ROTATE_API_KEY';
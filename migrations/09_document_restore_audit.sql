@@ -0,0 +1,7 @@
+-- ==========================================
+--  SOFT-DELETE RESTORES
+-- ==========================================
+--
+-- Audited once per document by POST /documents/restore/bulk.
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'RESTORE_DOCUMENT';
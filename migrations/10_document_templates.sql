@@ -0,0 +1,11 @@
+-- ==========================================
+--  DOCUMENT TEMPLATES
+-- ==========================================
+--
+-- Templates are instantiated with POST /documents/from-template/{id} and are
+-- left out of GET /documents unless `include_templates=true`.
+
+ALTER TABLE documents
+    ADD COLUMN IF NOT EXISTS is_template BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_documents_is_template ON documents (is_template) WHERE is_template;
@@ -0,0 +1,13 @@
+-- ==========================================
+--  FOLDER REGISTRY
+-- ==========================================
+--
+-- Folders live in storage as `.folder_metadata.json` markers, which can't be
+-- created atomically. POST /folders claims the name here first (FOLDER_REGISTRY),
+-- so of two concurrent requests for the same folder exactly one succeeds.
+
+CREATE TABLE IF NOT EXISTS folder_registry (
+    name        VARCHAR(255) PRIMARY KEY,
+    created_by  UUID REFERENCES users (id) ON DELETE SET NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
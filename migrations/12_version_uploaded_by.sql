@@ -0,0 +1,12 @@
+-- ==========================================
+--  VERSION UPLOADER
+-- ==========================================
+--
+-- `uploaded_by` records who created each version (upload, restore or template
+-- instantiation). NULL for versions from before this column; reports fall
+-- back to the document's `created_by` for those.
+
+ALTER TABLE document_versions
+    ADD COLUMN IF NOT EXISTS uploaded_by UUID REFERENCES users (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_document_versions_uploaded_by ON document_versions (uploaded_by);
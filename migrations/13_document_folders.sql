@@ -0,0 +1,18 @@
+-- ==========================================
+--  DOCUMENT FOLDERS
+-- ==========================================
+--
+-- Links documents to folders (POST /documents/:id/folder). Folders exist only
+-- as `.folder_metadata.json` markers in storage, so they are referenced by
+-- their full path (e.g. `finance/2024`) rather than by a foreign key. A
+-- document may be filed in several folders.
+
+CREATE TABLE IF NOT EXISTS document_folders (
+    document_id  UUID NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+    folder       VARCHAR(255) NOT NULL,
+    linked_by    UUID REFERENCES users (id) ON DELETE SET NULL,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (document_id, folder)
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_folders_folder ON document_folders (folder);
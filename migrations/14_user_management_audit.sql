@@ -0,0 +1,10 @@
+-- ==========================================
+--  USER MANAGEMENT
+-- ==========================================
+--
+-- Audited by POST /users, PATCH /users/{id}/role and DELETE /users/{id}.
+-- Generated API keys and passwords are never written to the audit trail.
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'CREATE_USER';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'UPDATE_USER_ROLE';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'DELETE_USER';
@@ -0,0 +1,30 @@
+-- ==========================================
+--  RESUMABLE UPLOADS
+-- ==========================================
+--
+-- A session is opened by POST /uploads/init and holds everything the final
+-- upload needs except the content. Chunks are stored under `uploads/{id}/`
+-- in object storage; `upload_chunks` records which ones arrived so a client
+-- can resume. Both are removed once the upload completes or is aborted.
+
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    id           UUID PRIMARY KEY DEFAULT uuid_generate_v4 (),
+    created_by   UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    document_id  UUID REFERENCES documents (id) ON DELETE CASCADE,
+    title        VARCHAR(255),
+    category     VARCHAR(100),
+    file_name    VARCHAR(255),
+    mime_type    VARCHAR(100),
+    metadata     JSONB NOT NULL DEFAULT '{}'::jsonb,
+    -- Set while POST /uploads/:id/complete assembles the chunks
+    completing   BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS upload_chunks (
+    upload_id     UUID NOT NULL REFERENCES upload_sessions (id) ON DELETE CASCADE,
+    chunk_number  INTEGER NOT NULL,
+    size          BIGINT NOT NULL,
+    created_at    TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (upload_id, chunk_number)
+);
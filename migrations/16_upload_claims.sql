@@ -0,0 +1,11 @@
+-- ==========================================
+--  RESUMABLE UPLOAD CLAIMS
+-- ==========================================
+--
+-- POST /uploads/:id/complete claims a session by stamping `completing_since`
+-- instead of setting a flag, so a claim left behind by a crash goes stale
+-- after UPLOAD_CLAIM_TIMEOUT_SECS and the session can be completed again,
+-- aborted or expired.
+
+ALTER TABLE upload_sessions ADD COLUMN IF NOT EXISTS completing_since TIMESTAMP WITH TIME ZONE;
+ALTER TABLE upload_sessions DROP COLUMN IF EXISTS completing;
@@ -1,14 +1,131 @@
-use sqlx::PgPool;
 use crate::models::{AuditLog, NewAuditLog, AuditAction};
 use crate::error::AppError;
+use crate::dtos::{AuditEvent, AuditEventActor, AuditEventResource};
+use crate::state::AppState;
+use crate::request_id;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use uuid::Uuid;
 use tracing::{info, warn, error};
 
-pub async fn log_action(
-    pool: &PgPool,
-    log_entry: NewAuditLog,
+/// Replace metadata whose serialized form exceeds `max_bytes` with a small
+/// placeholder, so an oversized payload still leaves an audit row behind.
+fn cap_metadata(metadata: serde_json::Value, max_bytes: usize) -> serde_json::Value {
+    let size = match serde_json::to_vec(&metadata) {
+        Ok(bytes) => bytes.len(),
+        Err(_) => return metadata,
+    };
+
+    if size <= max_bytes {
+        return metadata;
+    }
+
+    warn!(
+        size_bytes = size,
+        max_bytes = max_bytes,
+        "Audit metadata exceeds size limit, storing truncated placeholder"
+    );
+
+    serde_json::json!({
+        "truncated": true,
+        "original_size_bytes": size,
+        "max_bytes": max_bytes,
+    })
+}
+
+/// Metadata key holding the `X-Request-Id` of the request that wrote the row
+const REQUEST_ID_KEY: &str = "request_id";
+
+/// Record the current request id in object metadata, so the row (and the
+/// audit event built from it) can be matched to the request's log lines.
+/// Added after capping; the id is at most 128 bytes.
+fn tag_request_id(metadata: &mut serde_json::Value, request_id: Option<String>) {
+    if let (Some(map), Some(id)) = (metadata.as_object_mut(), request_id) {
+        map.entry(REQUEST_ID_KEY).or_insert(serde_json::Value::String(id));
+    }
+}
+
+/// Labels `AuditAction` is written as; each must exist in the `audit_action` enum
+const AUDIT_ACTION_LABELS: &[&str] = &[
+    "UPLOAD",
+    "DOWNLOAD",
+    "UPDATE_METADATA",
+    "CREATE_VERSION",
+    "DELETE",
+    "RESTORE_VERSION",
+    "PROMOTE_VERSION",
+    "RESET_PASSWORD",
+    "ROTATE_API_KEY",
+    "RESTORE_DOCUMENT",
+    "CREATE_USER",
+    "UPDATE_USER_ROLE",
+    "DELETE_USER",
+];
+
+/// Columns `log_action` inserts into and returns from `audit_logs`
+const AUDIT_LOG_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "action",
+    "document_id",
+    "document_version",
+    "metadata",
+    "created_at",
+];
+
+/// Startup check that the `audit_logs` table and `audit_action` enum on the
+/// search path match what this build writes, so migration drift shows up at
+/// boot rather than as silently dropped audit rows. Returns a description of
+/// the first problem.
+pub async fn verify_schema(pool: &PgPool) -> Result<(), String> {
+    let labels: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT e.enumlabel::text
+        FROM pg_enum e
+        WHERE e.enumtypid = to_regtype('audit_action')
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("failed to read audit_action enum: {}", e))?;
+
+    if labels.is_empty() {
+        return Err("enum type audit_action is missing".to_string());
+    }
+    if let Some(missing) = AUDIT_ACTION_LABELS.iter().find(|l| !labels.iter().any(|x| x == *l)) {
+        return Err(format!("audit_action enum has no value {}", missing));
+    }
+
+    let columns: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT column_name::text
+        FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = 'audit_logs'
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("failed to read audit_logs columns: {}", e))?;
+
+    if columns.is_empty() {
+        return Err("table audit_logs is missing".to_string());
+    }
+    if let Some(missing) = AUDIT_LOG_COLUMNS.iter().find(|c| !columns.iter().any(|x| x == *c)) {
+        return Err(format!("audit_logs has no column {}", missing));
+    }
+
+    Ok(())
+}
+
+/// Insert an audit row using any executor: the pool, or an open transaction
+async fn insert_audit_log<'e, E: PgExecutor<'e>>(
+    executor: E,
+    max_bytes: usize,
+    mut log_entry: NewAuditLog,
 ) -> Result<AuditLog, AppError> {
 
+    log_entry.metadata = cap_metadata(log_entry.metadata, max_bytes);
+    tag_request_id(&mut log_entry.metadata, request_id::current());
+
     let audit_log = sqlx::query_as::<_, AuditLog>(
     r#"
         INSERT INTO audit_logs (user_id, action, document_id, document_version, metadata)
@@ -17,11 +134,11 @@ pub async fn log_action(
         "#
     )
     .bind(&log_entry.user_id)
-    .bind(&log_entry.action) 
-    .bind(&log_entry.document_id)
-    .bind(&log_entry.document_version)
+    .bind(log_entry.action)
+    .bind(log_entry.document_id)
+    .bind(log_entry.document_version)
     .bind(&log_entry.metadata)
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
     .map_err(|e| {
         error!(error = ?e, "Failed to insert audit log");
@@ -38,56 +155,197 @@ pub async fn log_action(
     Ok(audit_log)
 }
 
-pub async fn log_upload(
-    pool: &PgPool,
+pub async fn log_action(
+    state: &AppState,
+    log_entry: NewAuditLog,
+) -> Result<AuditLog, AppError> {
+    let audit_log = insert_audit_log(&state.pool, state.config.audit_metadata_max_bytes, log_entry).await?;
+    if state.config.audit_event_log {
+        emit_event(&audit_log);
+    }
+    Ok(audit_log)
+}
+
+/// Version of the [`AuditEvent`] field layout; bump on breaking changes
+pub const AUDIT_EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// The action as stored in `audit_action`, e.g. `UPDATE_METADATA`
+fn action_label(action: AuditAction) -> String {
+    let mut label = String::new();
+    for (i, c) in format!("{:?}", action).chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            label.push('_');
+        }
+        label.push(c.to_ascii_uppercase());
+    }
+    label
+}
+
+/// Normalize an audit row into the stable event schema
+pub fn audit_event(log: &AuditLog) -> AuditEvent {
+    let action = action_label(log.action);
+
+    // Failed attempts are recorded by `log_failure` with an outcome marker
+    let outcome = match log.metadata.get("outcome").and_then(|v| v.as_str()) {
+        Some("failure") => "failure",
+        _ => "success",
+    };
+
+    AuditEvent {
+        schema_version: AUDIT_EVENT_SCHEMA_VERSION,
+        timestamp: log.created_at,
+        event_id: log.id,
+        trace_id: log
+            .metadata
+            .get(REQUEST_ID_KEY)
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        actor: AuditEventActor { id: log.user_id.clone() },
+        action,
+        resource: log.document_id.map(|id| AuditEventResource {
+            kind: "document",
+            id,
+            version: log.document_version,
+        }),
+        outcome,
+        attributes: log.metadata.clone(),
+    }
+}
+
+/// Write an audit row to the `audit_event` log target as one JSON record
+fn emit_event(log: &AuditLog) {
+    match serde_json::to_string(&audit_event(log)) {
+        Ok(event) => info!(target: "audit_event", event = %event, "audit event"),
+        Err(e) => warn!(error = ?e, audit_id = %log.id, "Failed to serialize audit event"),
+    }
+}
+
+/// Audit a mutation that runs in `tx`.
+///
+/// With AUDIT_FAIL_CLOSED the row is written inside the transaction and an
+/// error is returned so the caller rolls back. Otherwise nothing is written
+/// yet: the entry is handed back for [`log_deferred`] once the transaction
+/// has committed.
+pub async fn log_in_tx(
+    state: &AppState,
+    tx: &mut PgConnection,
+    log_entry: NewAuditLog,
+) -> Result<Option<NewAuditLog>, AppError> {
+    if !state.config.audit_fail_closed {
+        return Ok(Some(log_entry));
+    }
+
+    let audit_log = insert_audit_log(tx, state.config.audit_metadata_max_bytes, log_entry).await?;
+    if state.config.audit_event_log {
+        // Emitted before commit; a later rollback leaves the event without a row
+        emit_event(&audit_log);
+    }
+    Ok(None)
+}
+
+/// Write an entry deferred by [`log_in_tx`] (fail-open mode); errors are only logged
+pub async fn log_deferred(state: &AppState, log_entry: Option<NewAuditLog>) {
+    let Some(log_entry) = log_entry else {
+        return;
+    };
+
+    let action = log_entry.action;
+    let document_id = log_entry.document_id;
+    if let Err(e) = log_action(state, log_entry).await {
+        // Audit logging should not break the main functionality
+        warn!(
+            error = ?e,
+            action = ?action,
+            document_id = ?document_id,
+            "Failed to create audit log"
+        );
+    }
+}
+
+pub async fn log_download(
+    state: &AppState,
     user_id: String,
     document_id: Uuid,
-    document_version: i32,
+    document_version: Option<i32>,
     metadata: Option<serde_json::Value>,
 ) -> Result<AuditLog, AppError> {
     log_action(
-        pool,
+        state,
         NewAuditLog {
             user_id,
-            action: AuditAction::Upload,
+            action: AuditAction::Download,
             document_id: Some(document_id),
-            document_version: Some(document_version),
+            document_version,
             metadata: metadata.unwrap_or_else(|| serde_json::json!({})),
         },
     )
     .await
 }
 
-pub async fn log_download(
-    pool: &PgPool,
+pub async fn log_delete(
+    state: &AppState,
     user_id: String,
     document_id: Uuid,
-    document_version: Option<i32>,
+    metadata: Option<serde_json::Value>,
 ) -> Result<AuditLog, AppError> {
     log_action(
-        pool,
+        state,
         NewAuditLog {
             user_id,
-            action: AuditAction::Download,
+            action: AuditAction::Delete,
             document_id: Some(document_id),
-            document_version,
-            metadata: serde_json::json!({}),
+            document_version: None,
+            metadata: metadata.unwrap_or_else(|| serde_json::json!({})),
         },
     )
     .await
 }
 
-pub async fn log_delete(
-    pool: &PgPool,
+
+
+/// Record a failed or denied attempt when AUDIT_FAILURES is enabled. The
+/// outcome and reason go into `metadata`; errors here are only logged.
+pub async fn log_failure(
+    state: &AppState,
+    user_id: String,
+    action: AuditAction,
+    document_id: Option<Uuid>,
+    failure: &AppError,
+) {
+    if !state.config.audit_failures {
+        return;
+    }
+
+    if let Err(e) = log_action(
+        state,
+        NewAuditLog {
+            user_id,
+            action,
+            document_id,
+            document_version: None,
+            metadata: serde_json::json!({
+                "outcome": "failure",
+                "reason": failure.to_string(),
+            }),
+        },
+    )
+    .await
+    {
+        warn!(error = ?e, action = ?action, "Failed to create audit log for failed operation");
+    }
+}
+
+pub async fn log_update_metadata(
+    state: &AppState,
     user_id: String,
     document_id: Uuid,
     metadata: Option<serde_json::Value>,
 ) -> Result<AuditLog, AppError> {
     log_action(
-        pool,
+        state,
         NewAuditLog {
             user_id,
-            action: AuditAction::Delete,
+            action: AuditAction::UpdateMetadata,
             document_id: Some(document_id),
             document_version: None,
             metadata: metadata.unwrap_or_else(|| serde_json::json!({})),
@@ -96,3 +354,82 @@ pub async fn log_delete(
     .await
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn audit_row(metadata: serde_json::Value) -> AuditLog {
+        AuditLog {
+            id: Uuid::new_v4(),
+            user_id: "7d3c1a2e-0000-0000-0000-000000000001".to_string(),
+            action: AuditAction::Download,
+            document_id: Some(Uuid::nil()),
+            document_version: Some(3),
+            metadata,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn metadata_within_the_cap_is_kept() {
+        let metadata = json!({"file_name": "a.txt"});
+        let size = serde_json::to_vec(&metadata).unwrap().len();
+        assert_eq!(cap_metadata(metadata.clone(), size), metadata);
+    }
+
+    #[test]
+    fn oversized_metadata_is_replaced_by_a_placeholder() {
+        let metadata = json!({"note": "x".repeat(100)});
+        let size = serde_json::to_vec(&metadata).unwrap().len();
+
+        let capped = cap_metadata(metadata, size - 1);
+        assert_eq!(
+            capped,
+            json!({"truncated": true, "original_size_bytes": size, "max_bytes": size - 1})
+        );
+    }
+
+    #[test]
+    fn request_id_is_recorded_in_object_metadata() {
+        let mut metadata = json!({"file_name": "a.txt"});
+        tag_request_id(&mut metadata, Some("req-1".to_string()));
+        assert_eq!(metadata, json!({"file_name": "a.txt", "request_id": "req-1"}));
+
+        // Outside a request there is nothing to record
+        let mut metadata = json!({});
+        tag_request_id(&mut metadata, None);
+        assert_eq!(metadata, json!({}));
+    }
+
+    #[test]
+    fn event_carries_the_normalized_fields() {
+        let row = audit_row(json!({"request_id": "req-42", "file_name": "a.txt"}));
+        let event = serde_json::to_value(audit_event(&row)).unwrap();
+
+        assert_eq!(event["schema_version"], AUDIT_EVENT_SCHEMA_VERSION);
+        assert_eq!(event["event_id"], row.id.to_string());
+        assert_eq!(event["trace_id"], "req-42");
+        assert_eq!(event["actor"]["id"], row.user_id);
+        assert_eq!(event["action"], "DOWNLOAD");
+        assert_eq!(event["resource"], json!({"type": "document", "id": Uuid::nil(), "version": 3}));
+        assert_eq!(event["outcome"], "success");
+        assert_eq!(event["attributes"]["file_name"], "a.txt");
+    }
+
+    #[test]
+    fn action_labels_match_the_database_enum() {
+        assert_eq!(action_label(AuditAction::Upload), "UPLOAD");
+        assert_eq!(action_label(AuditAction::UpdateMetadata), "UPDATE_METADATA");
+        assert_eq!(action_label(AuditAction::RotateApiKey), "ROTATE_API_KEY");
+        assert!(AUDIT_ACTION_LABELS.contains(&action_label(AuditAction::RestoreDocument).as_str()));
+    }
+
+    #[test]
+    fn event_outcome_and_trace_id_follow_the_metadata() {
+        let row = audit_row(json!({"outcome": "failure", "reason": "forbidden"}));
+        let event = audit_event(&row);
+        assert_eq!(event.outcome, "failure");
+        assert_eq!(event.trace_id, None);
+    }
+}
@@ -1,22 +1,115 @@
 use axum::async_trait;
 use axum::extract::{FromRef, FromRequestParts};
+use axum::http::header;
 use axum::http::request::Parts;
-use tracing::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use sqlx::{Decode, Encode, Postgres};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::error::AppError;
 use crate::models::User;
 use crate::state::AppState;
+use crate::token::verify_token;
 
 /// Represents the currently authenticated user
 #[derive(Debug, Clone)]
 pub struct CurrentUser {
     pub id: Uuid,
     pub username: String,
-    pub role: String,
+    pub role: Role,
+    /// PERMISSION_MATRIX overrides of the config the user authenticated against
+    pub permissions: Arc<PermissionMatrix>,
 }
 
-/// Extract CurrentUser from the X-API-Key header
+/// Role stored in `users.role`. Rows holding anything else fail to decode,
+/// so a typo surfaces as a rejected login instead of silently denying access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only
+    Viewer,
+    /// Read and write
+    Editor,
+    /// Everything, including deletes and maintenance endpoints
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+// Stored as plain text in `users.role`, so borrow the `str` mapping
+impl sqlx::Type<Postgres> for Role {
+    fn type_info() -> PgTypeInfo {
+        <str as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <str as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Role {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let raw = <&str as Decode<Postgres>>::decode(value)?;
+        Ok(raw.parse()?)
+    }
+}
+
+impl Encode<'_, Postgres> for Role {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.as_str(), buf)
+    }
+}
+
+/// A `users` row whose role is not a known `Role` fails to decode; turn that
+/// into a clear rejection (and a loud log line) rather than a 500
+pub fn map_user_load_error(e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::ColumnDecode { index, source } => {
+            error!(column = %index, error = %source, "User row has an invalid value; fix users.role");
+            AppError::Forbidden("Account has an unrecognized role; contact an administrator")
+        }
+        other => AppError::Db(other),
+    }
+}
+
+/// Extract CurrentUser from an `Authorization: Bearer` access token (when
+/// JWT_SECRET is set) or the X-API-Key header
 #[async_trait]
 impl<S> FromRequestParts<S> for CurrentUser
 where
@@ -29,6 +122,46 @@ where
         // Get AppState from the router state
         let app_state = AppState::from_ref(state);
 
+        if app_state.config.test_auth_bypass {
+            if let Some(role) = parts.headers.get(TEST_USER_HEADER).and_then(|v| v.to_str().ok()) {
+                return test_user(&app_state, role).await;
+            }
+        }
+
+        // The token proves who the caller was at login; the account is looked
+        // up again so deleted users and changed roles don't outlive it
+        if let Some(secret) = &app_state.config.jwt_secret {
+            let bearer = parts
+                .headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "));
+            if let Some(token) = bearer {
+                let permissions = app_state.config.permission_overrides.clone();
+                let user = verify_token(secret, token.trim(), permissions).inspect_err(|e| {
+                    warn!(error = %e, "Rejected bearer token");
+                })?;
+                let role: Option<Role> = sqlx::query_scalar("SELECT role FROM users WHERE id = $1")
+                    .bind(user.id)
+                    .fetch_optional(&app_state.pool)
+                    .await
+                    .map_err(map_user_load_error)?;
+                match role {
+                    None => {
+                        warn!(user_id = %user.id, "Rejected bearer token of a deleted user");
+                        return Err(AppError::Unauthorized("Account no longer exists"));
+                    }
+                    Some(role) if role != user.role => {
+                        warn!(user_id = %user.id, token_role = %user.role, role = %role, "Rejected bearer token issued before a role change");
+                        return Err(AppError::Unauthorized("Access token is out of date; log in again"));
+                    }
+                    Some(_) => {}
+                }
+                debug!(user_id = %user.id, username = %user.username, role = %user.role, "User authenticated via bearer token");
+                return Ok(user);
+            }
+        }
+
         // Extract X-API-Key header
         let api_key = parts
             .headers
@@ -36,7 +169,7 @@ where
             .and_then(|v| v.to_str().ok())
             .ok_or_else(|| {
                 warn!("Missing X-API-Key header");
-                AppError::BadRequest("Missing X-API-Key header")
+                AppError::Unauthorized("Missing X-API-Key header")
             })?;
 
         debug!(api_key = %api_key, "Authenticating user with API key");
@@ -52,7 +185,7 @@ where
         .bind(api_key)
         .fetch_optional(&app_state.pool)
         .await
-        .map_err(AppError::Db)?;
+        .map_err(map_user_load_error)?;
 
         match user {
             Some(u) => {
@@ -61,72 +194,187 @@ where
                     id: u.id,
                     username: u.username,
                     role: u.role,
+                    permissions: app_state.config.permission_overrides.clone(),
                 })
             }
             None => {
                 warn!(api_key = %api_key, "Invalid API key");
-                Err(AppError::BadRequest("Invalid API key"))
+                Err(AppError::Unauthorized("Invalid API key"))
             }
         }
     }
 }
 
+/// Header naming the role to act as when TEST_AUTH_BYPASS is on
+pub const TEST_USER_HEADER: &str = "X-Test-User";
+
+/// Username prefix of the auth bypass users; `POST /users` refuses it
+pub const TEST_USER_PREFIX: &str = "~test-";
+
+/// Resolve the synthetic `~test-{role}` user for the auth bypass. The row is
+/// inserted (instead of faked) so foreign keys such as `documents.created_by`
+/// stay valid, and the same role always maps to the same user id. An
+/// existing row is reused only if it has the requested role; it is never
+/// re-roled.
+async fn test_user(state: &AppState, role: &str) -> Result<CurrentUser, AppError> {
+    let role: Role = role
+        .parse()
+        .map_err(|_| AppError::BadRequest("X-Test-User must be viewer, editor or admin"))?;
+
+    let username = format!("{}{}", TEST_USER_PREFIX, role);
+    // The no-op update makes RETURNING yield the existing row's id
+    let id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (username, api_key, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO UPDATE SET username = EXCLUDED.username
+        WHERE users.role = EXCLUDED.role
+        RETURNING id
+        "#,
+    )
+    .bind(&username)
+    .bind(Uuid::new_v4().to_string())
+    .bind(role)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let Some(id) = id else {
+        warn!(username = %username, role = %role, "TEST_AUTH_BYPASS user exists with a different role");
+        return Err(AppError::Conflict("X-Test-User account exists with a different role"));
+    };
+
+    warn!(user_id = %id, role = %role, "Authenticated via TEST_AUTH_BYPASS");
+
+    Ok(CurrentUser {
+        id,
+        username,
+        role,
+        permissions: state.config.permission_overrides.clone(),
+    })
+}
+
 /// Storage actions that require permission checks
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum StorageAction {
     Read,
     Write,
     Delete,
     Stat,
     GetActions,
+    Admin,
+}
+
+impl FromStr for StorageAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Ok(StorageAction::Read),
+            "write" => Ok(StorageAction::Write),
+            "delete" => Ok(StorageAction::Delete),
+            "stat" => Ok(StorageAction::Stat),
+            "get_actions" => Ok(StorageAction::GetActions),
+            "admin" => Ok(StorageAction::Admin),
+            other => Err(format!("unknown storage action: {}", other)),
+        }
+    }
+}
+
+/// Per-action role lists replacing the defaults below (PERMISSION_MATRIX)
+pub type PermissionMatrix = HashMap<StorageAction, Vec<Role>>;
+
+/// Whether the user's role is granted `action` by the overrides; None when not overridden
+fn overridden(user: &CurrentUser, action: StorageAction) -> Option<bool> {
+    user.permissions
+        .get(&action)
+        .map(|roles| roles.contains(&user.role))
 }
 
 /// Check if a user has permission for a specific storage action
 pub fn check_permission(user: &CurrentUser, action: StorageAction) -> Result<(), AppError> {
+    let Some(allowed) = overridden(user, action) else {
+        return default_permission(user, action);
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(denied_message(action)))
+    }
+}
+
+fn denied_message(action: StorageAction) -> &'static str {
     match action {
-        StorageAction::Read => {
-            // viewer, editor, admin can all read
-            if user.role == "viewer" || user.role == "editor" || user.role == "admin" {
-                Ok(())
-            } else {
-                Err(AppError::BadRequest(
-                    "Permission denied: read access required",
-                ))
-            }
-        }
-        StorageAction::Write => {
-            // only editor and admin can write
-            if user.role == "editor" || user.role == "admin" {
-                Ok(())
-            } else {
-                Err(AppError::BadRequest(
-                    "Permission denied: write access required",
-                ))
-            }
-        }
-        StorageAction::Delete => {
-            // only admin can delete
-            if user.role == "admin" {
-                Ok(())
-            } else {
-                Err(AppError::BadRequest(
-                    "Permission denied: admin access required",
-                ))
-            }
-        }
-        StorageAction::Stat => {
-            // same as read for now
-            check_permission(user, StorageAction::Read)
+        StorageAction::Read | StorageAction::Stat => "Permission denied: read access required",
+        StorageAction::Write => "Permission denied: write access required",
+        StorageAction::Delete => "Permission denied: delete access required",
+        StorageAction::GetActions | StorageAction::Admin => "Permission denied: admin access required",
+    }
+}
+
+/// Built-in role mapping, used for every action PERMISSION_MATRIX leaves out
+fn default_permission(user: &CurrentUser, action: StorageAction) -> Result<(), AppError> {
+    let allowed = match action {
+        // viewer, editor, admin can all read
+        StorageAction::Read => true,
+        // same as read for now, including any override of read
+        StorageAction::Stat => return check_permission(user, StorageAction::Read),
+        // only editor and admin can write
+        StorageAction::Write => matches!(user.role, Role::Editor | Role::Admin),
+        // deletes, the audit trail and maintenance endpoints are admin only
+        StorageAction::Delete | StorageAction::GetActions | StorageAction::Admin => {
+            user.role == Role::Admin
         }
-        StorageAction::GetActions => {
-            // only admin can reach to actions
-            if user.role == "admin" {
-                Ok(())
-            } else {
-                Err(AppError::BadRequest(
-                    "Permission denied: admin access required",
-                ))
-            }
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(denied_message(action)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_permission_matrix;
+
+    fn editor(permissions: PermissionMatrix) -> CurrentUser {
+        CurrentUser {
+            id: Uuid::nil(),
+            username: "editor".to_string(),
+            role: Role::Editor,
+            permissions: Arc::new(permissions),
         }
     }
+
+    #[test]
+    fn editors_cannot_delete_by_default() {
+        let result = check_permission(&editor(PermissionMatrix::new()), StorageAction::Delete);
+        assert!(matches!(result, Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn overridden_matrix_lets_an_editor_delete() {
+        let matrix = parse_permission_matrix("delete=editor|admin").unwrap();
+        assert!(check_permission(&editor(matrix), StorageAction::Delete).is_ok());
+    }
+
+    #[test]
+    fn actions_left_out_of_the_matrix_keep_their_defaults() {
+        let matrix = parse_permission_matrix("delete=editor").unwrap();
+        let user = editor(matrix);
+        assert!(check_permission(&user, StorageAction::Write).is_ok());
+        assert!(matches!(check_permission(&user, StorageAction::Admin), Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn overrides_can_also_revoke() {
+        let matrix = parse_permission_matrix("write=admin").unwrap();
+        assert!(matches!(
+            check_permission(&editor(matrix), StorageAction::Write),
+            Err(AppError::Forbidden(_))
+        ));
+    }
 }
@@ -0,0 +1,93 @@
+use std::future::Future;
+use std::time::Instant;
+use tracing::{debug, warn};
+
+/// Timing wrapper for SQL futures: logs a warning when a query takes at least
+/// `threshold_ms` (SLOW_QUERY_MS). A threshold of 0 disables the warning.
+pub trait TimedQuery: Future + Sized {
+    fn timed(self, label: &'static str, threshold_ms: u64) -> impl Future<Output = Self::Output> {
+        async move {
+            let started = Instant::now();
+            let output = self.await;
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+
+            if threshold_ms > 0 && elapsed_ms >= threshold_ms {
+                warn!(
+                    query = label,
+                    elapsed_ms = elapsed_ms,
+                    threshold_ms = threshold_ms,
+                    "Slow query"
+                );
+            } else {
+                debug!(query = label, elapsed_ms = elapsed_ms, "Query finished");
+            }
+
+            output
+        }
+    }
+}
+
+impl<F: Future> TimedQuery for F {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Log sink shared with the subscriber under test
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Time a `sleep_ms` sleep standing in for a query and return what was logged
+    async fn logged(sleep_ms: u64, threshold_ms: u64) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        tokio::time::sleep(Duration::from_millis(sleep_ms))
+            .timed("test.sleep", threshold_ms)
+            .await;
+
+        let bytes = captured.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn slow_query_logs_a_warning() {
+        let log = logged(30, 10).await;
+        assert!(log.contains("WARN"), "{}", log);
+        assert!(log.contains("Slow query"), "{}", log);
+        assert!(log.contains("query=\"test.sleep\""), "{}", log);
+    }
+
+    #[tokio::test]
+    async fn fast_query_only_logs_at_debug() {
+        let log = logged(0, 10_000).await;
+        assert!(!log.contains("Slow query"), "{}", log);
+        assert!(log.contains("Query finished"), "{}", log);
+    }
+
+    #[tokio::test]
+    async fn zero_threshold_disables_the_warning() {
+        let log = logged(5, 0).await;
+        assert!(!log.contains("Slow query"), "{}", log);
+    }
+}
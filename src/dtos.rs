@@ -1,4 +1,5 @@
-use crate::models::AuditLog;
+use crate::auth::Role;
+use crate::models::{AuditLog, Document, DocumentVersion, Notification, Tag};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -13,6 +14,44 @@ pub struct UploadResponse {
     pub metadata_message: String,
 }
 
+/// Open a resumable upload. Takes the same fields as the multipart
+/// `POST /upload`, minus the file itself.
+#[derive(Deserialize, ToSchema)]
+pub struct InitUploadRequest {
+    /// Add a new version to this document instead of creating one
+    pub document_id: Option<Uuid>,
+    /// Required when creating a new document
+    pub title: Option<String>,
+    pub category: Option<String>,
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub metadata: std::collections::HashMap<String, String>,
+}
+
+/// Progress of a resumable upload; chunks are numbered from 0
+#[derive(Serialize, ToSchema)]
+pub struct UploadSessionResponse {
+    pub upload_id: Uuid,
+    pub document_id: Option<Uuid>,
+    pub title: Option<String>,
+    pub file_name: Option<String>,
+    /// Chunk numbers stored so far, ascending; resend any gaps
+    pub received_chunks: Vec<i32>,
+    pub received_bytes: i64,
+    /// Largest chunk accepted by `PUT /uploads/{id}/chunk/{n}`
+    pub max_chunk_bytes: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Resumable uploads discarded by `POST /admin/uploads/expire`
+#[derive(Serialize, ToSchema)]
+pub struct ExpiredUploadsResponse {
+    pub expired: Vec<Uuid>,
+    /// Idle time after which a session expires (UPLOAD_SESSION_TTL_SECS); 0 disables expiry
+    pub ttl_secs: u64,
+}
+
 #[derive(Serialize, FromRow, ToSchema)]
 pub struct DocumentWithLatest {
     pub id: Uuid,
@@ -25,6 +64,14 @@ pub struct DocumentWithLatest {
     pub latest_file_size: Option<i64>,
     pub latest_mime_type: Option<String>,
     pub latest_created_at: Option<DateTime<Utc>>,
+    /// Metadata key/value pairs; only present with `include=metadata`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<std::collections::BTreeMap<String, String>>,
+    /// Tags, alphabetical; only present with `include=tags`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<Tag>>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -33,6 +80,103 @@ pub struct ListDocumentsResponse {
     pub page: u32,
     pub page_size: u32,
     pub total: i64,
+    pub total_pages: u32,
+    pub has_next: bool,
+    pub has_prev: bool,
+}
+
+/// Upload activity of one user across non-deleted documents
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct UploaderStats {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String,
+    /// Documents the user created
+    pub document_count: i64,
+    /// Versions the user uploaded; older versions count for the document's creator
+    pub version_count: i64,
+    /// Combined size of those versions
+    pub total_bytes: i64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SearchQuery {
+    pub q: String,
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub document: DocumentWithLatest,
+    /// `ts_rank` of the match; higher is more relevant
+    pub score: f32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub query: String,
+    pub data: Vec<SearchHit>,
+    pub page: u32,
+    pub page_size: u32,
+    pub total: i64,
+    pub total_pages: u32,
+    pub has_next: bool,
+    pub has_prev: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RecentVersionsQuery {
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+/// A version in the global activity feed, with its document's title
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct RecentVersion {
+    pub document_id: Uuid,
+    pub title: String,
+    pub category: Option<String>,
+    pub version_number: i32,
+    pub file_name: String,
+    pub file_size: i64,
+    pub mime_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecentVersionsResponse {
+    pub data: Vec<RecentVersion>,
+    pub page: u32,
+    pub page_size: u32,
+    pub total: i64,
+    pub total_pages: u32,
+    pub has_next: bool,
+    pub has_prev: bool,
+}
+
+/// Number of pages needed to hold `total` items (0 when there are none)
+pub fn total_pages(total: i64, page_size: u32) -> u32 {
+    if total <= 0 || page_size == 0 {
+        return 0;
+    }
+    (total as u64).div_ceil(page_size as u64) as u32
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RemoveTagQuery {
+    /// Also delete the tag itself if no document carries it any more (default false)
+    pub delete_orphan: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RemoveTagResponse {
+    pub document_id: Uuid,
+    pub tag_id: Uuid,
+    /// True when the tag was left unused and deleted
+    pub tag_deleted: bool,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -41,48 +185,181 @@ pub struct ListDocumentsQuery {
     pub page_size: Option<u32>,
     pub title: Option<String>,
     pub category: Option<String>,
+    /// Tag names a document must all carry; repeat `tag=` for several.
+    /// Collected from the raw query pairs, since a struct field can't hold
+    /// repeated keys.
+    #[serde(skip)]
+    pub tag: Vec<String>,
+    /// Also list template documents (default false)
+    pub include_templates: Option<bool>,
+    /// Only documents filed in this folder (exact path, e.g. `finance/2024`)
+    pub folder: Option<String>,
+    /// Column to sort by (default `created_at`)
+    pub sort_by: Option<DocumentSortField>,
+    /// Sort direction (default `desc`)
+    pub order: Option<SortOrder>,
+    /// Only documents created at or after this RFC 3339 timestamp
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only documents created before this RFC 3339 timestamp
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only documents updated at or after this RFC 3339 timestamp
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Only documents updated before this RFC 3339 timestamp
+    pub updated_before: Option<DateTime<Utc>>,
+    /// Comma-separated extras to embed in each document: `metadata`, `tags`
+    pub include: Option<String>,
+    /// Metadata key/value pairs a document must all have, from repeated
+    /// `meta.<key>=<value>` parameters. Collected from the raw query pairs
+    /// like `tag`.
+    #[serde(skip)]
+    pub meta: Vec<(String, String)>,
+}
+
+/// Columns `GET /documents` can sort by. Only these map into the ORDER BY
+/// clause, so the query string never reaches the SQL text.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+impl DocumentSortField {
+    pub fn column(self) -> &'static str {
+        match self {
+            DocumentSortField::CreatedAt => "d.created_at",
+            DocumentSortField::UpdatedAt => "d.updated_at",
+            DocumentSortField::Title => "d.title",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateDocumentRequest {
+    /// New title; must not be blank
+    pub title: Option<String>,
+    /// New category; an empty string clears it
+    pub category: Option<String>,
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct DownloadQuery {
     pub version: Option<i32>,
+    /// Re-hash the content against the stored checksum (default true)
+    pub verify: Option<bool>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct AuditResponse {
     pub data: Vec<AuditLog>,
     pub total: i64,
+    /// Newest row seen (or the cursor, if nothing new); pass it back as `since_id`
+    pub newest_id: Option<Uuid>,
+    pub newest_created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AuditQuery {
+    /// Only rows newer than this audit row; results are then oldest first
+    pub since_id: Option<Uuid>,
+    /// Only rows created after this time; results are then oldest first
+    pub since: Option<DateTime<Utc>>,
+    /// Maximum rows returned when polling with a cursor (default 500, max 1000)
+    pub limit: Option<u32>,
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct CreateFolderRequest {
+    /// Name of the new folder; a single path segment
     pub name: String,
+    /// Existing folder to create it under, e.g. `finance/2024`
+    #[serde(default)]
+    pub parent: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct CreateFolderResponse {
+    /// Full path of the folder, including its parents
     pub folder_name: String,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct LinkFolderRequest {
+    /// Full path of an existing folder, e.g. `finance/2024`
+    pub folder: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LinkFolderResponse {
+    pub document_id: Uuid,
+    /// Normalized folder path the document is now filed under
+    pub folder: String,
+    /// False when the document was already in this folder
+    pub linked: bool,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct AddTagToDocumentRequest {
     pub document_id: Uuid,
     pub tags: Vec<String>,
 }
 
+/// Link existing tags to a document by id; unknown ids are rejected rather
+/// than created
+#[derive(Deserialize, ToSchema)]
+pub struct AddTagIdsRequest {
+    pub tag_ids: Vec<Uuid>,
+}
+
+/// What happened to a single tag in an add-tags request
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TagStatus {
+    /// Existing tag newly linked to the document
+    Added,
+    /// Tag was already linked to the document
+    AlreadyPresent,
+    /// Tag did not exist; it was created and linked
+    Created,
+    /// Blank tag name, ignored
+    SkippedEmpty,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct TagInfo {
-    pub tag_id: Uuid,
+    /// None when the tag was skipped
+    pub tag_id: Option<Uuid>,
     pub tag_name: String,
     pub tag_created: bool,
+    pub status: TagStatus,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct AddTagToDocumentResponse {
     pub document_id: Uuid,
     pub tags: Vec<TagInfo>,
+    /// Number of tags now associated with the document (skipped tags excluded)
     pub total: usize,
 }
 
@@ -92,16 +369,83 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// New password; omit to have one generated
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResetPasswordResponse {
+    pub user_id: Uuid,
+    /// Only present when the server generated the password; shown this once
+    pub generated_password: Option<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub api_key: String,
     pub username: String,
     pub user_id: Uuid,
+    pub role: Role,
+    /// Short-lived bearer token; only issued when JWT_SECRET is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    /// When `access_token` stops being accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RotateKeyResponse {
+    /// The new key; the previous one no longer authenticates
+    pub api_key: String,
+    pub username: String,
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct UserInfo {
+    pub id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateUserRequest {
+    pub username: String,
+    /// One of `viewer`, `editor`, `admin`
     pub role: String,
+    /// Initial password; omit to have one generated
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateUserResponse {
+    pub user: UserInfo,
+    /// Generated key; shown this once
+    pub api_key: String,
+    /// Only present when the server generated the password; shown this once
+    pub generated_password: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateUserRoleRequest {
+    /// One of `viewer`, `editor`, `admin`
+    pub role: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListUsersResponse {
+    pub users: Vec<UserInfo>,
+    pub total: usize,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct FolderInfo {
+    /// Full path of the folder, including its parents
     pub folder_name: String,
     pub created_by: Uuid,
     pub created_by_username: String,
@@ -112,4 +456,627 @@ pub struct FolderInfo {
 pub struct ListFoldersResponse {
     pub folders: Vec<FolderInfo>,
     pub total: usize,
+    /// True when `depth` or `limit` cut the listing short
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ListFoldersQuery {
+    /// Only list folders below this one, e.g. `finance/2024`
+    pub parent: Option<String>,
+    /// Levels to descend below the root or `parent` (default 1, max 10)
+    pub depth: Option<u32>,
+    /// Maximum folders returned (default 1000, max 5000)
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WatchResponse {
+    pub document_id: Uuid,
+    pub watching: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NotificationsResponse {
+    pub data: Vec<Notification>,
+    pub total: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RepairMimeTypesRequest {
+    /// Start after this version id, e.g. to skip versions already repaired
+    pub after_id: Option<Uuid>,
+    /// Versions per batch (default 100, max 1000)
+    pub batch_size: Option<u32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AuditEventsQuery {
+    /// Only events created at or after this RFC 3339 timestamp
+    pub since: Option<DateTime<Utc>>,
+    /// Only events created before this RFC 3339 timestamp
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Normalized audit record for log pipelines. Field names are a stable
+/// contract (see `schema_version`) independent of the audit_logs table.
+#[derive(Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub schema_version: u32,
+    pub timestamp: DateTime<Utc>,
+    /// Audit row id
+    pub event_id: Uuid,
+    /// `X-Request-Id` of the request that caused the event, also recorded as
+    /// `request_id` on its `http_request` log span; null for background jobs
+    pub trace_id: Option<String>,
+    pub actor: AuditEventActor,
+    /// Audit action, e.g. `DOWNLOAD`
+    pub action: String,
+    pub resource: Option<AuditEventResource>,
+    /// `success` or `failure`
+    pub outcome: &'static str,
+    /// Action-specific details from the audit row
+    pub attributes: serde_json::Value,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEventActor {
+    /// User id, or a marker such as `anonymous` for unauthenticated access
+    pub id: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEventResource {
+    /// Always `document` today
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: Uuid,
+    pub version: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AuditExportQuery {
+    /// Export format; only `csv` is supported (default: csv)
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OrphanedDocumentsQuery {
+    /// Soft-delete the reported documents (default: false)
+    pub cleanup: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OrphanedDocumentsResponse {
+    pub data: Vec<Document>,
+    pub total: usize,
+    /// Number of documents soft-deleted by this call
+    pub cleaned_up: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VersionDiffQuery {
+    /// Version to compare from
+    pub from: i32,
+    /// Version to compare to
+    pub to: i32,
+    /// Unchanged lines shown around each change (default: 3, max: 20)
+    pub context: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionDiffResponse {
+    pub document_id: Uuid,
+    pub from_version: i32,
+    pub to_version: i32,
+    pub identical: bool,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    /// Unified diff of `from` against `to`; empty when identical
+    pub diff: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PreviewQuery {
+    pub version: Option<i32>,
+    /// Maximum rows (CSV) or lines (text) to return (default: 20, max: 100)
+    pub rows: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DocumentPreviewResponse {
+    pub document_id: Uuid,
+    pub version_number: i32,
+    pub mime_type: String,
+    /// `csv` or `text`
+    pub kind: String,
+    /// CSV header row
+    pub headers: Option<Vec<String>>,
+    /// CSV sample rows
+    pub rows: Option<Vec<Vec<String>>>,
+    /// Leading lines of a text file
+    pub lines: Option<Vec<String>>,
+    /// True when the preview does not cover the whole file
+    pub truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionFootprint {
+    pub version_number: i32,
+    pub storage_key: String,
+    /// Size recorded in the database
+    pub file_size: i64,
+    /// Size reported by storage; None when the object is missing
+    pub stored_size: Option<u64>,
+    pub missing: bool,
+    pub size_mismatch: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DocumentFootprintResponse {
+    pub document_id: Uuid,
+    pub versions: Vec<VersionFootprint>,
+    pub total_file_size: i64,
+    pub total_stored_size: u64,
+    pub mismatches: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RekeyedVersion {
+    pub version_number: i32,
+    pub old_key: String,
+    pub new_key: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RekeyDocumentResponse {
+    pub document_id: Uuid,
+    pub rekeyed: Vec<RekeyedVersion>,
+    /// Versions already stored under the current key strategy
+    pub unchanged: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateAliasRequest {
+    /// Lifetime of the alias in seconds; omit for a non-expiring alias
+    pub expires_in_secs: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateAliasResponse {
+    pub slug: String,
+    pub document_id: Uuid,
+    /// Relative URL that resolves the alias
+    pub url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetVisibilityRequest {
+    pub is_public: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VisibilityResponse {
+    pub document_id: Uuid,
+    pub is_public: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetTemplateRequest {
+    pub is_template: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TemplateResponse {
+    pub document_id: Uuid,
+    pub is_template: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FromTemplateRequest {
+    /// Title of the new document; defaults to the template's
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FromTemplateResponse {
+    pub template_id: Uuid,
+    pub document: Document,
+    /// Version 1, a copy of the template's latest version
+    pub version: DocumentVersion,
+    pub metadata_copied: u64,
+    pub tags_copied: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkMetadataRequest {
+    pub document_ids: Vec<Uuid>,
+    /// Key/value pairs upserted onto every listed document
+    pub metadata: std::collections::HashMap<String, String>,
+}
+
+/// What happened to a single document in a bulk metadata request
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMetadataStatus {
+    /// Metadata upserted onto the document
+    Applied,
+    /// Document does not exist or is soft-deleted, skipped
+    NotFound,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkMetadataResult {
+    pub document_id: Uuid,
+    pub status: BulkMetadataStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkMetadataResponse {
+    pub results: Vec<BulkMetadataResult>,
+    pub applied: usize,
+    pub skipped: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchDeleteRequest {
+    pub ids: Vec<Uuid>,
+    /// Permanently delete rows and stored files instead of soft-deleting
+    #[serde(default)]
+    pub hard: bool,
+}
+
+/// What happened to a single document in a batch delete
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchDeleteStatus {
+    Deleted,
+    /// Soft delete requested but the document already was
+    AlreadyDeleted,
+    NotFound,
+    /// Hard-deleted, but some stored files could not be removed and were left behind
+    StorageFailed,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchDeleteResult {
+    pub document_id: Uuid,
+    pub status: BatchDeleteStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchDeleteResponse {
+    pub results: Vec<BatchDeleteResult>,
+    /// Documents removed, including `storage_failed` ones
+    pub deleted: usize,
+    pub skipped: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ArchiveRequest {
+    pub ids: Vec<Uuid>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkRestoreRequest {
+    pub ids: Vec<Uuid>,
+}
+
+/// What happened to a single document in a bulk restore
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkRestoreStatus {
+    /// `deleted_at` cleared
+    Restored,
+    /// Document exists but was not soft-deleted
+    NotDeleted,
+    /// No such document
+    NotFound,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkRestoreResult {
+    pub document_id: Uuid,
+    pub status: BulkRestoreStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkRestoreResponse {
+    pub results: Vec<BulkRestoreResult>,
+    pub restored: usize,
+    pub skipped: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DedupReportQuery {
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+/// Versions sharing one checksum but stored as separate objects
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct DuplicateContentGroup {
+    pub checksum: String,
+    /// Distinct storage objects holding this content
+    pub object_count: i64,
+    pub document_ids: Vec<Uuid>,
+    pub file_size: i64,
+    /// Bytes freed by keeping a single copy
+    pub reclaimable_bytes: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DedupReportResponse {
+    pub data: Vec<DuplicateContentGroup>,
+    pub page: u32,
+    pub page_size: u32,
+    /// Number of duplicate groups across all pages
+    pub total: i64,
+    pub total_pages: u32,
+    /// Reclaimable bytes across all pages
+    pub total_reclaimable_bytes: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionIntegrity {
+    pub version_number: i32,
+    /// Recorded SHA-256 of the content; None for versions uploaded before checksums were kept
+    pub checksum: Option<String>,
+    /// Chain hash up to and including this version
+    pub chain_hash: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DocumentIntegrityResponse {
+    pub document_id: Uuid,
+    pub algorithm: &'static str,
+    /// Chain hash of the last version; changes if any version is altered, removed or reordered
+    pub root_hash: String,
+    pub versions: Vec<VersionIntegrity>,
+    /// False when some versions have no recorded checksum
+    pub complete: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PromoteVersionResponse {
+    pub document_id: Uuid,
+    /// Version now served when no `?version=` is given
+    pub current_version: i32,
+    /// Previously promoted version, if any
+    pub previous_version: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PruneVersionsRequest {
+    /// Number of newest versions to keep; 0 prunes down to nothing
+    pub keep: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PruneVersionsResponse {
+    pub document_id: Uuid,
+    /// Version numbers removed from the database and storage
+    pub pruned_versions: Vec<i32>,
+    pub remaining_versions: usize,
+    /// True when the prune would have emptied the document, so its last
+    /// version was kept and the document soft-deleted instead
+    pub soft_deleted: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MigrateKeyNamespacesRequest {
+    /// Documents per batch (default 50, max 500)
+    pub batch_size: Option<u32>,
+    /// Documents to leave alone, e.g. ones an earlier run could not migrate
+    pub skip: Option<Vec<Uuid>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateSignedLinkRequest {
+    /// Lifetime of the link in seconds (default 3600, max 7 days)
+    pub expires_in_secs: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SignedLinkResponse {
+    pub document_id: Uuid,
+    pub url: String,
+    pub sig: String,
+    /// Expiry as unix seconds, the `exp` query parameter
+    pub exp: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SignedLinkQuery {
+    pub sig: String,
+    pub exp: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SignedLinkVerification {
+    pub document_id: Uuid,
+    pub valid: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SimulateAccessQuery {
+    pub user_id: Uuid,
+    pub document_id: Uuid,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SimulatedAction {
+    pub action: &'static str,
+    pub allowed: bool,
+    /// Why the action would be denied
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SimulateAccessResponse {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub document_id: Uuid,
+    /// False when the document is missing or soft-deleted; document actions then fail with 404
+    pub document_available: bool,
+    pub actions: Vec<SimulatedAction>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ExplainQuery {
+    /// Endpoint whose query to explain: `list_documents`, `list_versions` or `document_metadata`
+    pub endpoint: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ExplainResponse {
+    pub endpoint: String,
+    /// The SQL that was explained
+    pub query: String,
+    /// Sample parameters bound to it, in order
+    pub params: Vec<String>,
+    /// Output of `EXPLAIN (ANALYZE, FORMAT JSON)`
+    #[schema(value_type = Object)]
+    pub plan: serde_json::Value,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DocumentMetadataResponse {
+    pub document_id: Uuid,
+    /// Every metadata entry of the document after the update
+    pub metadata: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FacetsQuery {
+    /// `category`, `tag` or `meta:<key>`
+    pub field: String,
+    /// Maximum facet values (default 100, max 1000)
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct FacetValue {
+    pub value: String,
+    /// Non-deleted documents carrying this value
+    pub document_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FacetsResponse {
+    pub field: String,
+    pub values: Vec<FacetValue>,
+    /// True when more values exist beyond `limit`
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MetadataKeysQuery {
+    /// Only keys starting with this prefix
+    pub prefix: Option<String>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct MetadataKeyUsage {
+    pub key: String,
+    /// Non-deleted documents carrying the key
+    pub document_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MetadataKeysResponse {
+    pub keys: Vec<MetadataKeyUsage>,
+    pub total: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionResponse {
+    /// Crate version from Cargo.toml
+    pub version: &'static str,
+    /// Commit the binary was built from, or "unknown" outside a git checkout
+    pub git_commit: &'static str,
+    pub built_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    /// Always "ok"; the process is up and serving requests
+    pub status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyCheck {
+    pub ok: bool,
+    pub latency_ms: u64,
+    /// Why the check failed; absent when `ok`
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// "ready" when every dependency answered, otherwise "unavailable"
+    pub status: &'static str,
+    pub database: DependencyCheck,
+    pub storage: DependencyCheck,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    /// Stopped early after `POST /admin/jobs/{id}/cancel`
+    Cancelled,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct JobInfo {
+    pub id: Uuid,
+    /// Operation the job runs, e.g. "backup" or "repair_mime_types"
+    pub kind: &'static str,
+    pub status: JobStatus,
+    pub started_by: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Items handled so far; what an item is depends on `kind`
+    pub processed: u64,
+    /// Items the job expects to handle, once known
+    pub total: Option<u64>,
+    pub cancel_requested: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListJobsResponse {
+    pub jobs: Vec<JobInfo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::total_pages;
+
+    #[test]
+    fn total_pages_is_zero_without_items() {
+        assert_eq!(total_pages(0, 20), 0);
+        assert_eq!(total_pages(-1, 20), 0);
+        assert_eq!(total_pages(5, 0), 0);
+    }
+
+    #[test]
+    fn total_pages_on_exact_multiple() {
+        assert_eq!(total_pages(20, 20), 1);
+        assert_eq!(total_pages(60, 20), 3);
+    }
+
+    #[test]
+    fn total_pages_rounds_up_remainder() {
+        assert_eq!(total_pages(1, 20), 1);
+        assert_eq!(total_pages(21, 20), 2);
+        assert_eq!(total_pages(59, 20), 3);
+    }
 }
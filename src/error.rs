@@ -9,9 +9,39 @@ pub enum AppError {
     #[error("bad request: {0}")]
     BadRequest(&'static str),
 
+    #[error("unauthorized: {0}")]
+    Unauthorized(&'static str),
+
+    #[error("forbidden: {0}")]
+    Forbidden(&'static str),
+
     #[error("not found: {0}")]
     NotFound(&'static str),
 
+    #[error("conflict: {0}")]
+    Conflict(&'static str),
+
+    #[error("conflict: content is identical to existing version {0}")]
+    DuplicateVersion(i32),
+
+    #[error("precondition failed: {0}")]
+    PreconditionFailed(&'static str),
+
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(&'static str),
+
+    #[error("response too large: {0}")]
+    ResponseTooLarge(&'static str),
+
+    #[error("payload too large: {0}")]
+    PayloadTooLarge(&'static str),
+
+    #[error("too many requests: {0}")]
+    TooManyRequests(&'static str),
+
+    #[error("bad gateway: {0}")]
+    BadGateway(&'static str),
+
     #[error("database error: {0}")]
     Db(#[from] sqlx::Error),
 
@@ -21,8 +51,10 @@ pub enum AppError {
     #[error("env error: {0}")]
     Env(#[from] std::env::VarError),
     
+    /// Boxed: `opendal::Error` is large enough to bloat every `Result` that
+    /// carries an `AppError`
     #[error("storage error: {0}")]
-    Storage(#[from] opendal::Error),
+    Storage(Box<opendal::Error>),
 
     #[error("other error: {0}")]
     Other(#[from] anyhow::Error),
@@ -30,7 +62,43 @@ pub enum AppError {
 
 #[derive(Serialize)]
 struct ErrorBody {
+    /// Stable machine-readable code, see `AppError::code`
+    code: &'static str,
     error: String,
+    /// Matches the `X-Request-Id` response header and the server's logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+impl From<opendal::Error> for AppError {
+    fn from(err: opendal::Error) -> Self {
+        AppError::Storage(Box::new(err))
+    }
+}
+
+impl AppError {
+    /// Error code clients can branch on instead of matching the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "BAD_REQUEST",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::Forbidden(_) => "PERMISSION_DENIED",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::DuplicateVersion(_) => "DUPLICATE_VERSION",
+            AppError::PreconditionFailed(_) => "PRECONDITION_FAILED",
+            AppError::UnsupportedMediaType(_) => "UNSUPPORTED_MEDIA_TYPE",
+            AppError::ResponseTooLarge(_) => "RESPONSE_TOO_LARGE",
+            AppError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            AppError::TooManyRequests(_) => "TOO_MANY_REQUESTS",
+            AppError::BadGateway(_) => "BAD_GATEWAY",
+            AppError::Db(_) => "DB_ERROR",
+            AppError::Io(_) => "IO_ERROR",
+            AppError::Env(_) => "ENV_ERROR",
+            AppError::Storage(_) => "STORAGE_ERROR",
+            AppError::Other(_) => "INTERNAL_ERROR",
+        }
+    }
 }
 
 // error --> HTTP mapping
@@ -42,10 +110,50 @@ impl IntoResponse for AppError {
                 tracing::warn!(message = %msg, "Bad request");
                 StatusCode::BAD_REQUEST
             }
+            AppError::Unauthorized(msg) => {
+                tracing::warn!(message = %msg, "Unauthorized");
+                StatusCode::UNAUTHORIZED
+            }
+            AppError::Forbidden(msg) => {
+                tracing::warn!(message = %msg, "Forbidden");
+                StatusCode::FORBIDDEN
+            }
             AppError::NotFound(msg) => {
                 tracing::info!(message = %msg, "Resource not found");
                 StatusCode::NOT_FOUND
             }
+            AppError::Conflict(msg) => {
+                tracing::warn!(message = %msg, "Conflict");
+                StatusCode::CONFLICT
+            }
+            AppError::DuplicateVersion(version) => {
+                tracing::warn!(existing_version = version, "Rejected duplicate version");
+                StatusCode::CONFLICT
+            }
+            AppError::PreconditionFailed(msg) => {
+                tracing::warn!(message = %msg, "Precondition failed");
+                StatusCode::PRECONDITION_FAILED
+            }
+            AppError::UnsupportedMediaType(msg) => {
+                tracing::warn!(message = %msg, "Unsupported media type");
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+            AppError::ResponseTooLarge(msg) => {
+                tracing::warn!(message = %msg, "Response too large");
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            AppError::PayloadTooLarge(msg) => {
+                tracing::warn!(message = %msg, "Payload too large");
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            AppError::TooManyRequests(msg) => {
+                tracing::warn!(message = %msg, "Rate limited");
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AppError::BadGateway(msg) => {
+                error!(message = %msg, "Upstream failure");
+                StatusCode::BAD_GATEWAY
+            }
             AppError::Db(_)
             | AppError::Io(_)
             | AppError::Env(_)
@@ -57,7 +165,9 @@ impl IntoResponse for AppError {
         };
 
         let body = ErrorBody {
+            code: self.code(),
             error: self.to_string(),
+            request_id: crate::request_id::current(),
         };
 
         (status, Json(body)).into_response()
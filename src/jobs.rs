@@ -0,0 +1,232 @@
+//! In-process registry of long-running admin jobs. Jobs run on spawned tasks
+//! and report progress here; cancellation is cooperative, so a job only stops
+//! at the next point its loop checks `JobHandle::is_cancelled`. The registry
+//! lives in memory and is lost on restart.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::dtos::{JobInfo, JobStatus};
+
+/// Finished jobs kept for `GET /admin/jobs`; older ones are dropped first
+const MAX_FINISHED_JOBS: usize = 100;
+
+struct JobEntry {
+    info: JobInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<Uuid, JobEntry>>>,
+}
+
+impl JobRegistry {
+    /// Register a running job and return the handle its task reports through
+    pub fn start(&self, kind: &'static str, started_by: Uuid) -> JobHandle {
+        let id = Uuid::new_v4();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let info = JobInfo {
+            id,
+            kind,
+            status: JobStatus::Running,
+            started_by,
+            started_at: Utc::now(),
+            finished_at: None,
+            processed: 0,
+            total: None,
+            cancel_requested: false,
+            error: None,
+        };
+
+        let mut jobs = self.lock();
+        prune_finished(&mut jobs);
+        jobs.insert(id, JobEntry { info, cancel: cancel.clone() });
+
+        JobHandle {
+            id,
+            registry: self.clone(),
+            cancel,
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<JobInfo> {
+        self.lock().get(&id).map(|e| e.info.clone())
+    }
+
+    /// Snapshot of every known job, newest first
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.lock().values().map(|e| e.info.clone()).collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        jobs
+    }
+
+    /// Ask a running job to stop. Returns None for an unknown id; finished
+    /// jobs are returned unchanged.
+    pub fn cancel(&self, id: Uuid) -> Option<JobInfo> {
+        let mut jobs = self.lock();
+        let entry = jobs.get_mut(&id)?;
+        if entry.info.status == JobStatus::Running {
+            entry.cancel.store(true, Ordering::SeqCst);
+            entry.info.cancel_requested = true;
+        }
+        Some(entry.info.clone())
+    }
+
+    fn update(&self, id: Uuid, f: impl FnOnce(&mut JobInfo)) {
+        if let Some(entry) = self.lock().get_mut(&id) {
+            f(&mut entry.info);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, JobEntry>> {
+        // A panic while holding the lock cannot leave a job half-written, so
+        // keep serving the map rather than poisoning every later request
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn prune_finished(jobs: &mut HashMap<Uuid, JobEntry>) {
+    let mut finished: Vec<(chrono::DateTime<Utc>, Uuid)> = jobs
+        .values()
+        .filter(|e| e.info.status != JobStatus::Running)
+        .map(|e| (e.info.finished_at.unwrap_or(e.info.started_at), e.info.id))
+        .collect();
+    if finished.len() < MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+/// Reporting side of a job, owned by the task doing the work
+pub struct JobHandle {
+    pub id: Uuid,
+    registry: JobRegistry,
+    cancel: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    pub fn set_total(&self, total: u64) {
+        self.registry.update(self.id, |info| info.total = Some(total));
+    }
+
+    pub fn advance(&self, by: u64) {
+        self.registry.update(self.id, |info| info.processed += by);
+    }
+
+    /// Record the outcome; a job that saw its cancel flag ends as cancelled
+    /// even if it returned Ok
+    pub fn finish<E: std::fmt::Display>(self, result: Result<(), E>) {
+        let cancelled = self.is_cancelled();
+        self.registry.update(self.id, |info| {
+            info.finished_at = Some(Utc::now());
+            match result {
+                Err(e) => {
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e.to_string());
+                }
+                Ok(()) if cancelled => info.status = JobStatus::Cancelled,
+                Ok(()) => info.status = JobStatus::Completed,
+            }
+        });
+    }
+}
+
+impl Drop for JobHandle {
+    /// A task that panicked or returned without calling `finish` would
+    /// otherwise show as running forever
+    fn drop(&mut self) {
+        self.registry.update(self.id, |info| {
+            if info.status == JobStatus::Running {
+                info.status = JobStatus::Failed;
+                info.finished_at = Some(Utc::now());
+                info.error = Some("job ended without reporting a result".to_string());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_reported_through_the_handle() {
+        let registry = JobRegistry::default();
+        let job = registry.start("test", Uuid::nil());
+
+        job.set_total(10);
+        job.advance(3);
+        job.advance(4);
+
+        let info = registry.get(job.id).unwrap();
+        assert_eq!(info.status, JobStatus::Running);
+        assert_eq!(info.total, Some(10));
+        assert_eq!(info.processed, 7);
+    }
+
+    #[test]
+    fn cancel_flips_the_flag_and_finish_records_cancelled() {
+        let registry = JobRegistry::default();
+        let job = registry.start("test", Uuid::nil());
+        assert!(!job.is_cancelled());
+
+        let info = registry.cancel(job.id).unwrap();
+        assert!(info.cancel_requested);
+        assert!(job.is_cancelled());
+
+        let id = job.id;
+        job.finish::<String>(Ok(()));
+        let info = registry.get(id).unwrap();
+        assert_eq!(info.status, JobStatus::Cancelled);
+        assert!(info.finished_at.is_some());
+    }
+
+    #[test]
+    fn finish_records_completion_and_failure() {
+        let registry = JobRegistry::default();
+
+        let ok = registry.start("test", Uuid::nil());
+        let ok_id = ok.id;
+        ok.finish::<String>(Ok(()));
+        assert_eq!(registry.get(ok_id).unwrap().status, JobStatus::Completed);
+
+        let failed = registry.start("test", Uuid::nil());
+        let failed_id = failed.id;
+        failed.finish(Err("boom"));
+        let info = registry.get(failed_id).unwrap();
+        assert_eq!(info.status, JobStatus::Failed);
+        assert_eq!(info.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn cancelling_a_finished_job_changes_nothing() {
+        let registry = JobRegistry::default();
+        let job = registry.start("test", Uuid::nil());
+        let id = job.id;
+        job.finish::<String>(Ok(()));
+
+        let info = registry.cancel(id).unwrap();
+        assert_eq!(info.status, JobStatus::Completed);
+        assert!(!info.cancel_requested);
+        assert!(registry.cancel(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn dropped_handle_marks_job_failed() {
+        let registry = JobRegistry::default();
+        let id = registry.start("test", Uuid::nil()).id;
+        assert_eq!(registry.get(id).unwrap().status, JobStatus::Failed);
+    }
+}
@@ -0,0 +1,27 @@
+//! Document management service. The server binary lives in `main.rs`; the
+//! modules are exposed as a library so integration tests can build the router.
+
+pub mod config;
+pub mod models;
+pub mod dtos;
+pub mod error;
+pub mod state;
+pub mod routes;
+pub mod auth;
+pub mod audit;
+pub mod openapi;
+pub mod notifications;
+pub mod mime;
+pub mod db;
+pub mod storage;
+pub mod quota;
+pub mod signing;
+pub mod response;
+pub mod text;
+pub mod password;
+pub mod token;
+pub mod jobs;
+pub mod request_id;
+#[cfg(feature = "test-helpers")]
+pub mod testing;
+
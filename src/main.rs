@@ -1,21 +1,13 @@
-#![allow(unused_imports, unused_variables, non_snake_case, unused_mut, dead_code)]
-mod models;
-mod dtos;
-mod error;
-mod state;
-mod routes;
-mod auth;
-mod audit;
-mod openapi;
-
-use axum::Router;
-use sqlx::PgPool;
-use std::{fs, path::PathBuf};
+use axum::extract::Request;
+use axum::ServiceExt;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::{fs, path::PathBuf, time::Duration};
 use tokio::net::TcpListener;
-use tracing_subscriber;
 use tracing::{info, debug, warn};
 
-use state::AppState;
+use rust_dms::{audit, config, jobs, routes};
+use rust_dms::state::AppState;
+use rust_dms::storage::StorageBackend;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -31,25 +23,148 @@ async fn main() -> anyhow::Result<()> {
     let database_url = std::env::var("DATABASE_URL")
         .map_err(|_| anyhow::anyhow!("DATABASE_URL is not set"))?;
 
-    let pool = PgPool::connect(&database_url).await?;
-
-    // Ensure uploads directory exists
-    // let upload_dir = PathBuf::from("uploads");
-    // fs::create_dir_all(&upload_dir)?;
-
-    // Build OpenDAL operator for local filesystem storage rooted at `uploads/`.
-    //
-    // Old manual filesystem approach:
-    // let stored_file_name = format!("{}_{}", Uuid::new_v4(), file_name);
-    // let stored_path = upload_dir.join(&stored_file_name);
-    // fs::write(&stored_path, &file_bytes)?;
-    //
-    // New approach: use OpenDAL `Operator` with an FS service.
-    // let mut builder = opendal::services::Fs::default();
-    // Use the uploads directory as the root for all storage operations.
-    // builder = builder.root(&upload_dir.to_string_lossy());
-    // let storage = opendal::Operator::new(builder)?.finish();
+    let mut config = config::Config::from_env();
 
+    info!(
+        max_connections = config.db_max_connections,
+        acquire_timeout_secs = config.db_acquire_timeout_secs,
+        connect_timeout_secs = config.db_connect_timeout_secs,
+        "Database pool settings"
+    );
+    let pool = connect_pool(&config, &database_url).await?;
+
+    // Optional read replica for read-heavy endpoints; falls back to the primary
+    let read_pool = match std::env::var("DATABASE_READ_URL") {
+        Ok(read_url) if !read_url.trim().is_empty() => {
+            info!("Connecting to read replica (DATABASE_READ_URL)");
+            connect_pool(&config, &read_url).await?
+        }
+        _ => pool.clone(),
+    };
+
+    let storage = match config.storage_backend {
+        StorageBackend::S3 => s3_storage().await?,
+        StorageBackend::Fs => fs_storage(&config.upload_dir)?,
+    };
+
+    let permission_overrides = config::permission_overrides_from_env()
+        .map_err(|problem| anyhow::anyhow!("Invalid PERMISSION_MATRIX: {}", problem))?;
+    if !permission_overrides.is_empty() {
+        info!(overrides = ?permission_overrides, "Permission matrix overrides installed");
+    }
+    config.permission_overrides = std::sync::Arc::new(permission_overrides);
+
+    match audit::verify_schema(&pool).await {
+        Ok(()) => debug!("Audit schema check passed"),
+        Err(problem) if config.audit_schema_required => {
+            return Err(anyhow::anyhow!(
+                "Audit schema check failed: {} (AUDIT_SCHEMA_REQUIRED is set)",
+                problem
+            ));
+        }
+        Err(problem) => {
+            tracing::error!(
+                problem = %problem,
+                "Audit schema check failed; audit logs will not be written until migrations are applied"
+            );
+        }
+    }
+
+    let trim_trailing_slash = config.trim_trailing_slash;
+    // Kept to close the pools once in-flight requests have drained
+    let (db_pool, db_read_pool) = (pool.clone(), read_pool.clone());
+    let state = AppState {
+        pool,
+        read_pool,
+        storage,
+        config: std::sync::Arc::new(config),
+        jobs: jobs::JobRegistry::default(),
+    };
+    let app = routes::router(state);
+
+    let listener = TcpListener::bind("0.0.0.0:3000").await?;
+    println!("Server running on http://0.0.0.0:3000");
+
+    if trim_trailing_slash {
+        let app = routes::trim_trailing_slash(app);
+        axum::serve(listener, ServiceExt::<Request>::into_make_service(app))
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+    } else {
+        axum::serve(listener, app.into_make_service())
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+    }
+
+    // Every request has finished by now, so nothing is mid-transaction
+    info!("In-flight requests drained, closing database pools");
+    db_read_pool.close().await;
+    db_pool.close().await;
+    info!("Shutdown complete");
+    Ok(())
+}
+
+/// Open a pool with the DB_* limits from `config`. sqlx has no Postgres
+/// connect timeout of its own, so the first connection is bounded here.
+async fn connect_pool(config: &config::Config, url: &str) -> anyhow::Result<PgPool> {
+    let options = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(Duration::from_secs(config.db_acquire_timeout_secs));
+
+    let connect_timeout = Duration::from_secs(config.db_connect_timeout_secs);
+    match tokio::time::timeout(connect_timeout, options.connect(url)).await {
+        Ok(pool) => Ok(pool?),
+        Err(_) => Err(anyhow::anyhow!(
+            "Timed out connecting to the database after {}s (DB_CONNECT_TIMEOUT_SECS)",
+            config.db_connect_timeout_secs
+        )),
+    }
+}
+
+/// Resolve on Ctrl-C or SIGTERM. The server then stops accepting connections
+/// and waits for in-flight requests (uploads, streamed downloads) to finish.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!(error = %e, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C, shutting down gracefully"),
+        _ = terminate => info!("Received SIGTERM, shutting down gracefully"),
+    }
+}
+
+/// Local filesystem storage rooted at UPLOAD_DIR, for running without SeaweedFS
+fn fs_storage(upload_dir: &str) -> anyhow::Result<opendal::Operator> {
+    let upload_dir = PathBuf::from(upload_dir);
+    fs::create_dir_all(&upload_dir)?;
+    info!("Using filesystem storage at {}", upload_dir.display());
+
+    let builder = opendal::services::Fs::default().root(&upload_dir.to_string_lossy());
+    Ok(opendal::Operator::new(builder)?.finish())
+}
+
+/// S3 storage on SeaweedFS (SEAWEEDFS_*), creating the bucket if needed
+async fn s3_storage() -> anyhow::Result<opendal::Operator> {
     let endpoint = std::env::var("SEAWEEDFS_ENDPOINT")
        .unwrap_or_else(|_| "http://localhost:8333".to_string());
     let access_key = std::env::var("SEAWEEDFS_ACCESS_KEY")
@@ -130,11 +245,5 @@ async fn main() -> anyhow::Result<()> {
         warn!("Bucket '{}' may not exist, uploads might fail on first request", bucket);
     }
 
-    let state = AppState { pool, storage };
-    let app = routes::router(state);
-
-    let listener = TcpListener::bind("0.0.0.0:3000").await?;
-    println!("Server running on http://0.0.0.0:3000");
-    axum::serve(listener, app.into_make_service()).await?;
-    Ok(())
-}
\ No newline at end of file
+    Ok(storage)
+}
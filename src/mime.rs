@@ -0,0 +1,142 @@
+use std::str::FromStr;
+
+/// Number of leading bytes needed to recognise every format `infer` knows about
+pub const SNIFF_PREFIX_BYTES: u64 = 8192;
+
+/// Fallback content type when nothing better is known
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// When upload MIME types are sniffed from magic bytes (SNIFF_MIME). The
+/// default never trusts a declared type the content disagrees with; the
+/// other policies trade that for skipping the sniff on trusted deployments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SniffPolicy {
+    /// Sniff every upload; whenever the magic bytes disagree with the
+    /// declared type, the detected one is stored (default)
+    #[default]
+    Always,
+    /// Opt-in: sniff only when the client sent no type or octet-stream
+    WhenMissing,
+    /// Opt-in: never sniff; trust the declared type or the category default
+    Never,
+}
+
+impl FromStr for SniffPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "always" => Ok(SniffPolicy::Always),
+            "when_missing" => Ok(SniffPolicy::WhenMissing),
+            "never" => Ok(SniffPolicy::Never),
+            other => Err(format!("unknown sniff policy: {}", other)),
+        }
+    }
+}
+
+/// Detect a MIME type from the magic bytes at the start of a file
+pub fn sniff(prefix: &[u8]) -> Option<String> {
+    infer::get(prefix).map(|kind| kind.mime_type().to_string())
+}
+
+/// Pick the MIME type to store for an upload. Under `Always` a type sniffed
+/// from the content wins over the client-declared one; `WhenMissing` sniffs
+/// only when nothing useful was declared, and `Never` does not sniff at all.
+/// After those come the category default, then octet-stream.
+pub fn resolve_upload_mime(
+    policy: SniffPolicy,
+    declared: Option<&str>,
+    file_bytes: &[u8],
+    category_default: Option<&str>,
+) -> String {
+    let sniffed = || {
+        let prefix = &file_bytes[..file_bytes.len().min(SNIFF_PREFIX_BYTES as usize)];
+        sniff(prefix)
+    };
+
+    // Many clients send octet-stream when they simply don't know
+    let known = declared.filter(|m| !m.is_empty() && *m != OCTET_STREAM);
+
+    let detected = match (policy, known) {
+        (SniffPolicy::Always, _) => sniffed(),
+        (SniffPolicy::WhenMissing, None) => sniffed(),
+        (SniffPolicy::WhenMissing, Some(_)) | (SniffPolicy::Never, _) => None,
+    };
+
+    detected
+        .or_else(|| known.map(str::to_string))
+        .or_else(|| category_default.map(str::to_string))
+        .unwrap_or_else(|| OCTET_STREAM.to_string())
+}
+
+/// Whether content of this type is human-readable text, e.g. for diffing
+pub fn is_text_like(mime_type: &str) -> bool {
+    let essence = mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/xml"
+                | "application/csv"
+                | "application/yaml"
+                | "application/x-yaml"
+                | "application/javascript"
+                | "application/sql"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDF: &[u8] = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n";
+    const PLAIN: &[u8] = b"just some words";
+
+    #[test]
+    fn never_trusts_declared_then_category() {
+        let policy = SniffPolicy::Never;
+        assert_eq!(resolve_upload_mime(policy, Some("text/plain"), PDF, None), "text/plain");
+        assert_eq!(resolve_upload_mime(policy, None, PDF, Some("text/csv")), "text/csv");
+        assert_eq!(resolve_upload_mime(policy, Some(OCTET_STREAM), PDF, None), OCTET_STREAM);
+    }
+
+    #[test]
+    fn when_missing_sniffs_only_without_a_useful_declared_type() {
+        let policy = SniffPolicy::WhenMissing;
+        assert_eq!(resolve_upload_mime(policy, Some("text/plain"), PDF, None), "text/plain");
+        assert_eq!(resolve_upload_mime(policy, None, PDF, None), "application/pdf");
+        assert_eq!(resolve_upload_mime(policy, Some(OCTET_STREAM), PDF, None), "application/pdf");
+        assert_eq!(resolve_upload_mime(policy, Some(""), PLAIN, Some("text/csv")), "text/csv");
+    }
+
+    #[test]
+    fn always_lets_the_sniffed_type_win() {
+        let policy = SniffPolicy::Always;
+        assert_eq!(resolve_upload_mime(policy, Some("text/plain"), PDF, Some("text/csv")), "application/pdf");
+        // Content with no magic bytes falls back to the declared type, then the category
+        assert_eq!(resolve_upload_mime(policy, Some("text/markdown"), PLAIN, Some("text/csv")), "text/markdown");
+        assert_eq!(resolve_upload_mime(policy, None, PLAIN, Some("text/csv")), "text/csv");
+        assert_eq!(resolve_upload_mime(policy, None, PLAIN, None), OCTET_STREAM);
+    }
+
+    #[test]
+    fn default_policy_stores_the_sniffed_type_over_a_disagreeing_declared_one() {
+        assert_eq!(SniffPolicy::default(), SniffPolicy::Always);
+        assert_eq!(
+            resolve_upload_mime(SniffPolicy::default(), Some("image/png"), PDF, None),
+            "application/pdf"
+        );
+        assert_eq!(
+            resolve_upload_mime(SniffPolicy::default(), Some("text/markdown"), PLAIN, None),
+            "text/markdown"
+        );
+    }
+}
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use sqlx::FromRow;
 use uuid::Uuid;
-use utoipa::{ToSchema, schema};
+use utoipa::ToSchema;
 
 /// Document model - represents a logical document
 /// Maps to the `documents` table
@@ -18,6 +18,12 @@ pub struct Document {
     /// Optional category - VARCHAR(100) NULLABLE
     pub category: Option<String>,
     
+    /// Owner - UUID REFERENCES users(id) NULLABLE (NULL for legacy rows)
+    pub created_by: Option<Uuid>,
+    
+    /// Downloadable without authentication - BOOLEAN NOT NULL DEFAULT FALSE
+    pub is_public: bool,
+    
     /// Soft delete timestamp - NULL means not deleted, non-NULL means soft-deleted
     pub deleted_at: Option<DateTime<Utc>>,
     
@@ -113,7 +119,7 @@ pub struct User {
     pub username: String,
     pub api_key: String,
     pub password: Option<String>, 
-    pub role: String,
+    pub role: crate::auth::Role,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -134,6 +140,20 @@ pub enum AuditAction {
     Delete,
     /// Previous version restored
     RestoreVersion,
+    /// Existing version made the one served by default
+    PromoteVersion,
+    /// User password reset by an admin
+    ResetPassword,
+    /// User API key replaced (by the user or an admin)
+    RotateApiKey,
+    /// Soft-deleted document brought back
+    RestoreDocument,
+    /// User account created by an admin
+    CreateUser,
+    /// User role changed by an admin
+    UpdateUserRole,
+    /// User account deleted by an admin
+    DeleteUser,
 }
 
 /// Audit log model - represents an immutable audit record
@@ -225,4 +245,60 @@ pub struct NewDocumentTag {
     
     /// Tag ID
     pub tag_id: Uuid,
-}
\ No newline at end of file
+}
+/// DocumentWatch model - a user's subscription to changes on a document
+/// Maps to the `document_watches` table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DocumentWatch {
+    /// Foreign key to users table - UUID NOT NULL
+    pub user_id: Uuid,
+
+    /// Foreign key to documents table - UUID NOT NULL
+    pub document_id: Uuid,
+
+    /// Creation timestamp - TIMESTAMP WITH TIME ZONE
+    pub created_at: DateTime<Utc>,
+}
+
+/// Notification model - a message queued for a watcher
+/// Maps to the `notifications` table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Notification {
+    /// Primary key - UUID
+    pub id: Uuid,
+
+    /// Recipient - UUID NOT NULL
+    pub user_id: Uuid,
+
+    /// Document the notification is about (NULL once the document is hard-deleted)
+    pub document_id: Option<Uuid>,
+
+    /// Version that triggered the notification - INTEGER NULLABLE
+    pub document_version: Option<i32>,
+
+    /// Human-readable message - TEXT NOT NULL
+    pub message: String,
+
+    /// Creation timestamp - TIMESTAMP WITH TIME ZONE
+    pub created_at: DateTime<Utc>,
+}
+
+/// DocumentAlias model - a short, shareable slug pointing at a document
+/// Maps to the `document_aliases` table
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DocumentAlias {
+    /// Primary key - opaque slug VARCHAR(32)
+    pub slug: String,
+
+    /// Foreign key to documents table - UUID NOT NULL
+    pub document_id: Uuid,
+
+    /// User who minted the alias - UUID NULLABLE
+    pub created_by: Option<Uuid>,
+
+    /// Expiry - NULL means the alias never expires
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// Creation timestamp - TIMESTAMP WITH TIME ZONE
+    pub created_at: DateTime<Utc>,
+}
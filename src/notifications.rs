@@ -0,0 +1,46 @@
+use sqlx::PgPool;
+use crate::error::AppError;
+use uuid::Uuid;
+use tracing::{info, error};
+
+/// Queue a notification for every user watching `document_id`, except the
+/// user who caused the change. Returns the number of notifications created.
+pub async fn notify_watchers(
+    pool: &PgPool,
+    document_id: Uuid,
+    document_version: i32,
+    actor_id: Uuid,
+    message: &str,
+) -> Result<u64, AppError> {
+
+    let created = sqlx::query(
+        r#"
+        INSERT INTO notifications (user_id, document_id, document_version, message)
+        SELECT w.user_id, w.document_id, $2, $3
+        FROM document_watches w
+        WHERE w.document_id = $1 AND w.user_id <> $4
+        "#
+    )
+    .bind(document_id)
+    .bind(document_version)
+    .bind(message)
+    .bind(actor_id)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!(error = ?e, "Failed to enqueue watcher notifications");
+        AppError::Db(e)
+    })?
+    .rows_affected();
+
+    if created > 0 {
+        info!(
+            document_id = %document_id,
+            document_version = document_version,
+            notifications = created,
+            "Watcher notifications queued"
+        );
+    }
+
+    Ok(created)
+}
@@ -1,42 +1,213 @@
 use utoipa::OpenApi;
-use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
-use crate::models::{Document, DocumentVersion, AuditLog, AuditAction};
-use crate::dtos::{UploadResponse, ListDocumentsResponse, ListDocumentsQuery, DownloadQuery, AuditResponse, DocumentWithLatest, CreateFolderRequest, CreateFolderResponse,AddTagToDocumentRequest,AddTagToDocumentResponse,TagInfo, LoginRequest, LoginResponse,FolderInfo,ListFoldersResponse};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use crate::auth::Role;
+use crate::models::{Document, DocumentVersion, AuditLog, AuditAction, Notification, DocumentAlias, Tag};
+use crate::dtos::{UploadResponse, InitUploadRequest, UploadSessionResponse, ExpiredUploadsResponse, ListDocumentsResponse, ListDocumentsQuery, DownloadQuery, AuditResponse, DocumentWithLatest, CreateFolderRequest, CreateFolderResponse,AddTagToDocumentRequest,AddTagToDocumentResponse,AddTagIdsRequest,TagInfo,TagStatus, RemoveTagQuery, RemoveTagResponse, LoginRequest, LoginResponse, RotateKeyResponse, ResetPasswordRequest, ResetPasswordResponse,FolderInfo,ListFoldersResponse, WatchResponse, NotificationsResponse, RepairMimeTypesRequest, AuditExportQuery, OrphanedDocumentsQuery, OrphanedDocumentsResponse, PreviewQuery, DocumentPreviewResponse, VersionDiffQuery, VersionDiffResponse, VersionFootprint, DocumentFootprintResponse, RekeyedVersion, RekeyDocumentResponse, CreateAliasRequest, CreateAliasResponse, SetVisibilityRequest, VisibilityResponse, SetTemplateRequest, TemplateResponse, FromTemplateRequest, FromTemplateResponse, BulkMetadataRequest, BulkMetadataResponse, BulkMetadataResult, BulkMetadataStatus, ArchiveRequest, BatchDeleteRequest, BatchDeleteResponse, BatchDeleteResult, BatchDeleteStatus, BulkRestoreRequest, BulkRestoreResponse, BulkRestoreResult, BulkRestoreStatus, DedupReportQuery, DedupReportResponse, DuplicateContentGroup, VersionIntegrity, DocumentIntegrityResponse, PromoteVersionResponse, RecentVersionsQuery, RecentVersion, RecentVersionsResponse, PruneVersionsRequest, PruneVersionsResponse, MigrateKeyNamespacesRequest, CreateSignedLinkRequest, SignedLinkResponse, SignedLinkQuery, SignedLinkVerification, ListFoldersQuery, SimulateAccessQuery, SimulateAccessResponse, SimulatedAction, DocumentMetadataResponse, ExplainQuery, ExplainResponse, FacetsQuery, FacetValue, FacetsResponse, UploaderStats, SearchQuery, SearchHit, SearchResponse, MetadataKeysQuery, MetadataKeyUsage, MetadataKeysResponse, AuditQuery, AuditEventsQuery, AuditEvent, AuditEventActor, AuditEventResource, VersionResponse, HealthResponse, DependencyCheck, ReadinessResponse, JobStatus, JobInfo, ListJobsResponse, LinkFolderRequest, LinkFolderResponse, UserInfo, CreateUserRequest, CreateUserResponse, UpdateUserRoleRequest, ListUsersResponse, DocumentSortField, SortOrder, UpdateDocumentRequest};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::routes::upload::upload_file,
+        crate::routes::resumable_upload::init_upload,
+        crate::routes::resumable_upload::get_upload,
+        crate::routes::resumable_upload::put_chunk,
+        crate::routes::resumable_upload::complete_upload,
+        crate::routes::resumable_upload::abort_upload,
+        crate::routes::resumable_upload::expire_uploads,
         crate::routes::documents::list_documents,
+        crate::routes::documents::list_versions,
+        crate::routes::documents::list_recent_versions,
+        crate::routes::documents::restore_document_version,
+        crate::routes::documents::bulk_restore_documents,
+        crate::routes::documents::batch_delete_documents,
+        crate::routes::documents::promote_document_version,
         crate::routes::documents::download_document,
+        crate::routes::documents::head_document,
+        crate::routes::documents::preview_document,
+        crate::routes::documents::diff_versions,
+        crate::routes::documents::document_footprint,
+        crate::routes::documents::document_integrity,
+        crate::routes::documents::prune_versions,
+        crate::routes::documents::update_document,
         crate::routes::documents::soft_delete_document,
         crate::routes::documents::hard_delete_document,
         crate::routes::audit::get_actions,
+        crate::routes::audit::export_document_audit,
+        crate::routes::audit::export_audit_events,
         crate::routes::folders::create_folder,
+        crate::routes::tags::list_tags,
         crate::routes::tags::add_tags_to_document,
+        crate::routes::tags::add_tag_ids_to_document,
+        crate::routes::tags::remove_tag_from_document,
         crate::routes::login::login,
         crate::routes::folders::list_folders, 
+        crate::routes::folders::link_document_folder,
+        crate::routes::watches::watch_document,
+        crate::routes::watches::unwatch_document,
+        crate::routes::watches::list_notifications,
+        crate::routes::admin::list_orphaned_documents,
+        crate::routes::admin::rekey_document,
+        crate::routes::admin::dedup_report,
+        crate::routes::admin::simulate_access,
+        crate::routes::admin::reset_password,
+        crate::routes::login::rotate_own_key,
+        crate::routes::login::rotate_user_key,
+        crate::routes::users::list_users,
+        crate::routes::users::create_user,
+        crate::routes::users::update_user_role,
+        crate::routes::users::delete_user,
+        crate::routes::admin::explain_endpoint,
+        crate::routes::backup::backup,
+        crate::routes::aliases::create_alias,
+        crate::routes::aliases::resolve_alias,
+        crate::routes::visibility::set_visibility,
+        crate::routes::visibility::download_public_document,
+        crate::routes::signed_links::create_signed_link,
+        crate::routes::signed_links::download_signed,
+        crate::routes::signed_links::verify_signed_link,
+        crate::routes::metadata::get_document_metadata,
+        crate::routes::metadata::update_document_metadata,
+        crate::routes::metadata::delete_document_metadata_key,
+        crate::routes::metadata::bulk_upsert_metadata,
+        crate::routes::metadata::list_metadata_keys,
+        crate::routes::facets::list_facets,
+        crate::routes::search::search_documents,
+        crate::routes::templates::set_template,
+        crate::routes::templates::create_from_template,
+        crate::routes::archive::archive_documents,
+        crate::routes::stats::list_uploaders,
+        crate::routes::version::version,
+        crate::routes::health::health,
+        crate::routes::health::ready,
+        crate::routes::jobs::list_jobs,
+        crate::routes::jobs::cancel_job,
+        crate::routes::jobs::start_repair_mime_types,
+        crate::routes::jobs::start_migrate_key_namespaces,
     ),
     components(schemas(
         Document,
         DocumentVersion,
         AuditLog,
         AuditAction,
+        Tag,
+        RemoveTagQuery,
+        RemoveTagResponse,
         UploadResponse,
+        InitUploadRequest,
+        UploadSessionResponse,
+        ExpiredUploadsResponse,
         DocumentWithLatest,
         ListDocumentsResponse,
         ListDocumentsQuery,
+        DocumentSortField,
+        SortOrder,
+        UpdateDocumentRequest,
         DownloadQuery,
         AuditResponse,
         CreateFolderRequest,
         CreateFolderResponse,
         AddTagToDocumentRequest,
         AddTagToDocumentResponse,
+        AddTagIdsRequest,
         TagInfo,
+        TagStatus,
         LoginRequest,
+        ResetPasswordRequest,
+        ResetPasswordResponse,
         LoginResponse,
+        RotateKeyResponse,
         FolderInfo,
-        ListFoldersResponse
+        ListFoldersResponse,
+        Notification,
+        WatchResponse,
+        NotificationsResponse,
+        RepairMimeTypesRequest,
+        AuditExportQuery,
+        OrphanedDocumentsQuery,
+        OrphanedDocumentsResponse,
+        PreviewQuery,
+        DocumentPreviewResponse,
+        VersionDiffQuery,
+        VersionDiffResponse,
+        VersionFootprint,
+        DocumentFootprintResponse,
+        RekeyedVersion,
+        RekeyDocumentResponse,
+        DocumentAlias,
+        CreateAliasRequest,
+        CreateAliasResponse,
+        SetVisibilityRequest,
+        VisibilityResponse,
+        SetTemplateRequest,
+        TemplateResponse,
+        FromTemplateRequest,
+        FromTemplateResponse,
+        BulkMetadataRequest,
+        BulkMetadataResponse,
+        BulkMetadataResult,
+        BulkMetadataStatus,
+        ArchiveRequest,
+        BatchDeleteRequest,
+        BatchDeleteResponse,
+        BatchDeleteResult,
+        BatchDeleteStatus,
+        BulkRestoreRequest,
+        BulkRestoreResponse,
+        BulkRestoreResult,
+        BulkRestoreStatus,
+        DedupReportQuery,
+        DedupReportResponse,
+        DuplicateContentGroup,
+        VersionIntegrity,
+        DocumentIntegrityResponse,
+        PromoteVersionResponse,
+        RecentVersionsQuery,
+        RecentVersion,
+        RecentVersionsResponse,
+        PruneVersionsRequest,
+        PruneVersionsResponse,
+        MigrateKeyNamespacesRequest,
+        CreateSignedLinkRequest,
+        SignedLinkResponse,
+        SignedLinkQuery,
+        SignedLinkVerification,
+        ListFoldersQuery,
+        SimulateAccessQuery,
+        SimulateAccessResponse,
+        SimulatedAction,
+        DocumentMetadataResponse,
+        ExplainQuery,
+        ExplainResponse,
+        FacetsQuery,
+        FacetValue,
+        FacetsResponse,
+        UploaderStats,
+        SearchQuery,
+        SearchHit,
+        SearchResponse,
+        MetadataKeysQuery,
+        MetadataKeyUsage,
+        MetadataKeysResponse,
+        AuditQuery,
+        AuditEventsQuery,
+        AuditEvent,
+        AuditEventActor,
+        AuditEventResource,
+        VersionResponse,
+        HealthResponse,
+        DependencyCheck,
+        ReadinessResponse,
+        JobStatus,
+        JobInfo,
+        ListJobsResponse,
+        LinkFolderRequest,
+        LinkFolderResponse,
+        Role,
+        UserInfo,
+        CreateUserRequest,
+        CreateUserResponse,
+        UpdateUserRoleRequest,
+        ListUsersResponse
     )),
     tags(
         (name = "documents", description = "Document management endpoints"),
@@ -45,6 +216,12 @@ use crate::dtos::{UploadResponse, ListDocumentsResponse, ListDocumentsQuery, Dow
         (name = "folders", description = "Folder management endpoints"),
         (name = "tags", description = "Tag management endpoints"),
         (name = "auth", description = "Authentication endpoints"),
+        (name = "users", description = "User management (admin only)"),
+        (name = "watches", description = "Document subscriptions and notifications"),
+        (name = "admin", description = "Maintenance endpoints (admin only)"),
+        (name = "aliases", description = "Short shareable document links"),
+        (name = "metadata", description = "Document metadata endpoints"),
+        (name = "system", description = "Server information"),
     ),
     info(
         title = "Document Management System API",
@@ -64,6 +241,16 @@ pub fn openapi_with_security() -> utoipa::openapi::OpenApi {
             "api_key",
             SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
         );
+        // Access tokens from /auth/login are accepted wherever the API key is
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
     }
     openapi
 }
\ No newline at end of file
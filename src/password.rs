@@ -0,0 +1,67 @@
+use crate::error::AppError;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use subtle::ConstantTimeEq;
+
+/// Length of passwords generated for admin resets
+const GENERATED_PASSWORD_LEN: usize = 20;
+
+/// Hash a password for storage in `users.password` (argon2id, PHC string)
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to hash password: {}", e)))
+}
+
+/// Check a login attempt against the stored value. Rows written before
+/// hashing was introduced still hold plain text; they are compared in
+/// constant time and should be re-hashed (see [`is_legacy_plaintext`]).
+pub fn verify_password(stored: &str, candidate: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(candidate.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => bool::from(stored.as_bytes().ct_eq(candidate.as_bytes())),
+    }
+}
+
+/// Whether a stored password predates hashing (not a PHC hash string)
+pub fn is_legacy_plaintext(stored: &str) -> bool {
+    PasswordHash::new(stored).is_err()
+}
+
+/// Complexity policy for new passwords: at least `min_len` characters
+/// (PASSWORD_MIN_LENGTH) with a lowercase letter, an uppercase letter and a digit
+pub fn validate_password(password: &str, min_len: usize) -> Result<(), AppError> {
+    if password.chars().count() < min_len {
+        return Err(AppError::BadRequest("Password is too short"));
+    }
+    let has_lower = password.chars().any(|c| c.is_lowercase());
+    let has_upper = password.chars().any(|c| c.is_uppercase());
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    if !(has_lower && has_upper && has_digit) {
+        return Err(AppError::BadRequest(
+            "Password must contain a lowercase letter, an uppercase letter and a digit",
+        ));
+    }
+    Ok(())
+}
+
+/// Random password that satisfies [`validate_password`]
+pub fn generate_password(min_len: usize) -> String {
+    let len = GENERATED_PASSWORD_LEN.max(min_len);
+    loop {
+        let candidate: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(len)
+            .map(char::from)
+            .collect();
+        if validate_password(&candidate, min_len).is_ok() {
+            return candidate;
+        }
+    }
+}
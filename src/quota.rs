@@ -0,0 +1,145 @@
+use crate::auth::CurrentUser;
+use crate::db::TimedQuery;
+use crate::error::AppError;
+use crate::models::AuditAction;
+use crate::state::AppState;
+use tracing::warn;
+
+/// Enforce the per-role download limits (DOWNLOAD_LIMITS, DOWNLOAD_BANDWIDTH_LIMITS)
+/// before serving `next_bytes` more bytes to `user`.
+///
+/// Usage is counted from successful DOWNLOAD audit rows inside the window, so
+/// it only works while downloads are being audited. Bandwidth is the
+/// `bytes_served` each row records; rows written before that was recorded
+/// fall back to the size of the version they name.
+pub async fn check_download_quota(
+    state: &AppState,
+    user: &CurrentUser,
+    next_bytes: i64,
+) -> Result<(), AppError> {
+    let role = user.role.as_str();
+    let count_limit = state.config.download_limits.get(role).copied();
+    let bytes_limit = state.config.download_bandwidth_limits.get(role).copied();
+
+    if count_limit.is_none() && bytes_limit.is_none() {
+        return Ok(());
+    }
+
+    let (downloads, bytes): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), COALESCE(SUM(COALESCE((a.metadata->>'bytes_served')::bigint, dv.file_size)), 0)::bigint
+        FROM audit_logs a
+        LEFT JOIN document_versions dv
+            ON dv.document_id = a.document_id AND dv.version_number = a.document_version
+        WHERE a.user_id = $1
+          AND a.action = $2
+          AND a.created_at > CURRENT_TIMESTAMP - make_interval(secs => $3)
+          AND COALESCE(a.metadata->>'outcome', '') <> 'failure'
+        "#,
+    )
+    .bind(user.id.to_string())
+    .bind(AuditAction::Download)
+    .bind(state.config.download_limit_window_secs as f64)
+    .fetch_one(&state.pool)
+    .timed("download_quota", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    if let Some(limit) = count_limit {
+        if downloads as u64 >= limit {
+            warn!(
+                user_id = %user.id,
+                role = %user.role,
+                downloads = downloads,
+                limit = limit,
+                "Download count limit reached"
+            );
+            return Err(AppError::TooManyRequests("Download limit reached, try again later"));
+        }
+    }
+
+    if let Some(limit) = bytes_limit {
+        if (bytes + next_bytes) as u64 > limit {
+            warn!(
+                user_id = %user.id,
+                role = %user.role,
+                bytes = bytes,
+                next_bytes = next_bytes,
+                limit = limit,
+                "Download bandwidth limit reached"
+            );
+            return Err(AppError::TooManyRequests("Download bandwidth limit reached, try again later"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Storage usage that crossed STORAGE_WARN_PCT
+pub struct StorageWarning {
+    /// `global` or `user`
+    pub scope: &'static str,
+    pub used_bytes: u64,
+    pub limit_bytes: u64,
+    pub usage_pct: u64,
+}
+
+/// Check global (STORAGE_CAPACITY_BYTES) and per-owner (USER_STORAGE_QUOTA_BYTES)
+/// usage against STORAGE_WARN_PCT, summing `document_versions.file_size`.
+/// Only informs clients; nothing is blocked. Returns the fuller scope.
+pub async fn storage_usage_warning(
+    state: &AppState,
+    user: &CurrentUser,
+) -> Result<Option<StorageWarning>, AppError> {
+    let capacity = state.config.storage_capacity_bytes;
+    let user_quota = state.config.user_storage_quota_bytes;
+
+    if capacity == 0 && user_quota == 0 {
+        return Ok(None);
+    }
+
+    let (total_bytes, user_bytes): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            COALESCE(SUM(dv.file_size), 0)::bigint,
+            COALESCE(SUM(dv.file_size) FILTER (WHERE d.created_by = $1), 0)::bigint
+        FROM document_versions dv
+        JOIN documents d ON d.id = dv.document_id
+        "#,
+    )
+    .bind(user.id)
+    .fetch_one(&state.read_pool)
+    .timed("storage_usage", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    let candidates = [
+        ("global", total_bytes as u64, capacity),
+        ("user", user_bytes as u64, user_quota),
+    ];
+
+    let warning = candidates
+        .into_iter()
+        .filter(|(_, _, limit)| *limit > 0)
+        .map(|(scope, used_bytes, limit_bytes)| StorageWarning {
+            scope,
+            used_bytes,
+            limit_bytes,
+            usage_pct: used_bytes.saturating_mul(100) / limit_bytes,
+        })
+        .filter(|w| w.usage_pct >= state.config.storage_warn_pct as u64)
+        .max_by_key(|w| w.usage_pct);
+
+    if let Some(w) = &warning {
+        warn!(
+            user_id = %user.id,
+            scope = w.scope,
+            used_bytes = w.used_bytes,
+            limit_bytes = w.limit_bytes,
+            usage_pct = w.usage_pct,
+            "Storage usage above warning threshold"
+        );
+    }
+
+    Ok(warning)
+}
@@ -0,0 +1,37 @@
+use crate::error::AppError;
+use axum::body::Body;
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use serde::Serialize;
+use tracing::warn;
+
+/// Serialize `value` as a JSON response, refusing with 413 when the body
+/// would exceed `max_bytes` (MAX_RESPONSE_BYTES). A limit of 0 disables the
+/// check. Used by list endpoints whose size grows with page size and row
+/// contents.
+pub fn capped_json<T: Serialize>(
+    label: &'static str,
+    max_bytes: usize,
+    value: &T,
+) -> Result<Response, AppError> {
+    let body = serde_json::to_vec(value)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to serialize response: {}", e)))?;
+
+    if max_bytes > 0 && body.len() > max_bytes {
+        warn!(
+            endpoint = label,
+            size_bytes = body.len(),
+            max_bytes = max_bytes,
+            "Response exceeds MAX_RESPONSE_BYTES"
+        );
+        return Err(AppError::ResponseTooLarge(
+            "Response too large; request a smaller page or narrower filter",
+        ));
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
@@ -0,0 +1,897 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::audit::{log_deferred, log_delete, log_in_tx};
+use crate::dtos::{
+    total_pages, DedupReportQuery, DedupReportResponse, DuplicateContentGroup, ExplainQuery,
+    ExplainResponse,
+    OrphanedDocumentsQuery,
+    OrphanedDocumentsResponse, RekeyDocumentResponse, RekeyedVersion,
+    ResetPasswordRequest, ResetPasswordResponse, SimulateAccessQuery,
+    SimulateAccessResponse, SimulatedAction,
+};
+use crate::error::AppError;
+use crate::mime::{sniff, OCTET_STREAM, SNIFF_PREFIX_BYTES};
+use crate::models::{AuditAction, Document, DocumentVersion, NewAuditLog, User};
+use crate::password::{generate_password, hash_password, validate_password};
+use crate::db::TimedQuery;
+use crate::routes::documents::{list_documents_page_sql, LIST_VERSIONS_SQL};
+use crate::routes::metadata::DOCUMENT_METADATA_SQL;
+use crate::routes::visibility::check_visibility_permission;
+use crate::state::AppState;
+use crate::storage::{folder_metadata_key, read_prefix, version_key, KeyNamespace};
+use axum::extract::{Path, Query, State};
+use axum::{routing::{get, post}, Json, Router};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/documents/orphaned", get(list_orphaned_documents))
+        .route("/admin/documents/:id/rekey", post(rekey_document))
+        .route("/admin/dedup-report", get(dedup_report))
+        .route("/admin/simulate-access", get(simulate_access))
+        .route("/admin/users/:id/reset-password", post(reset_password))
+        .route("/admin/explain", get(explain_endpoint))
+}
+
+/// Outcome of one [`repair_mime_batch`]
+pub(crate) struct RepairMimeBatch {
+    pub scanned: usize,
+    pub updated: usize,
+    pub unresolved: usize,
+    /// Present while more candidates may remain
+    pub next_cursor: Option<Uuid>,
+}
+
+/// Sniff and store MIME types for up to `batch_size` versions after `after_id`
+pub(crate) async fn repair_mime_batch(
+    state: &AppState,
+    after_id: Option<Uuid>,
+    batch_size: u32,
+) -> Result<RepairMimeBatch, AppError> {
+    // Walk candidates in id order so a caller can resume from `next_cursor`
+    let versions = sqlx::query_as::<_, DocumentVersion>(
+        r#"
+        SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+        FROM document_versions
+        WHERE (mime_type IS NULL OR mime_type = $1)
+          AND ($2::uuid IS NULL OR id > $2)
+        ORDER BY id
+        LIMIT $3
+        "#,
+    )
+    .bind(OCTET_STREAM)
+    .bind(after_id)
+    .bind(batch_size as i64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut updated = 0;
+    let mut unresolved = 0;
+
+    for version in &versions {
+        let prefix = match read_prefix(&state.storage, &version.file_path, SNIFF_PREFIX_BYTES).await {
+            Ok(prefix) => prefix,
+            Err(e) => {
+                warn!(
+                    error = ?e,
+                    version_id = %version.id,
+                    file_path = %version.file_path,
+                    "Failed to read object prefix, skipping"
+                );
+                unresolved += 1;
+                continue;
+            }
+        };
+
+        let Some(detected) = sniff(&prefix) else {
+            debug!(version_id = %version.id, "Could not detect MIME type");
+            unresolved += 1;
+            continue;
+        };
+
+        sqlx::query("UPDATE document_versions SET mime_type = $1 WHERE id = $2")
+            .bind(&detected)
+            .bind(version.id)
+            .execute(&state.pool)
+            .await
+            .map_err(AppError::Db)?;
+
+        debug!(version_id = %version.id, mime_type = %detected, "MIME type repaired");
+        updated += 1;
+    }
+
+    let next_cursor = if versions.len() as u32 == batch_size {
+        versions.last().map(|v| v.id)
+    } else {
+        None
+    };
+
+    info!(
+        scanned = versions.len(),
+        updated = updated,
+        unresolved = unresolved,
+        "MIME type repair batch finished"
+    );
+
+    Ok(RepairMimeBatch {
+        scanned: versions.len(),
+        updated,
+        unresolved,
+        next_cursor,
+    })
+}
+
+/// Documents that have no versions at all (e.g. an interrupted upload)
+#[utoipa::path(
+    get,
+    path = "/admin/documents/orphaned",
+    tag = "admin",
+    params(
+        ("cleanup" = Option<bool>, Query, description = "Soft-delete the reported documents")
+    ),
+    responses(
+        (status = 200, description = "Documents without any versions", body = OrphanedDocumentsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_orphaned_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<OrphanedDocumentsQuery>,
+) -> Result<Json<OrphanedDocumentsResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let orphaned = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT d.id, d.title, d.category, d.created_by, d.is_public, d.deleted_at, d.created_at, d.updated_at
+        FROM documents d
+        WHERE d.deleted_at IS NULL
+          AND NOT EXISTS (SELECT 1 FROM document_versions dv WHERE dv.document_id = d.id)
+        ORDER BY d.created_at
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut cleaned_up = 0;
+
+    if query.cleanup.unwrap_or(false) {
+        for doc in &orphaned {
+            // Re-check for versions so a concurrent upload is not hidden
+            let rows_affected = sqlx::query(
+                r#"
+                UPDATE documents
+                SET deleted_at = CURRENT_TIMESTAMP
+                WHERE id = $1
+                  AND deleted_at IS NULL
+                  AND NOT EXISTS (SELECT 1 FROM document_versions WHERE document_id = $1)
+                "#,
+            )
+            .bind(doc.id)
+            .execute(&state.pool)
+            .await
+            .map_err(AppError::Db)?
+            .rows_affected();
+
+            if rows_affected == 0 {
+                continue;
+            }
+            cleaned_up += rows_affected;
+
+            if let Err(e) = log_delete(
+                &state,
+                current_user.id.to_string(),
+                doc.id,
+                Some(serde_json::json!({
+                    "delete_type": "soft",
+                    "reason": "orphaned_document_cleanup",
+                    "title": &doc.title,
+                })),
+            )
+            .await
+            {
+                warn!(
+                    error = ?e,
+                    document_id = %doc.id,
+                    "Failed to create audit log for orphaned document cleanup"
+                );
+            }
+        }
+    }
+
+    info!(
+        orphaned = orphaned.len(),
+        cleaned_up = cleaned_up,
+        "Orphaned documents report generated"
+    );
+
+    Ok(Json(OrphanedDocumentsResponse {
+        total: orphaned.len(),
+        data: orphaned,
+        cleaned_up,
+    }))
+}
+
+/// Best-effort removal of objects written during a failed multi-step operation
+async fn cleanup_objects(state: &AppState, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = state.storage.delete(key).await {
+            warn!(error = ?e, key = %key, "Failed to clean up storage object");
+        }
+    }
+}
+
+/// Move a document's version objects to the keys of the configured KEY_STRATEGY
+#[utoipa::path(
+    post,
+    path = "/admin/documents/{id}/rekey",
+    tag = "admin",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Version objects moved to the current key strategy", body = RekeyDocumentResponse),
+        (status = 404, description = "Document not found"),
+        (status = 400, description = "Target key already exists"),
+        (status = 502, description = "Storage copy failed; nothing was changed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn rekey_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<RekeyDocumentResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let response = rekey(&state, document_id).await?;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        rekeyed = response.rekeyed.len(),
+        unchanged = response.unchanged,
+        "Document rekeyed"
+    );
+
+    Ok(Json(response))
+}
+
+/// Copy a document's version objects to their current keys, repoint the
+/// versions, then drop the old objects. Nothing changes if a copy fails.
+async fn rekey(state: &AppState, document_id: Uuid) -> Result<RekeyDocumentResponse, AppError> {
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found"))?;
+
+    let versions = sqlx::query_as::<_, DocumentVersion>(
+        r#"
+        SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+        FROM document_versions
+        WHERE document_id = $1
+        ORDER BY version_number
+        "#,
+    )
+    .bind(document_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut moves = Vec::new();
+    let mut unchanged = 0;
+
+    for version in &versions {
+        let new_key = version_key(
+            state.config.key_strategy,
+            state.config.key_namespaces,
+            document.category.as_deref(),
+            &document.title,
+            document.id,
+            version.version_number,
+        );
+
+        if new_key == version.file_path {
+            unchanged += 1;
+            continue;
+        }
+
+        // Never overwrite an object we don't own
+        if state.storage.stat(&new_key).await.is_ok() {
+            warn!(key = %new_key, "Rekey target already exists");
+            return Err(AppError::BadRequest("Rekey target key already exists in storage"));
+        }
+
+        moves.push((version.id, RekeyedVersion {
+            version_number: version.version_number,
+            old_key: version.file_path.clone(),
+            new_key,
+        }));
+    }
+
+    // Copy first; the old objects stay until the database points at the new keys
+    let mut copied: Vec<String> = Vec::new();
+    for (_, m) in &moves {
+        if let Err(e) = state.storage.copy(&m.old_key, &m.new_key).await {
+            error!(error = ?e, old_key = %m.old_key, new_key = %m.new_key, "Failed to copy object during rekey");
+            cleanup_objects(state, &copied).await;
+            return Err(AppError::BadGateway("Failed to copy object to its new key; rekey rolled back"));
+        }
+        copied.push(m.new_key.clone());
+    }
+
+    let mut tx = state.pool.begin().await?;
+    for (version_id, m) in &moves {
+        if let Err(e) = sqlx::query("UPDATE document_versions SET file_path = $1 WHERE id = $2")
+            .bind(&m.new_key)
+            .bind(version_id)
+            .execute(&mut *tx)
+            .await
+        {
+            cleanup_objects(state, &copied).await;
+            return Err(AppError::Db(e));
+        }
+    }
+    if let Err(e) = tx.commit().await {
+        cleanup_objects(state, &copied).await;
+        return Err(AppError::Db(e));
+    }
+
+    let old_keys: Vec<String> = moves.iter().map(|(_, m)| m.old_key.clone()).collect();
+    cleanup_objects(state, &old_keys).await;
+
+    Ok(RekeyDocumentResponse {
+        document_id,
+        rekeyed: moves.into_iter().map(|(_, m)| m).collect(),
+        unchanged,
+    })
+}
+
+/// Checksums stored in more than one object, i.e. what content-addressed
+/// storage would save. Versions already sharing an object count once.
+#[utoipa::path(
+    get,
+    path = "/admin/dedup-report",
+    tag = "admin",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number (default: 1)"),
+        ("page_size" = Option<u32>, Query, description = "Page size (default: 20, max: 100)")
+    ),
+    responses(
+        (status = 200, description = "Duplicate content groups, largest savings first", body = DedupReportResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn dedup_report(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<DedupReportQuery>,
+) -> Result<Json<DedupReportResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) as i64 * page_size as i64;
+
+    let (total, total_reclaimable_bytes): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), COALESCE(SUM(reclaimable_bytes), 0)::bigint
+        FROM (
+            SELECT (COUNT(DISTINCT file_path) - 1) * MAX(file_size) AS reclaimable_bytes
+            FROM document_versions
+            WHERE checksum IS NOT NULL
+            GROUP BY checksum
+            HAVING COUNT(DISTINCT file_path) > 1
+        ) groups
+        "#,
+    )
+    .fetch_one(&state.read_pool)
+    .timed("dedup_report.totals", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    let groups = sqlx::query_as::<_, DuplicateContentGroup>(
+        r#"
+        SELECT
+            checksum,
+            COUNT(DISTINCT file_path) AS object_count,
+            ARRAY_AGG(DISTINCT document_id) AS document_ids,
+            MAX(file_size) AS file_size,
+            (COUNT(DISTINCT file_path) - 1) * MAX(file_size) AS reclaimable_bytes
+        FROM document_versions
+        WHERE checksum IS NOT NULL
+        GROUP BY checksum
+        HAVING COUNT(DISTINCT file_path) > 1
+        ORDER BY reclaimable_bytes DESC, checksum
+        LIMIT $1 OFFSET $2
+        "#,
+    )
+    .bind(page_size as i64)
+    .bind(offset)
+    .fetch_all(&state.read_pool)
+    .timed("dedup_report.page", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    info!(
+        user_id = %current_user.id,
+        groups = total,
+        reclaimable_bytes = total_reclaimable_bytes,
+        "Dedup report generated"
+    );
+
+    Ok(Json(DedupReportResponse {
+        data: groups,
+        page,
+        page_size,
+        total,
+        total_pages: total_pages(total, page_size),
+        total_reclaimable_bytes,
+    }))
+}
+
+/// Outcome of one [`migrate_namespaces_batch`]
+pub(crate) struct NamespaceMigrationBatch {
+    pub folders_moved: usize,
+    pub documents_rekeyed: usize,
+    pub versions_moved: usize,
+    /// Documents that could not be moved (e.g. target key already taken)
+    pub failed: Vec<Uuid>,
+    /// Documents still having versions outside `versions/`, not counting skipped ones
+    pub remaining: i64,
+}
+
+/// Move legacy folder markers, then rekey up to `batch_size` documents whose
+/// versions still live outside `versions/`. Documents in `skip` (typically
+/// ones that already failed) are neither picked nor counted as remaining.
+pub(crate) async fn migrate_namespaces_batch(
+    state: &AppState,
+    batch_size: u32,
+    skip: &[Uuid],
+) -> Result<NamespaceMigrationBatch, AppError> {
+    // Legacy folder markers live at `{folder}/.folder_metadata.json`, with
+    // nested folders under their parent's directory. Walk breadth-first,
+    // descending only into folders, as list_folders does.
+    let mut folders_moved = 0;
+    let reserved = [
+        KeyNamespace::Versions.prefix(),
+        KeyNamespace::Folders.prefix(),
+        KeyNamespace::Uploads.prefix(),
+    ];
+    let mut pending: VecDeque<String> = VecDeque::from([String::new()]);
+    while let Some(dir) = pending.pop_front() {
+        for entry in state.storage.list(&dir).await? {
+            let path = entry.path();
+            if !path.ends_with('/') || path == dir || (dir.is_empty() && reserved.contains(&path)) {
+                continue;
+            }
+
+            let folder_name = path.trim_end_matches('/');
+            let old_key = folder_metadata_key(false, folder_name);
+            let new_key = folder_metadata_key(true, folder_name);
+
+            if state.storage.stat(&old_key).await.is_ok() {
+                if state.storage.stat(&new_key).await.is_err() {
+                    state.storage.copy(&old_key, &new_key).await?;
+                }
+                state.storage.delete(&old_key).await?;
+                folders_moved += 1;
+            } else if state.storage.stat(&new_key).await.is_err() {
+                // Not a folder (e.g. a category of version keys)
+                continue;
+            }
+            pending.push_back(path.to_string());
+        }
+    }
+
+    let legacy_pattern = format!("{}%", KeyNamespace::Versions.prefix());
+
+    let document_ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT document_id
+        FROM document_versions
+        WHERE file_path NOT LIKE $1 AND document_id <> ALL($3)
+        ORDER BY document_id
+        LIMIT $2
+        "#,
+    )
+    .bind(&legacy_pattern)
+    .bind(batch_size as i64)
+    .bind(skip)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut documents_rekeyed = 0;
+    let mut versions_moved = 0;
+    let mut failed = Vec::new();
+
+    for document_id in document_ids {
+        match rekey(state, document_id).await {
+            Ok(result) => {
+                documents_rekeyed += 1;
+                versions_moved += result.rekeyed.len();
+            }
+            Err(e) => {
+                warn!(error = ?e, document_id = %document_id, "Failed to migrate document keys");
+                failed.push(document_id);
+            }
+        }
+    }
+
+    let remaining: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT document_id) FROM document_versions WHERE file_path NOT LIKE $1 AND document_id <> ALL($2)"
+    )
+    .bind(&legacy_pattern)
+    .bind(skip)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    Ok(NamespaceMigrationBatch {
+        folders_moved,
+        documents_rekeyed,
+        versions_moved,
+        failed,
+        remaining,
+    })
+}
+
+/// Actions reported by the access simulation, in the order they are listed
+const SIMULATED_ACTIONS: &[(&str, StorageAction)] = &[
+    ("read", StorageAction::Read),
+    ("stat", StorageAction::Stat),
+    ("write", StorageAction::Write),
+    ("delete", StorageAction::Delete),
+    ("get_actions", StorageAction::GetActions),
+    ("admin", StorageAction::Admin),
+];
+
+/// Report which actions a user could perform on a document, using the same
+/// checks the handlers run, without performing any of them
+#[utoipa::path(
+    get,
+    path = "/admin/simulate-access",
+    tag = "admin",
+    params(
+        ("user_id" = Uuid, Query, description = "User to simulate"),
+        ("document_id" = Uuid, Query, description = "Target document")
+    ),
+    responses(
+        (status = 200, description = "Simulated permission outcomes", body = SimulateAccessResponse),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn simulate_access(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<SimulateAccessQuery>,
+) -> Result<Json<SimulateAccessResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, api_key, password, role, created_at
+        FROM users
+        WHERE id = $1
+        "#,
+    )
+    .bind(query.user_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("User not found"))?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(query.document_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let simulated = CurrentUser {
+        id: user.id,
+        username: user.username,
+        role: user.role,
+        permissions: state.config.permission_overrides.clone(),
+    };
+
+    let mut actions: Vec<SimulatedAction> = SIMULATED_ACTIONS
+        .iter()
+        .map(|(name, action)| {
+            let outcome = check_permission(&simulated, *action);
+            SimulatedAction {
+                action: name,
+                allowed: outcome.is_ok(),
+                reason: outcome.err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+
+    // Same checks as POST /documents/{id}/visibility
+    let visibility = check_permission(&simulated, StorageAction::Write).and_then(|_| match &document {
+        Some(document) => check_visibility_permission(&simulated, document),
+        None => Err(AppError::NotFound("Document not found or has been deleted")),
+    });
+    actions.push(SimulatedAction {
+        action: "set_visibility",
+        allowed: visibility.is_ok(),
+        reason: visibility.err().map(|e| e.to_string()),
+    });
+
+    info!(
+        user_id = %current_user.id,
+        simulated_user_id = %simulated.id,
+        document_id = %query.document_id,
+        "Access simulated"
+    );
+
+    Ok(Json(SimulateAccessResponse {
+        user_id: simulated.id,
+        username: simulated.username,
+        role: simulated.role,
+        document_id: query.document_id,
+        document_available: document.is_some(),
+        actions,
+    }))
+}
+
+/// Set a new password for a user, either the one supplied or a generated one
+/// returned once in the response. API keys are left alone; there are no
+/// sessions to revoke.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/reset-password",
+    tag = "admin",
+    params(
+        ("id" = Uuid, Path, description = "User ID")
+    ),
+    request_body(content = ResetPasswordRequest, description = "Optional new password"),
+    responses(
+        (status = 200, description = "Password reset", body = ResetPasswordResponse),
+        (status = 400, description = "Password does not meet the complexity policy"),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(user_id): Path<Uuid>,
+    request: Option<Json<ResetPasswordRequest>>,
+) -> Result<Json<ResetPasswordResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let min_len = state.config.password_min_length;
+    let supplied = request.and_then(|Json(r)| r.password);
+
+    let (password, generated) = match supplied {
+        Some(password) => {
+            validate_password(&password, min_len)?;
+            (password, false)
+        }
+        None => (generate_password(min_len), true),
+    };
+
+    let hashed = hash_password(&password)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let updated = sqlx::query("UPDATE users SET password = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(&hashed)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("User not found"));
+    }
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::ResetPassword,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({
+                "target_user_id": user_id,
+                "generated": generated,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        admin_id = %current_user.id,
+        user_id = %user_id,
+        generated = generated,
+        "Password reset by admin"
+    );
+
+    Ok(Json(ResetPasswordResponse {
+        user_id,
+        generated_password: generated.then_some(password),
+    }))
+}
+
+/// Endpoints `/admin/explain` knows the query of
+const EXPLAIN_ENDPOINTS: &[&str] = &["list_documents", "list_versions", "document_metadata"];
+
+/// The sample values bound for `list_documents`, one per placeholder, as shown
+/// in the response
+const LIST_DOCUMENTS_SAMPLE_PARAMS: [&str; 13] =
+    ["''", "NULL", "{}", "false", "NULL", "NULL", "NULL", "NULL", "NULL", "{}", "{}", "20", "0"];
+
+/// Upper bound for one `EXPLAIN ANALYZE` run
+const EXPLAIN_STATEMENT_TIMEOUT_MS: u64 = 5_000;
+
+/// Run `EXPLAIN (ANALYZE, FORMAT JSON)` on the query behind a named endpoint
+/// with sample parameters, to spot missing indexes. The statement runs in a
+/// read-only transaction that is always rolled back.
+#[utoipa::path(
+    get,
+    path = "/admin/explain",
+    tag = "admin",
+    params(
+        ("endpoint" = String, Query, description = "One of `list_documents`, `list_versions`, `document_metadata`")
+    ),
+    responses(
+        (status = 200, description = "Query plan", body = ExplainResponse),
+        (status = 400, description = "Unknown endpoint"),
+        (status = 404, description = "Disabled (ADMIN_EXPLAIN is not set)"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn explain_endpoint(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<ExplainQuery>,
+) -> Result<Json<ExplainResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    if !state.config.admin_explain {
+        return Err(AppError::NotFound("EXPLAIN endpoint is disabled (ADMIN_EXPLAIN is not set)"));
+    }
+
+    let endpoint = query.endpoint.trim();
+    if !EXPLAIN_ENDPOINTS.contains(&endpoint) {
+        return Err(AppError::BadRequest(
+            "endpoint must be list_documents, list_versions or document_metadata",
+        ));
+    }
+
+    let mut tx = state.read_pool.begin().await.map_err(AppError::Db)?;
+
+    // ANALYZE really executes the statement, so keep it read-only and bounded
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+    sqlx::query(&format!("SET LOCAL statement_timeout = {}", EXPLAIN_STATEMENT_TIMEOUT_MS))
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    // Per-document queries run against the most recently touched live document
+    let sample_document: Uuid = sqlx::query_scalar(
+        "SELECT id FROM documents WHERE deleted_at IS NULL ORDER BY updated_at DESC LIMIT 1",
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .unwrap_or_else(Uuid::nil);
+
+    let (sql, params, plan): (String, Vec<String>, serde_json::Value) = match endpoint {
+        "list_documents" => {
+            let sql = list_documents_page_sql(Default::default(), Default::default());
+            let explain = format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", sql);
+            let plan = sqlx::query_scalar(&explain)
+                .bind("")
+                .bind(None::<String>)
+                .bind(Vec::<String>::new())
+                .bind(false)
+                .bind(None::<String>)
+                .bind(None::<DateTime<Utc>>)
+                .bind(None::<DateTime<Utc>>)
+                .bind(None::<DateTime<Utc>>)
+                .bind(None::<DateTime<Utc>>)
+                .bind(Vec::<String>::new())
+                .bind(Vec::<String>::new())
+                .bind(20i64)
+                .bind(0i64)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(AppError::Db)?;
+            let params = LIST_DOCUMENTS_SAMPLE_PARAMS.iter().map(|p| p.to_string()).collect();
+            (sql, params, plan)
+        }
+        _ => {
+            let sql = if endpoint == "list_versions" { LIST_VERSIONS_SQL } else { DOCUMENT_METADATA_SQL };
+            let explain = format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", sql);
+            let plan = sqlx::query_scalar(&explain)
+                .bind(sample_document)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(AppError::Db)?;
+            (sql.to_string(), vec![sample_document.to_string()], plan)
+        }
+    };
+
+    tx.rollback().await.map_err(AppError::Db)?;
+
+    info!(user_id = %current_user.id, endpoint = %endpoint, "Explained endpoint query");
+
+    Ok(Json(ExplainResponse {
+        endpoint: endpoint.to_string(),
+        query: sql,
+        params,
+        plan,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Highest `$n` placeholder in `sql`
+    fn placeholder_count(sql: &str) -> usize {
+        sql.split('$')
+            .skip(1)
+            .filter_map(|rest| {
+                let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().ok()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn list_documents_sample_params_cover_every_placeholder() {
+        let sql = list_documents_page_sql(Default::default(), Default::default());
+        assert_eq!(placeholder_count(&sql), LIST_DOCUMENTS_SAMPLE_PARAMS.len());
+    }
+
+    #[test]
+    fn placeholder_count_reads_multi_digit_placeholders() {
+        assert_eq!(placeholder_count("a = $1 AND b = ANY($12::text[]) AND c = $3"), 12);
+        assert_eq!(placeholder_count("SELECT 1"), 0);
+    }
+}
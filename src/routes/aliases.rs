@@ -0,0 +1,175 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{CreateAliasRequest, CreateAliasResponse, DownloadQuery};
+use crate::error::AppError;
+use crate::models::DocumentAlias;
+use crate::routes::documents::{download, resolve_version, serve_version};
+use crate::routes::visibility::{is_public_document, ANONYMOUS_USER};
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::{routing::{get, post}, Json, Router};
+use chrono::{Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Length of generated slugs (62^10 possibilities)
+const SLUG_LENGTH: usize = 10;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/documents/:id/alias", post(create_alias))
+        .route("/d/:slug", get(resolve_alias))
+}
+
+fn generate_slug() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SLUG_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/alias",
+    tag = "aliases",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body(content = CreateAliasRequest, description = "Optional expiry", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Alias created", body = CreateAliasResponse),
+        (status = 400, description = "Invalid expiry"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_alias(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    request: Option<Json<CreateAliasRequest>>,
+) -> Result<Json<CreateAliasResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let expires_at = match request.and_then(|Json(r)| r.expires_in_secs) {
+        Some(secs) if secs <= 0 => {
+            return Err(AppError::BadRequest("expires_in_secs must be positive"));
+        }
+        Some(secs) => Some(Utc::now() + Duration::seconds(secs)),
+        None => None,
+    };
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    // Retry on the (unlikely) slug collision
+    let mut alias = None;
+    for _ in 0..3 {
+        alias = sqlx::query_as::<_, DocumentAlias>(
+            r#"
+            INSERT INTO document_aliases (slug, document_id, created_by, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (slug) DO NOTHING
+            RETURNING slug, document_id, created_by, expires_at, created_at
+            "#,
+        )
+        .bind(generate_slug())
+        .bind(document_id)
+        .bind(current_user.id)
+        .bind(expires_at)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(AppError::Db)?;
+
+        if alias.is_some() {
+            break;
+        }
+        warn!("Alias slug collision, retrying");
+    }
+
+    let alias = alias.ok_or_else(|| {
+        AppError::Other(anyhow::anyhow!("Failed to generate a unique alias slug"))
+    })?;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        slug = %alias.slug,
+        expires_at = ?alias.expires_at,
+        "Document alias created"
+    );
+
+    Ok(Json(CreateAliasResponse {
+        url: format!("/d/{}", alias.slug),
+        slug: alias.slug,
+        document_id,
+        expires_at: alias.expires_at,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/d/{slug}",
+    tag = "aliases",
+    params(
+        ("slug" = String, Path, description = "Alias slug")
+    ),
+    responses(
+        (status = 200, description = "Latest version content", content_type = "application/octet-stream"),
+        (status = 404, description = "Alias not found or expired"),
+        (status = 401, description = "Unauthorized - API key required for private documents")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn resolve_alias(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    current_user: Option<CurrentUser>,
+) -> Result<Response, AppError> {
+    let document_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT document_id
+        FROM document_aliases
+        WHERE slug = $1 AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+        "#,
+    )
+    .bind(&slug)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let Some(document_id) = document_id else {
+        return Err(AppError::NotFound("Alias not found or expired"));
+    };
+
+    debug!(slug = %slug, document_id = %document_id, "Alias resolved");
+
+    // Public documents are shareable without an API key
+    if is_public_document(&state, document_id).await? {
+        let user_id = current_user
+            .map(|u| u.id.to_string())
+            .unwrap_or_else(|| ANONYMOUS_USER.to_string());
+        let dv = resolve_version(&state, &state.pool, document_id, None).await?;
+        return serve_version(&state, dv, user_id, true, None).await;
+    }
+
+    let Some(current_user) = current_user else {
+        return Err(AppError::Unauthorized("API key required for this document"));
+    };
+
+    download(&state, document_id, DownloadQuery { version: None, verify: None }, &current_user, &HeaderMap::new()).await
+}
@@ -0,0 +1,350 @@
+use crate::audit::log_download;
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::ArchiveRequest;
+use crate::error::AppError;
+use crate::models::DocumentVersion;
+use crate::quota::check_download_quota;
+use crate::routes::documents::resolve_version;
+use crate::state::AppState;
+use crate::storage::sanitize_segment;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::{routing::post, Json, Router};
+use chrono::{Datelike, Timelike, Utc};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Most documents one archive may contain
+const ARCHIVE_MAX_DOCUMENTS: usize = 100;
+
+/// Entries are written without ZIP64 records, so every size and offset must fit in 32 bits
+const ZIP32_MAX_BYTES: u64 = u32::MAX as u64 - 64 * 1024 * 1024;
+
+/// General purpose flags: sizes and CRC follow the data (bit 3), UTF-8 names (bit 11)
+const ZIP_FLAGS: u16 = 0x0808;
+
+type Chunk = Result<Bytes, std::io::Error>;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/documents/archive", post(archive_documents))
+}
+
+/// One document's latest version and its name inside the archive
+struct ArchiveEntry {
+    version: DocumentVersion,
+    name: String,
+}
+
+/// What the central directory needs to know about a written entry
+struct WrittenEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A size or offset for a ZIP field, which holds 32 bits without ZIP64
+fn zip32(value: u64) -> Result<u32, std::io::Error> {
+    u32::try_from(value).map_err(|_| std::io::Error::other("archive exceeds the 4 GiB ZIP limit"))
+}
+
+/// MS-DOS time and date of `now`, as stored in ZIP headers
+fn dos_timestamp() -> (u16, u16) {
+    let now = Utc::now();
+    let time = ((now.hour() as u16) << 11) | ((now.minute() as u16) << 5) | (now.second() as u16 / 2);
+    let year = (now.year().clamp(1980, 2107) - 1980) as u16;
+    let date = (year << 9) | ((now.month() as u16) << 5) | now.day() as u16;
+    (time, date)
+}
+
+/// Local file header for a stored (uncompressed) entry; CRC and sizes go in
+/// the data descriptor written after the content
+fn local_header(name: &str, (time, date): (u16, u16)) -> Bytes {
+    let mut buf = Vec::with_capacity(30 + name.len());
+    buf.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+    buf.extend_from_slice(&20u16.to_le_bytes()); // version needed
+    buf.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+    buf.extend_from_slice(&0u16.to_le_bytes()); // stored
+    buf.extend_from_slice(&time.to_le_bytes());
+    buf.extend_from_slice(&date.to_le_bytes());
+    buf.extend_from_slice(&[0u8; 12]); // crc, compressed and uncompressed size
+    buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    buf.extend_from_slice(&0u16.to_le_bytes()); // extra length
+    buf.extend_from_slice(name.as_bytes());
+    Bytes::from(buf)
+}
+
+fn data_descriptor(crc: u32, size: u32) -> Bytes {
+    let mut buf = Vec::with_capacity(16);
+    buf.extend_from_slice(&0x0807_4b50u32.to_le_bytes());
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf.extend_from_slice(&size.to_le_bytes());
+    buf.extend_from_slice(&size.to_le_bytes());
+    Bytes::from(buf)
+}
+
+/// Central directory plus end-of-central-directory record
+fn central_directory(entries: &[WrittenEntry], offset: u32, (time, date): (u16, u16)) -> Bytes {
+    let mut buf = Vec::new();
+    for entry in entries {
+        buf.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        buf.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        buf.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        buf.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes()); // stored
+        buf.extend_from_slice(&time.to_le_bytes());
+        buf.extend_from_slice(&date.to_le_bytes());
+        buf.extend_from_slice(&entry.crc.to_le_bytes());
+        buf.extend_from_slice(&entry.size.to_le_bytes());
+        buf.extend_from_slice(&entry.size.to_le_bytes());
+        buf.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&[0u8; 12]); // extra, comment, disk, internal and external attributes
+        buf.extend_from_slice(&entry.offset.to_le_bytes());
+        buf.extend_from_slice(entry.name.as_bytes());
+    }
+
+    let directory_size = buf.len() as u32;
+    let count = entries.len() as u16;
+    buf.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    buf.extend_from_slice(&[0u8; 4]); // disk numbers
+    buf.extend_from_slice(&count.to_le_bytes());
+    buf.extend_from_slice(&count.to_le_bytes());
+    buf.extend_from_slice(&directory_size.to_le_bytes());
+    buf.extend_from_slice(&offset.to_le_bytes());
+    buf.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    Bytes::from(buf)
+}
+
+/// Archive name for a document: its title plus the extension of the stored
+/// file name, made unique with a ` (n)` suffix
+fn entry_name(title: &str, file_name: &str, taken: &[ArchiveEntry]) -> String {
+    let stem = sanitize_segment(title);
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| format!(".{}", sanitize_segment(ext)))
+        .unwrap_or_default();
+
+    let mut name = format!("{}{}", stem, extension);
+    let mut n = 2;
+    while taken.iter().any(|e| e.name == name) {
+        name = format!("{} ({}){}", stem, n, extension);
+        n += 1;
+    }
+    name
+}
+
+/// Stream a ZIP holding the latest version of each listed document. Missing
+/// and deleted ids are skipped. Entries are stored uncompressed so nothing is
+/// buffered beyond one chunk.
+#[utoipa::path(
+    post,
+    path = "/documents/archive",
+    tag = "documents",
+    request_body = ArchiveRequest,
+    responses(
+        (status = 200, description = "ZIP of the documents' latest versions, named by title", content_type = "application/zip"),
+        (status = 400, description = "Empty or oversized id list"),
+        (status = 404, description = "None of the documents exist"),
+        (status = 413, description = "Combined size exceeds ARCHIVE_MAX_BYTES"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Per-role download limit reached")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn archive_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<ArchiveRequest>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    if request.ids.is_empty() {
+        return Err(AppError::BadRequest("ids cannot be empty"));
+    }
+    if request.ids.len() > ARCHIVE_MAX_DOCUMENTS {
+        return Err(AppError::BadRequest("Too many ids (max 100)"));
+    }
+
+    let mut document_ids: Vec<Uuid> = Vec::with_capacity(request.ids.len());
+    for id in request.ids {
+        if !document_ids.contains(&id) {
+            document_ids.push(id);
+        }
+    }
+
+    let titles: Vec<(Uuid, String)> = sqlx::query_as(
+        "SELECT id, title FROM documents WHERE id = ANY($1) AND deleted_at IS NULL"
+    )
+    .bind(&document_ids)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut entries: Vec<ArchiveEntry> = Vec::with_capacity(titles.len());
+    for document_id in &document_ids {
+        let Some((_, title)) = titles.iter().find(|(id, _)| id == document_id) else {
+            continue;
+        };
+        let version = match resolve_version(&state, &state.read_pool, *document_id, None).await {
+            Ok(version) => version,
+            Err(AppError::NotFound(reason)) => {
+                warn!(document_id = %document_id, reason = reason, "Skipping document without content");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let name = entry_name(title, &version.file_name, &entries);
+        entries.push(ArchiveEntry { version, name });
+    }
+
+    if entries.is_empty() {
+        return Err(AppError::NotFound("None of the requested documents were found"));
+    }
+
+    let total_size: i64 = entries.iter().map(|e| e.version.file_size).sum();
+    let max_bytes = state.config.archive_max_bytes.min(ZIP32_MAX_BYTES);
+    if total_size as u64 > max_bytes {
+        return Err(AppError::ResponseTooLarge("Archive exceeds ARCHIVE_MAX_BYTES"));
+    }
+
+    check_download_quota(&state, &current_user, total_size).await?;
+
+    for entry in &entries {
+        if let Err(e) = log_download(
+            &state,
+            current_user.id.to_string(),
+            entry.version.document_id,
+            Some(entry.version.version_number),
+            Some(serde_json::json!({ "bytes_served": entry.version.file_size })),
+        )
+        .await
+        {
+            warn!(
+                error = ?e,
+                document_id = %entry.version.document_id,
+                user_id = %current_user.id,
+                "Failed to create audit log for archived document"
+            );
+        }
+    }
+
+    info!(
+        user_id = %current_user.id,
+        requested = document_ids.len(),
+        included = entries.len(),
+        total_size = total_size,
+        "Streaming document archive"
+    );
+
+    let (mut tx, rx) = mpsc::channel::<Chunk>(8);
+
+    tokio::spawn(async move {
+        let timestamp = dos_timestamp();
+        let mut written: Vec<WrittenEntry> = Vec::with_capacity(entries.len());
+        let mut offset: u64 = 0;
+
+        for entry in entries {
+            let header = local_header(&entry.name, timestamp);
+            let header_len = header.len() as u64;
+            if tx.send(Ok(header)).await.is_err() {
+                return;
+            }
+
+            let stream = match state.storage.reader(&entry.version.file_path).await {
+                Ok(reader) => reader.into_bytes_stream(..).await,
+                Err(e) => Err(e),
+            };
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!(error = ?e, file_path = %entry.version.file_path, "Failed to open object, aborting archive");
+                    let _ = tx.send(Err(std::io::Error::other(e))).await;
+                    return;
+                }
+            };
+
+            // The up-front size check assumed `file_size`; an object that
+            // changed since would corrupt the archive, so abort instead
+            let expected = entry.version.file_size as u64;
+            let mut crc = crc32fast::Hasher::new();
+            let mut size: u64 = 0;
+            while let Some(chunk) = stream.next().await {
+                let bytes = match chunk {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        error!(error = ?e, file_path = %entry.version.file_path, "Failed reading object, aborting archive");
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                size += bytes.len() as u64;
+                if size > expected {
+                    break;
+                }
+                crc.update(&bytes);
+                if tx.send(Ok(bytes)).await.is_err() {
+                    return;
+                }
+            }
+            if size != expected {
+                error!(
+                    file_path = %entry.version.file_path,
+                    expected = expected,
+                    streamed = size,
+                    "Object changed size while archiving, aborting archive"
+                );
+                let _ = tx.send(Err(std::io::Error::other("object changed size while archiving"))).await;
+                return;
+            }
+
+            let (size32, offset32) = match (zip32(size), zip32(offset)) {
+                (Ok(size), Ok(offset)) => (size, offset),
+                (Err(e), _) | (_, Err(e)) => {
+                    error!(error = ?e, "Archive outgrew 32-bit ZIP fields, aborting archive");
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            let crc = crc.finalize();
+            if tx.send(Ok(data_descriptor(crc, size32))).await.is_err() {
+                return;
+            }
+
+            written.push(WrittenEntry {
+                name: entry.name,
+                crc,
+                size: size32,
+                offset: offset32,
+            });
+            offset += header_len + size + 16;
+        }
+
+        let directory_offset = match zip32(offset) {
+            Ok(offset) => offset,
+            Err(e) => {
+                error!(error = ?e, "Archive outgrew 32-bit ZIP fields, aborting archive");
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        let _ = tx.send(Ok(central_directory(&written, directory_offset, timestamp))).await;
+        info!(entries = written.len(), "Archive stream finished");
+    });
+
+    let file_name = format!("documents-{}.zip", Utc::now().format("%Y%m%dT%H%M%SZ"));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(Body::from_stream(rx))
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to build archive response: {}", e)))
+}
@@ -1,22 +1,43 @@
 use crate::models::{AuditLog};
 use crate::error::AppError;
-use crate::{state::AppState,dtos::AuditResponse};
+use crate::{state::AppState,dtos::{AuditResponse, AuditExportQuery, AuditQuery, AuditEventsQuery}};
 use crate::auth::{CurrentUser, check_permission, StorageAction};
-use tracing::{info, warn, error,debug};
+use tracing::{info, error,debug};
 use axum::{routing::get, Router};
-use axum::extract::{State};
-use axum::Json;
+use axum::extract::{State, Path, Query};
+use axum::body::Body;
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::db::TimedQuery;
+use crate::response::capped_json;
+use crate::audit::audit_event;
+use axum::body::Bytes;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
 
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/audit", get(get_actions))
+    Router::new()
+        .route("/audit", get(get_actions))
+        .route("/audit/export.jsonl", get(export_audit_events))
+        .route("/documents/:id/audit/export", get(export_document_audit))
 }
 
 #[utoipa::path(
     get,
     path = "/audit",
     tag = "audit",
+    params(
+        ("since_id" = Option<Uuid>, Query, description = "Only rows newer than this audit row (polling cursor)"),
+        ("since" = Option<String>, Query, description = "Only rows created after this RFC 3339 timestamp"),
+        ("limit" = Option<u32>, Query, description = "Maximum rows when polling (default: 500, max: 1000)")
+    ),
     responses(
         (status = 200, description = "List of audit logs", body = AuditResponse),
+        (status = 400, description = "Unknown since_id"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - Admin access required")
     ),
@@ -27,29 +48,327 @@ pub fn routes() -> Router<AppState> {
 async fn get_actions(
     State(state): State<AppState>,
     current_user: CurrentUser,
-)->Result<Json<AuditResponse>, AppError>{
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, AppError> {
     info!(user_id = %current_user.id, username = %current_user.username, role = %current_user.role, "Get actions request received");
 
     check_permission(&current_user, StorageAction::GetActions)?;
 
-    let audit_logs = sqlx::query_as::<_, AuditLog>(
+    // Resolve the cursor to a (created_at, id) position; a bare timestamp
+    // sorts after every id so rows at exactly `since` are excluded.
+    let cursor = match (query.since_id, query.since) {
+        (Some(since_id), _) => {
+            let created_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+                "SELECT created_at FROM audit_logs WHERE id = $1"
+            )
+            .bind(since_id)
+            .fetch_optional(&state.read_pool)
+            .await
+            .map_err(AppError::Db)?;
+
+            let created_at = created_at.ok_or(AppError::BadRequest("Unknown since_id"))?;
+            Some((created_at, since_id))
+        }
+        (None, Some(since)) => Some((since, Uuid::from_u128(u128::MAX))),
+        (None, None) => None,
+    };
+
+    let audit_logs = match cursor {
+        Some((created_at, id)) => {
+            let limit = query.limit.unwrap_or(500).clamp(1, 1000);
+            sqlx::query_as::<_, AuditLog>(
+                r#"
+                SELECT id, user_id, action, document_id, document_version, metadata, created_at
+                FROM audit_logs
+                WHERE (created_at, id) > ($1, $2)
+                ORDER BY created_at ASC, id ASC
+                LIMIT $3
+                "#
+            )
+            .bind(created_at)
+            .bind(id)
+            .bind(limit as i64)
+            .fetch_all(&state.read_pool)
+            .timed("audit.get_actions_since", state.config.slow_query_ms)
+            .await
+            .map_err(AppError::Db)?
+        }
+        None => sqlx::query_as::<_, AuditLog>(
+            r#"
+            SELECT id, user_id, action, document_id, document_version, metadata, created_at
+            FROM audit_logs
+            ORDER BY created_at DESC, id DESC
+            "#
+        )
+        .fetch_all(&state.read_pool)
+        .timed("audit.get_actions", state.config.slow_query_ms)
+        .await
+        .map_err(AppError::Db)?,
+    };
+
+    let total = audit_logs.len() as i64;
+
+    // Polling results are oldest first, so the newest row is last
+    let newest = if cursor.is_some() { audit_logs.last() } else { audit_logs.first() };
+    let (newest_id, newest_created_at) = match (newest, query.since_id) {
+        (Some(log), _) => (Some(log.id), Some(log.created_at)),
+        (None, Some(since_id)) => (Some(since_id), cursor.map(|(created_at, _)| created_at)),
+        (None, None) => (None, cursor.map(|(created_at, _)| created_at)),
+    };
+
+    debug!(returned = total, newest_id = ?newest_id, "Audit logs retrieved");
+
+    let response = AuditResponse {
+        data: audit_logs,
+        total,
+        newest_id,
+        newest_created_at,
+    };
+
+    capped_json("audit.get_actions", state.config.max_response_bytes, &response)
+
+}
+
+/// Stream the audit log as newline-delimited [`AuditEvent`](crate::dtos::AuditEvent)
+/// records, oldest first, for SIEM ingestion. Unlike the per-document CSV this
+/// uses the normalized event schema rather than raw audit rows.
+#[utoipa::path(
+    get,
+    path = "/audit/export.jsonl",
+    tag = "audit",
+    params(
+        ("since" = Option<String>, Query, description = "Only events at or after this RFC 3339 timestamp"),
+        ("until" = Option<String>, Query, description = "Only events before this RFC 3339 timestamp")
+    ),
+    responses(
+        (status = 200, description = "One AuditEvent JSON object per line", content_type = "application/x-ndjson"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn export_audit_events(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<AuditEventsQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::GetActions)?;
+
+    info!(
+        user_id = %current_user.id,
+        since = ?query.since,
+        until = ?query.until,
+        "Streaming audit event export"
+    );
+
+    let (mut tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(64);
+    let pool = state.read_pool.clone();
+
+    tokio::spawn(async move {
+        let mut rows = sqlx::query_as::<_, AuditLog>(
+            r#"
+            SELECT id, user_id, action, document_id, document_version, metadata, created_at
+            FROM audit_logs
+            WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+              AND ($2::timestamptz IS NULL OR created_at < $2)
+            ORDER BY created_at ASC, id ASC
+            "#
+        )
+        .bind(query.since)
+        .bind(query.until)
+        .fetch(&pool);
+
+        let mut exported = 0u64;
+        while let Some(row) = rows.next().await {
+            let line = match row {
+                Ok(log) => serde_json::to_vec(&audit_event(&log))
+                    .map(|mut line| {
+                        line.push(b'\n');
+                        Bytes::from(line)
+                    })
+                    .map_err(std::io::Error::other),
+                Err(e) => {
+                    error!(error = ?e, "Failed reading audit rows, aborting export");
+                    Err(std::io::Error::other(e))
+                }
+            };
+            let failed = line.is_err();
+            if tx.send(line).await.is_err() || failed {
+                return;
+            }
+            exported += 1;
+        }
+
+        debug!(exported = exported, "Audit event export finished");
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"audit-events.jsonl\"",
+        )
+        .body(Body::from_stream(rx))
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
+
+/// Audit history of a single document, oldest first
+pub async fn fetch_document_audit(
+    pool: &PgPool,
+    document_id: Uuid,
+) -> Result<Vec<AuditLog>, AppError> {
+    sqlx::query_as::<_, AuditLog>(
         r#"
         SELECT id, user_id, action, document_id, document_version, metadata, created_at
         FROM audit_logs
-        ORDER BY created_at DESC
+        WHERE document_id = $1
+        ORDER BY created_at ASC, id ASC
         "#
     )
-    .fetch_all(&state.pool)  
+    .bind(document_id)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::Db)
+}
+
+fn audit_logs_to_csv(logs: &[AuditLog]) -> Result<Vec<u8>, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    writer
+        .write_record(["id", "created_at", "user_id", "action", "document_version", "metadata"])
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to write CSV header: {}", e)))?;
+
+    for log in logs {
+        let action = serde_json::to_value(log.action)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+
+        writer
+            .write_record([
+                log.id.to_string(),
+                log.created_at.to_rfc3339(),
+                log.user_id.clone(),
+                action,
+                log.document_version.map(|v| v.to_string()).unwrap_or_default(),
+                log.metadata.to_string(),
+            ])
+            .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to write CSV row: {}", e)))?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to finish CSV: {}", e)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/audit/export",
+    tag = "audit",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("format" = Option<String>, Query, description = "Export format (default and only supported value: csv)")
+    ),
+    responses(
+        (status = 200, description = "Document audit trail as CSV", content_type = "text/csv"),
+        (status = 400, description = "Unsupported export format"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn export_document_audit(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<AuditExportQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::GetActions)?;
+
+    let format = query.format.as_deref().unwrap_or("csv").to_ascii_lowercase();
+    if format != "csv" {
+        return Err(AppError::BadRequest("Unsupported export format; use format=csv"));
+    }
+
+    // Soft-deleted documents still have an audit trail worth exporting
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
     .await
     .map_err(AppError::Db)?;
 
-    let total = audit_logs.len() as i64;
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found"));
+    }
 
-    let response = AuditResponse {
-        data: audit_logs,
-        total,
-    };
+    let logs = fetch_document_audit(&state.read_pool, document_id).await?;
+    let csv_bytes = audit_logs_to_csv(&logs)?;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        rows = logs.len(),
+        "Exported document audit trail"
+    );
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/csv; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"audit-{}.csv\"", document_id),
+        )
+        .body(Body::from(csv_bytes))
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AuditAction;
+
+    fn row(action: AuditAction, version: Option<i32>) -> AuditLog {
+        AuditLog {
+            id: Uuid::new_v4(),
+            user_id: "user-1".to_string(),
+            action,
+            document_id: Some(Uuid::nil()),
+            document_version: version,
+            metadata: serde_json::json!({"k": "v"}),
+            created_at: chrono::Utc::now(),
+        }
+    }
 
-    Ok(Json(response))
+    #[test]
+    fn csv_has_a_header_and_one_row_per_log_in_order() {
+        let logs = [
+            row(AuditAction::Upload, Some(1)),
+            row(AuditAction::Download, Some(1)),
+            row(AuditAction::Delete, None),
+        ];
+        let csv = audit_logs_to_csv(&logs).unwrap();
 
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["id", "created_at", "user_id", "action", "document_version", "metadata"]
+        );
+        let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 3);
+        for (record, log) in records.iter().zip(&logs) {
+            assert_eq!(&record[0], log.id.to_string());
+        }
+        let actions: Vec<&str> = records.iter().map(|r| &r[3]).collect();
+        assert_eq!(actions, ["Upload", "Download", "Delete"]);
+        assert_eq!(&records[2][4], "");
+        assert_eq!(&records[0][5], r#"{"k":"v"}"#);
+    }
 }
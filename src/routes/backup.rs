@@ -0,0 +1,278 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::error::AppError;
+use crate::models::{Document, DocumentVersion};
+use crate::state::AppState;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::{routing::post, Router};
+use chrono::Utc;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// tar block size; entries and the archive end are padded to it
+const TAR_BLOCK: usize = 512;
+
+/// Only one backup may stream at a time
+static BACKUP_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears BACKUP_RUNNING when the backup task finishes or is dropped
+struct BackupGuard;
+
+impl Drop for BackupGuard {
+    fn drop(&mut self) {
+        BACKUP_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+type Chunk = Result<Bytes, std::io::Error>;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/admin/backup", post(backup))
+}
+
+/// Archive path of a version's content; short enough for a plain tar header
+fn archive_path(version: &DocumentVersion) -> String {
+    format!("documents/{}/v{}", version.document_id, version.version_number)
+}
+
+fn tar_header(path: &str, size: u64) -> Result<Bytes, std::io::Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_path(path)?;
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_cksum();
+    Ok(Bytes::copy_from_slice(header.as_bytes()))
+}
+
+fn tar_padding(size: u64) -> Bytes {
+    let rem = (size % TAR_BLOCK as u64) as usize;
+    Bytes::from(vec![0u8; (TAR_BLOCK - rem) % TAR_BLOCK])
+}
+
+/// Stream a tar of every version object plus `manifest.json` describing
+/// documents, versions and metadata. Objects missing from storage are listed
+/// in the manifest with `included: false`.
+#[utoipa::path(
+    post,
+    path = "/admin/backup",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Tar archive of the whole corpus; `X-Job-Id` names the job shown in GET /admin/jobs", content_type = "application/x-tar"),
+        (status = 409, description = "Another backup is already running"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn backup(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    if BACKUP_RUNNING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(AppError::Conflict("A backup is already running"));
+    }
+    let guard = BackupGuard;
+
+    let documents = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        ORDER BY created_at, id
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let versions = sqlx::query_as::<_, DocumentVersion>(
+        r#"
+        SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+        FROM document_versions
+        ORDER BY document_id, version_number
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let metadata: Vec<(Uuid, String, Option<String>)> = sqlx::query_as(
+        "SELECT document_id, key, value FROM document_metadata ORDER BY document_id, key"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    // Sizes come from storage so each tar header matches the bytes we stream
+    let mut stored_sizes: HashMap<Uuid, u64> = HashMap::new();
+    for version in &versions {
+        match state.storage.stat(&version.file_path).await {
+            Ok(meta) => {
+                stored_sizes.insert(version.id, meta.content_length());
+            }
+            Err(e) if e.kind() == opendal::ErrorKind::NotFound => {
+                warn!(file_path = %version.file_path, "Object missing, leaving it out of the backup");
+            }
+            Err(e) => return Err(AppError::from(e)),
+        }
+    }
+
+    let mut metadata_by_doc: HashMap<Uuid, serde_json::Map<String, serde_json::Value>> = HashMap::new();
+    for (document_id, key, value) in metadata {
+        metadata_by_doc
+            .entry(document_id)
+            .or_default()
+            .insert(key, serde_json::json!(value));
+    }
+
+    let manifest = serde_json::json!({
+        "created_at": Utc::now(),
+        "created_by": current_user.id,
+        "documents": documents.iter().map(|doc| serde_json::json!({
+            "document": doc,
+            "metadata": metadata_by_doc.remove(&doc.id).unwrap_or_default(),
+            "versions": versions.iter().filter(|v| v.document_id == doc.id).map(|v| serde_json::json!({
+                "version": v,
+                "archive_path": archive_path(v),
+                "included": stored_sizes.contains_key(&v.id),
+            })).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+    });
+    let manifest = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to serialize backup manifest: {}", e)))?;
+
+    info!(
+        user_id = %current_user.id,
+        documents = documents.len(),
+        versions = versions.len(),
+        included = stored_sizes.len(),
+        "Streaming backup"
+    );
+
+    // The archive still streams to this response; the job only makes it
+    // visible in GET /admin/jobs and lets another admin cancel it
+    let job = state.jobs.start("backup", current_user.id);
+    job.set_total(stored_sizes.len() as u64);
+    let job_id = job.id;
+
+    let (mut tx, rx) = mpsc::channel::<Chunk>(8);
+
+    tokio::spawn(async move {
+        let _guard = guard;
+
+        let manifest_len = manifest.len() as u64;
+        let mut chunks: Vec<Chunk> = vec![
+            tar_header("manifest.json", manifest_len),
+            Ok(Bytes::from(manifest)),
+            Ok(tar_padding(manifest_len)),
+        ];
+        for chunk in chunks.drain(..) {
+            if tx.send(chunk).await.is_err() {
+                job.finish(Err("client disconnected"));
+                return;
+            }
+        }
+
+        for version in &versions {
+            let Some(&size) = stored_sizes.get(&version.id) else {
+                continue;
+            };
+
+            if job.is_cancelled() {
+                warn!(job_id = %job.id, "Backup cancelled, aborting stream");
+                let _ = tx.send(Err(std::io::Error::other("backup cancelled"))).await;
+                job.finish(Ok::<(), String>(()));
+                return;
+            }
+
+            if tx.send(tar_header(&archive_path(version), size)).await.is_err() {
+                job.finish(Err("client disconnected"));
+                return;
+            }
+
+            let stream = match state.storage.reader(&version.file_path).await {
+                Ok(reader) => reader.into_bytes_stream(..).await,
+                Err(e) => Err(e),
+            };
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!(error = ?e, file_path = %version.file_path, "Failed to open object, aborting backup");
+                    job.finish(Err(format!("failed to open {}: {}", version.file_path, e)));
+                    let _ = tx.send(Err(std::io::Error::other(e))).await;
+                    return;
+                }
+            };
+
+            // The header already promised `size` bytes; an object rewritten
+            // since the stat would corrupt the archive, so abort instead
+            let mut streamed = 0u64;
+            while let Some(chunk) = stream.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        error!(error = ?e, file_path = %version.file_path, "Failed reading object, aborting backup");
+                        job.finish(Err(format!("failed reading {}", version.file_path)));
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                streamed += chunk.len() as u64;
+                if streamed > size {
+                    break;
+                }
+                if tx.send(Ok(chunk)).await.is_err() {
+                    job.finish(Err("client disconnected"));
+                    return;
+                }
+            }
+            if streamed != size {
+                error!(
+                    file_path = %version.file_path,
+                    expected = size,
+                    streamed = streamed,
+                    "Object changed size during the backup, aborting backup"
+                );
+                job.finish(Err(format!("{} changed size during the backup", version.file_path)));
+                let _ = tx.send(Err(std::io::Error::other("object changed size during the backup"))).await;
+                return;
+            }
+
+            if tx.send(Ok(tar_padding(size))).await.is_err() {
+                job.finish(Err("client disconnected"));
+                return;
+            }
+            job.advance(1);
+        }
+
+        // Two empty blocks mark the end of the archive
+        let _ = tx.send(Ok(Bytes::from(vec![0u8; TAR_BLOCK * 2]))).await;
+        job.finish(Ok::<(), String>(()));
+        info!("Backup stream finished");
+    });
+
+    let file_name = format!("dms-backup-{}.tar", Utc::now().format("%Y%m%dT%H%M%SZ"));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-tar")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .header("x-job-id", job_id.to_string())
+        .body(Body::from_stream(rx))
+        .map_err(|e| AppError::Other(anyhow::anyhow!("Failed to build backup response: {}", e)))
+}
@@ -1,65 +1,73 @@
 use axum::response::Response;
 use uuid::Uuid;
-use axum::{routing::{get, delete}, Router};
+use axum::{routing::{get, delete, post}, Router};
+use crate::text::normalize_name;
 use axum::extract::{Query, State,Path};
 use axum::Json;
-use axum::http::{header};
-use axum::body::Body;
+use axum::http::{header, header::AsHeaderName, HeaderMap};
+use axum::body::{Body, Bytes};
+use futures::{Stream, StreamExt};
 use axum::http::StatusCode;
-use crate::{state::AppState,models::{Document, DocumentVersion}, dtos::{ListDocumentsQuery, ListDocumentsResponse, DocumentWithLatest, DownloadQuery}, error::AppError};
-use tracing::{info, debug, warn};
+use crate::{state::AppState,models::{AuditAction, Document, DocumentVersion, NewAuditLog, Tag}, dtos::{ListDocumentsQuery, ListDocumentsResponse, UpdateDocumentRequest, DocumentSortField, SortOrder, DocumentWithLatest, DownloadQuery, DocumentPreviewResponse, PreviewQuery, VersionDiffQuery, VersionDiffResponse, DocumentFootprintResponse, VersionFootprint, DocumentIntegrityResponse, VersionIntegrity, PruneVersionsRequest, PruneVersionsResponse, PromoteVersionResponse, RecentVersion, RecentVersionsQuery, RecentVersionsResponse, BulkRestoreRequest, BulkRestoreResponse, BulkRestoreResult, BulkRestoreStatus, BatchDeleteRequest, BatchDeleteResponse, BatchDeleteResult, BatchDeleteStatus, total_pages}, error::AppError};
+use tracing::{info, debug, warn, error};
 
 use crate::auth::{CurrentUser, check_permission, StorageAction};
 
-use crate::audit::{log_delete,log_download};
+use crate::audit::{log_deferred, log_download, log_failure, log_in_tx};
+use crate::db::TimedQuery;
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Utc};
+use crate::quota::check_download_quota;
+use crate::mime::is_text_like;
+use similar::{ChangeTag, TextDiff};
+use sqlx::types::Json as SqlJson;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use crate::response::capped_json;
+use crate::storage::{read_prefix, version_key};
+use crate::routes::folders::normalize_folder_path;
+use crate::notifications::notify_watchers;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/documents", get(list_documents))
-        .route("/documents/:id/content", get(download_document))
-        .route("/documents/:id", delete(soft_delete_document))
+        .route("/versions/recent", get(list_recent_versions))
+        .route("/documents/:id/versions", get(list_versions))
+        .route("/documents/:id/versions/diff", get(diff_versions))
+        .route("/documents/:id/versions/:version/restore", post(restore_document_version))
+        .route("/documents/restore/bulk", post(bulk_restore_documents))
+        .route("/documents/:id/promote/:version", post(promote_document_version))
+        .route("/documents/:id/content", get(download_document).head(head_document))
+        .route("/documents/:id/preview", get(preview_document))
+        .route("/documents/:id/footprint", get(document_footprint))
+        .route("/documents/:id/integrity", get(document_integrity))
+        .route("/documents/:id", delete(soft_delete_document).patch(update_document))
         .route("/documents/:id/hard", delete(hard_delete_document))
+        .route("/documents/batch-delete", post(batch_delete_documents))
+        .route("/documents/:id/prune", post(prune_versions))
 }
 
-#[utoipa::path(
-    get,
-    path = "/documents/{id}/content",
-    tag = "documents",
-    params(
-        ("id" = Uuid, Path, description = "Document ID"),
-        ("version" = Option<i32>, Query, description = "Version number (optional, defaults to latest)")
-    ),
-    responses(
-        (status = 200, description = "File content", content_type = "application/octet-stream"),
-        (status = 404, description = "Document not found"),
-        (status = 401, description = "Unauthorized")
-    ),
-    security(
-        ("api_key" = [])
-    )
-)]
-async fn download_document(
-    State(state) : State<AppState>,
-    Path(document_id) : Path<Uuid>, 
-    Query(query) : Query<DownloadQuery>,
-    current_user: CurrentUser,
-) -> Result<Response,AppError> {
-
-    info!(user_id = %current_user.id, username = %current_user.username, role = %current_user.role, "File download request received");
-    
-    // Check if user has read permission
-    check_permission(&current_user, StorageAction::Read)?;
-
+/// Resolve the version to serve for a document: the requested `version`, or
+/// the latest one. Fails with 404 when the document is missing or soft-deleted.
+/// Reads go to `pool`: `state.read_pool` for plain reads, `state.pool` when
+/// the caller acts on the result and must not see a lagging replica.
+pub async fn resolve_version(
+    state: &AppState,
+    pool: &PgPool,
+    document_id: Uuid,
+    version: Option<i32>,
+) -> Result<DocumentVersion, AppError> {
     // Check if document exists and is not soft-deleted
     let document = sqlx::query_as::<_, Document>(
         r#"
-        SELECT id, title, category, deleted_at, created_at, updated_at
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
         FROM documents
         WHERE id = $1 AND deleted_at IS NULL
         "#,
     )
     .bind(document_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
+    .timed("resolve_version.fetch_document", state.config.slow_query_ms)
     .await
     .map_err(AppError::Db)?;
 
@@ -67,18 +75,32 @@ async fn download_document(
         return Err(AppError::NotFound("Document not found or has been deleted"));
     }
 
-    let version_number: i32 = if let Some(v) = query.version {
+    let version_number: i32 = if let Some(v) = version {
         v
     } else {
+        // A promoted version wins over the highest number, as long as it
+        // still exists
         let latest: Option<i32> = sqlx::query_scalar(
             r#"
-            SELECT MAX(version_number)
-            FROM document_versions
-            WHERE document_id = $1
+            SELECT COALESCE(
+                (
+                    SELECT dv.version_number
+                    FROM documents d
+                    JOIN document_versions dv
+                      ON dv.document_id = d.id AND dv.version_number = d.current_version
+                    WHERE d.id = $1
+                ),
+                (
+                    SELECT MAX(version_number)
+                    FROM document_versions
+                    WHERE document_id = $1
+                )
+            )
             "#,
         )
         .bind(document_id)
-        .fetch_one(&state.pool)
+        .fetch_one(pool)
+        .timed("resolve_version.latest_version", state.config.slow_query_ms)
         .await
         .map_err(AppError::Db)?;
 
@@ -106,7 +128,8 @@ async fn download_document(
     )
     .bind(document_id)
     .bind(version_number)
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
+    .timed("resolve_version.fetch_version", state.config.slow_query_ms)
     .await
     .map_err(AppError::Db)?;
 
@@ -115,22 +138,314 @@ async fn download_document(
         None => return Err(AppError::NotFound("document version not found")),
     };
 
-    // OpenDAL's `read` returns a Buffer; convert it to Vec<u8> for the HTTP body.
-    let data = state
+    Ok(dv)
+}
+
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/content",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("version" = Option<i32>, Query, description = "Version number (optional, defaults to latest)"),
+        ("verify" = Option<bool>, Query, description = "Check content against the stored checksum while streaming; a mismatch aborts the transfer (default: true)")
+    ),
+    responses(
+        (status = 200, description = "File content", content_type = "application/octet-stream"),
+        (status = 206, description = "Requested byte range (single `Range: bytes=start-end` only)", content_type = "application/octet-stream"),
+        (status = 304, description = "`If-None-Match` matches the version's ETag, or the version is no newer than `If-Modified-Since`"),
+        (status = 412, description = "Checksum asserted via `If-Match` or `X-Expected-Checksum` does not match the stored one"),
+        (status = 416, description = "Requested range lies outside the file"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Per-role download limit reached")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn download_document(
+    State(state) : State<AppState>,
+    Path(document_id) : Path<Uuid>, 
+    Query(query) : Query<DownloadQuery>,
+    current_user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<Response,AppError> {
+    let result = download(&state, document_id, query, &current_user, &headers).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::Download, Some(document_id), e).await;
+    }
+    result
+}
+
+/// Serve a document version to an authenticated user, honouring the
+/// request's `Range`, `If-Match`, `If-None-Match` and `If-Modified-Since`
+pub async fn download(
+    state: &AppState,
+    document_id: Uuid,
+    query: DownloadQuery,
+    current_user: &CurrentUser,
+    headers: &HeaderMap,
+) -> Result<Response,AppError> {
+    let range = header_str(headers, header::RANGE);
+
+    info!(user_id = %current_user.id, username = %current_user.username, role = %current_user.role, "File download request received");
+    
+    // Check if user has read permission
+    check_permission(current_user, StorageAction::Read)?;
+
+    let dv = resolve_version(state, &state.read_pool, document_id, query.version).await?;
+
+    // A revalidation sends no content, so it neither counts against the
+    // download quota nor is audited as a download
+    if let Some(response) = check_preconditions(state, &dv, headers)? {
+        return Ok(response);
+    }
+
+    // A ranged request is charged only the bytes it will be sent
+    let requested = match parse_range(range, dv.file_size as u64) {
+        ByteRange::Full => dv.file_size,
+        ByteRange::Partial(start, end) => (end - start + 1) as i64,
+        ByteRange::Unsatisfiable => 0,
+    };
+    check_download_quota(state, current_user, requested).await?;
+
+    serve_version(state, dv, current_user.id.to_string(), query.verify.unwrap_or(true), range).await
+}
+
+/// Evaluate a request's `If-Match` (or `X-Expected-Checksum`), then its
+/// `If-None-Match` / `If-Modified-Since`, against a resolved version. Fails
+/// with 412 on a checksum mismatch and returns the 304 to send when the
+/// client's copy is current. Shared by GET and HEAD so both answer alike.
+fn check_preconditions(
+    state: &AppState,
+    dv: &DocumentVersion,
+    headers: &HeaderMap,
+) -> Result<Option<Response>, AppError> {
+    if let Some(expected) = header_str(headers, header::IF_MATCH).or_else(|| header_str(headers, EXPECTED_CHECKSUM_HEADER)) {
+        check_expected_checksum(dv, expected, state.config.require_stored_checksum)?;
+    }
+
+    if is_not_modified(dv, header_str(headers, header::IF_NONE_MATCH), header_str(headers, header::IF_MODIFIED_SINCE)) {
+        debug!(document_id = %dv.document_id, version_number = dv.version_number, "Version not modified");
+        return not_modified(dv).map(Some);
+    }
+    Ok(None)
+}
+
+fn header_str(headers: &HeaderMap, name: impl AsHeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Alternative to `If-Match` for clients that cannot set it
+const EXPECTED_CHECKSUM_HEADER: &str = "x-expected-checksum";
+
+/// Compare a client-asserted checksum with the stored one before any bytes
+/// are sent. Accepts `"<sha256>"`, a bare hex digest, a comma-separated list
+/// or `*`, as `If-Match` allows.
+fn check_expected_checksum(
+    dv: &DocumentVersion,
+    expected: &str,
+    require_stored: bool,
+) -> Result<(), AppError> {
+    let candidates: Vec<&str> = expected
+        .split(',')
+        .map(|c| c.trim().trim_start_matches("W/").trim_matches('"'))
+        .filter(|c| !c.is_empty())
+        .collect();
+    if candidates.is_empty() || candidates.contains(&"*") {
+        return Ok(());
+    }
+
+    let Some(stored) = dv.checksum.as_deref() else {
+        if require_stored {
+            return Err(AppError::PreconditionFailed("version has no stored checksum to compare against"));
+        }
+        warn!(version_id = %dv.id, "Expected checksum ignored; version has no stored checksum");
+        return Ok(());
+    };
+
+    if candidates.iter().any(|c| c.eq_ignore_ascii_case(stored)) {
+        Ok(())
+    } else {
+        warn!(version_id = %dv.id, expected = %expected, "Expected checksum does not match stored content");
+        Err(AppError::PreconditionFailed("content checksum does not match the expected value"))
+    }
+}
+
+/// Strong validator for a version: its content hash, or its identity when no
+/// checksum was recorded. Versions are immutable, so either is stable.
+fn version_etag(dv: &DocumentVersion) -> String {
+    match &dv.checksum {
+        Some(checksum) => format!("\"{}\"", checksum),
+        None => format!("\"{}-v{}\"", dv.document_id, dv.version_number),
+    }
+}
+
+fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether a conditional request can be answered with 304. `If-None-Match`
+/// takes precedence over `If-Modified-Since` when both are sent.
+fn is_not_modified(
+    dv: &DocumentVersion,
+    if_none_match: Option<&str>,
+    if_modified_since: Option<&str>,
+) -> bool {
+    if let Some(candidates) = if_none_match {
+        let etag = version_etag(dv);
+        let etag = etag.trim_matches('"');
+        return candidates
+            .split(',')
+            .map(|c| c.trim().trim_start_matches("W/").trim_matches('"'))
+            .any(|c| c == "*" || c.eq_ignore_ascii_case(etag));
+    }
+
+    // HTTP dates have whole-second precision
+    if_modified_since
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+        .is_some_and(|since| dv.created_at.timestamp() <= since.timestamp())
+}
+
+fn not_modified(dv: &DocumentVersion) -> Result<Response, AppError> {
+    Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(header::ETAG, version_etag(dv))
+        .header(header::LAST_MODIFIED, http_date(dv.created_at))
+        .body(Body::empty())
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
+
+/// How a `Range` header applies to an object of a given size
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// No usable range; serve the whole object
+    Full,
+    /// Inclusive start and end offsets
+    Partial(u64, u64),
+    /// Well-formed but outside the object
+    Unsatisfiable,
+}
+
+/// Parse a single `bytes=` range. Malformed headers, other units and
+/// multi-range requests fall back to the full body.
+fn parse_range(header: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        // bytes=-N: the last N bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial(size.saturating_sub(n), size - 1),
+            Err(_) => ByteRange::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            let end = match end {
+                "" => None,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => Some(end),
+                    _ => return ByteRange::Full,
+                },
+            };
+            if start >= size {
+                return ByteRange::Unsatisfiable;
+            }
+            ByteRange::Partial(start, end.map_or(size - 1, |e| e.min(size - 1)))
+        }
+    }
+}
+
+/// Half-open byte span `[start, end)` a `Range` header selects, or `None`
+/// when it cannot be satisfied
+fn served_span(range: Option<&str>, size: u64) -> Option<(u64, u64)> {
+    match parse_range(range, size) {
+        ByteRange::Full => Some((0, size)),
+        ByteRange::Partial(start, end) => Some((start, end + 1)),
+        ByteRange::Unsatisfiable => None,
+    }
+}
+
+fn range_not_satisfiable(size: u64) -> Result<Response, AppError> {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(Body::empty())
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
+
+/// Content headers for serving `[start, end)` of a version, as a 206 with
+/// `Content-Range` when that is less than the whole object
+fn span_headers(dv: &DocumentVersion, start: u64, end: u64) -> axum::http::response::Builder {
+    let size = dv.file_size as u64;
+    let builder = content_headers(dv, end - start);
+    if end - start < size {
+        builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, size))
+    } else {
+        builder
+    }
+}
+
+/// Stream a resolved version from storage, audit the download and build the
+/// file response. With `verify`, content that no longer matches the recorded
+/// checksum aborts the transfer. Callers are responsible for access checks.
+pub async fn serve_version(
+    state: &AppState,
+    dv: DocumentVersion,
+    user_id: String,
+    verify: bool,
+    range: Option<&str>,
+) -> Result<Response,AppError> {
+    let document_id = dv.document_id;
+    let version_number = dv.version_number;
+    let size = dv.file_size as u64;
+
+    // Surface a missing object before any headers are sent
+    state.storage.stat(&dv.file_path).await?;
+
+    let Some((start, end)) = served_span(range, size) else {
+        return range_not_satisfiable(size);
+    };
+    let partial = end - start < size;
+
+    // Bytes flow straight from storage to the client instead of being
+    // buffered in memory. A checksum only covers the whole object.
+    let stream = state
         .storage
-        .read(&dv.file_path)
+        .reader(&dv.file_path)
         .await?
-        .to_vec();
-
-    let content_type = dv
-        .mime_type
-        .unwrap_or_else(|| "application/octet-stream".to_string());
+        .into_bytes_stream(start..end)
+        .await?;
+    let body = Body::from_stream(verified_stream(stream, &dv, end - start, verify && !partial));
 
+    // Every served range is audited with the bytes it carries, so fetching a
+    // file in pieces counts against the download quota like fetching it whole
+    let mut served = serde_json::json!({ "bytes_served": end - start });
+    if partial {
+        served["range_start"] = serde_json::json!(start);
+        served["range_end"] = serde_json::json!(end - 1);
+    }
     if let Err(e) = log_download(
-        &state.pool,
-        current_user.id.to_string(),
+        state,
+        user_id.clone(),
         document_id,
         Some(version_number),
+        Some(served),
     )
     .await
     {
@@ -139,25 +454,329 @@ async fn download_document(
         warn!(
             error = ?e,
             document_id = %document_id,
-            user_id = %current_user.id,
+            user_id = %user_id,
             version_number = version_number,
             "Failed to create audit log for download"
         );
     }
 
-    let response = Response::builder()
+    let response = span_headers(&dv, start, end)
+        .body(body)
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))?;
+
+    Ok(response)
+
+}
+
+/// Wrap an object stream so a failed download is never mistaken for a
+/// complete one. The latest chunk is held back until the next one arrives;
+/// once storage is exhausted the byte count (and, with `verify`, the checksum)
+/// is checked, and on a mismatch the stream ends with an error instead of the
+/// final chunk, so the client sees an aborted transfer rather than a silently
+/// truncated or corrupt file.
+fn verified_stream<S>(
+    inner: S,
+    dv: &DocumentVersion,
+    expected_size: u64,
+    verify: bool,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + Unpin + 'static,
+{
+    struct State<S> {
+        inner: S,
+        held: Option<Bytes>,
+        received: u64,
+        hasher: Option<Sha256>,
+        expected_checksum: Option<String>,
+        expected_size: u64,
+        document_id: Uuid,
+        version_number: i32,
+        done: bool,
+    }
+
+    impl<S> State<S> {
+        fn finish(&mut self) -> std::io::Result<()> {
+            if self.received != self.expected_size {
+                error!(
+                    document_id = %self.document_id,
+                    version_number = self.version_number,
+                    expected = self.expected_size,
+                    received = self.received,
+                    "Stored object is shorter or longer than recorded"
+                );
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "stored object size does not match recorded size",
+                ));
+            }
+
+            if let (Some(hasher), Some(expected)) = (self.hasher.take(), self.expected_checksum.as_deref()) {
+                let actual = hex::encode(hasher.finalize());
+                if !actual.eq_ignore_ascii_case(expected) {
+                    error!(
+                        document_id = %self.document_id,
+                        version_number = self.version_number,
+                        expected = %expected,
+                        actual = %actual,
+                        "Stored content does not match its checksum"
+                    );
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "stored content does not match its checksum",
+                    ));
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    let state = State {
+        inner,
+        held: None,
+        received: 0,
+        hasher: (verify && dv.checksum.is_some()).then(Sha256::new),
+        expected_checksum: dv.checksum.clone(),
+        expected_size,
+        document_id: dv.document_id,
+        version_number: dv.version_number,
+        done: false,
+    };
+
+    futures::stream::unfold(state, |mut s| async move {
+        if s.done {
+            return None;
+        }
+        loop {
+            match s.inner.next().await {
+                Some(Ok(chunk)) => {
+                    if let Some(hasher) = s.hasher.as_mut() {
+                        hasher.update(&chunk);
+                    }
+                    s.received += chunk.len() as u64;
+                    if let Some(previous) = s.held.replace(chunk) {
+                        return Some((Ok(previous), s));
+                    }
+                }
+                Some(Err(e)) => {
+                    error!(
+                        error = ?e,
+                        document_id = %s.document_id,
+                        version_number = s.version_number,
+                        "Failed reading object mid-download"
+                    );
+                    s.done = true;
+                    return Some((Err(e), s));
+                }
+                None => {
+                    s.done = true;
+                    if let Err(e) = s.finish() {
+                        return Some((Err(e), s));
+                    }
+                    return s.held.take().map(|last| (Ok(last), s));
+                }
+            }
+        }
+    })
+}
+
+/// Response headers describing a version's content, shared by GET and HEAD
+fn content_headers(dv: &DocumentVersion, content_length: u64) -> axum::http::response::Builder {
+    let content_type = dv
+        .mime_type
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, content_length)
+        .header(header::ACCEPT_RANGES, "bytes")
         // tell browser / Postman to treat it as a download; you can adjust the filename
         .header(
             header::CONTENT_DISPOSITION,
             format!("attachment; filename=\"{}\"", dv.file_name),
         )
-        .body(Body::from(data))
-        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))?;
+        .header(header::ETAG, version_etag(dv))
+        // The version's creation time rather than the object's mtime, which
+        // changes when a version is rekeyed
+        .header(header::LAST_MODIFIED, http_date(dv.created_at))
+}
 
-    Ok(response)
+/// Same status and headers as GET /documents/{id}/content, without a body, download audit or quota use
+#[utoipa::path(
+    head,
+    path = "/documents/{id}/content",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("version" = Option<i32>, Query, description = "Version number (optional, defaults to latest)")
+    ),
+    responses(
+        (status = 200, description = "Content headers only"),
+        (status = 206, description = "Headers for the requested byte range, as for GET"),
+        (status = 304, description = "Not modified, as for GET"),
+        (status = 412, description = "Stored checksum does not match If-Match or X-Expected-Checksum"),
+        (status = 416, description = "Requested range lies outside the file"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn head_document(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<DownloadQuery>,
+    current_user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let dv = resolve_version(&state, &state.read_pool, document_id, query.version).await?;
+
+    if let Some(response) = check_preconditions(&state, &dv, &headers)? {
+        return Ok(response);
+    }
+
+    state.storage.stat(&dv.file_path).await?;
+
+    let size = dv.file_size as u64;
+    let Some((start, end)) = served_span(header_str(&headers, header::RANGE), size) else {
+        return range_not_satisfiable(size);
+    };
+
+    span_headers(&dv, start, end)
+        .body(Body::empty())
+        .map_err(|_| AppError::Other(anyhow::anyhow!("failed to build response")))
+}
+
+/// Rename a document or change its category. Existing versions keep their
+/// storage keys; only versions uploaded afterwards use the new values.
+#[utoipa::path(
+    patch,
+    path = "/documents/{id}",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body = UpdateDocumentRequest,
+    responses(
+        (status = 200, description = "Document updated", body = Document),
+        (status = 400, description = "Empty title, title or category too long, or nothing to update"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Write access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn update_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(request): Json<UpdateDocumentRequest>,
+) -> Result<Json<Document>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    if request.title.is_none() && request.category.is_none() {
+        return Err(AppError::BadRequest("Provide a title or category to update"));
+    }
+
+    let clean = |value: String| {
+        if state.config.normalize_text {
+            normalize_name(&value)
+        } else {
+            value.trim().to_string()
+        }
+    };
+
+    let title = request.title.map(clean);
+    if let Some(title) = &title {
+        if title.is_empty() {
+            return Err(AppError::BadRequest("Title cannot be empty"));
+        }
+        if title.chars().count() > 255 {
+            return Err(AppError::BadRequest("Title is longer than 255 characters"));
+        }
+    }
+    // Some("") clears the category, None leaves it alone
+    let category = request.category.map(clean);
+    if category.as_ref().is_some_and(|c| c.chars().count() > 100) {
+        return Err(AppError::BadRequest("Category is longer than 100 characters"));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    let before = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found or has been deleted"))?;
+
+    let new_title = title.unwrap_or_else(|| before.title.clone());
+    let new_category = match category {
+        Some(c) if c.is_empty() => None,
+        Some(c) => Some(c),
+        None => before.category.clone(),
+    };
+
+    let updated = sqlx::query_as::<_, Document>(
+        r#"
+        UPDATE documents
+        SET title = $2, category = $3, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        "#,
+    )
+    .bind(document_id)
+    .bind(&new_title)
+    .bind(&new_category)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::UpdateMetadata,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "change": "document",
+                "before": { "title": before.title, "category": before.category },
+                "after": { "title": updated.title, "category": updated.category },
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
 
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        title = %updated.title,
+        category = ?updated.category,
+        "Document updated"
+    );
+
+    Ok(Json(updated))
 }
 
 /// Soft delete: Mark document as deleted (set deleted_at timestamp)
@@ -184,14 +803,26 @@ pub async fn soft_delete_document(
     State(state): State<AppState>,
     current_user: CurrentUser,
     Path(document_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let result = soft_delete(&state, &current_user, document_id).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::Delete, Some(document_id), e).await;
+    }
+    result
+}
+
+async fn soft_delete(
+    state: &AppState,
+    current_user: &CurrentUser,
+    document_id: Uuid,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Check delete permission (admin only)
-    check_permission(&current_user, StorageAction::Delete)?;
+    check_permission(current_user, StorageAction::Delete)?;
 
     // Verify document exists and is not already soft-deleted
     let document = sqlx::query_as::<_, Document>(
         r#"
-        SELECT id, title, category, deleted_at, created_at, updated_at
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
         FROM documents
         WHERE id = $1
         "#,
@@ -222,6 +853,8 @@ pub async fn soft_delete_document(
         }
     };
 
+    let mut tx = state.pool.begin().await?;
+
     // Update deleted_at timestamp
     let rows_affected = sqlx::query(
         r#"
@@ -231,7 +864,7 @@ pub async fn soft_delete_document(
         "#,
     )
     .bind(document_id)
-    .execute(&state.pool)
+    .execute(&mut *tx)
     .await
     .map_err(AppError::Db)?
     .rows_affected();
@@ -240,27 +873,26 @@ pub async fn soft_delete_document(
         return Err(AppError::BadRequest("Document is already deleted or not found"));
     }
 
-    if let Err(e) = log_delete(
-        &state.pool,
-        current_user.id.to_string(),
-        document_id,
-        Some(serde_json::json!({
-            "delete_type": "soft",
-            "title": &doc.title,
-            "category": &doc.category,
-        })),
+    let deferred_audit = log_in_tx(
+        state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::Delete,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "delete_type": "soft",
+                "title": &doc.title,
+                "category": &doc.category,
+            }),
+        },
     )
-    .await
-    {
-        // Log the error but don't fail the delete operation
-        // Audit logging should not break the main functionality
-        warn!(
-            error = ?e,
-            document_id = %document_id,
-            user_id = %current_user.id,
-            "Failed to create audit log for soft delete"
-        );
-    }
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(state, deferred_audit).await;
 
     info!(
         user_id = %current_user.id,
@@ -275,6 +907,40 @@ pub async fn soft_delete_document(
     })))
 }
 
+/// Delete every version object of a document from storage, in batches to
+/// save round trips. Returns how many objects could not be deleted.
+async fn delete_version_objects(state: &AppState, document_id: Uuid, versions: &[DocumentVersion]) -> usize {
+    let mut failed_deletes = 0;
+    for batch in versions.chunks(state.config.hard_delete_batch_size) {
+        let paths: Vec<String> = batch.iter().map(|v| v.file_path.clone()).collect();
+        debug!(
+            document_id = %document_id,
+            objects = paths.len(),
+            "Deleting batch of files from storage"
+        );
+
+        if let Err(e) = state.storage.remove(paths).await {
+            // A batch error doesn't say which object failed; retry one by one
+            // so only the real failures are counted.
+            warn!(error = ?e, "Batch delete failed, retrying objects individually");
+
+            for version in batch {
+                // Note: If file doesn't exist, OpenDAL might return an error.
+                // We log a warning but continue deletion.
+                if let Err(e) = state.storage.delete(&version.file_path).await {
+                    warn!(
+                        error = ?e,
+                        file_path = %version.file_path,
+                        "Failed to delete file from storage (continuing anyway)"
+                    );
+                    failed_deletes += 1;
+                }
+            }
+        }
+    }
+    failed_deletes
+}
+
 /// Hard delete: Permanently delete document, all versions, metadata, folder links, and files from storage
 #[utoipa::path(
     delete,
@@ -287,7 +953,8 @@ pub async fn soft_delete_document(
         (status = 200, description = "Document permanently deleted successfully"),
         (status = 404, description = "Document not found"),
         (status = 401, description = "Unauthorized"),
-        (status = 403, description = "Forbidden - Admin access required")
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 502, description = "Storage delete failed (strict mode only); nothing was deleted from the database")
     ),
     security(
         ("api_key" = [])
@@ -297,14 +964,26 @@ pub async fn hard_delete_document(
     State(state): State<AppState>,
     current_user: CurrentUser,
     Path(document_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let result = hard_delete(&state, &current_user, document_id).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::Delete, Some(document_id), e).await;
+    }
+    result
+}
+
+async fn hard_delete(
+    state: &AppState,
+    current_user: &CurrentUser,
+    document_id: Uuid,
 ) -> Result<Json<serde_json::Value>, AppError> {
     // Check delete permission (admin only)
-    check_permission(&current_user, StorageAction::Delete)?;
+    check_permission(current_user, StorageAction::Delete)?;
 
     // Verify document exists
     let document = sqlx::query_as::<_, Document>(
         r#"
-        SELECT id, title, category, deleted_at, created_at, updated_at
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
         FROM documents
         WHERE id = $1
         "#,
@@ -344,50 +1023,44 @@ pub async fn hard_delete_document(
     .await
     .map_err(AppError::Db)?;
 
-    // Delete all files from OpenDAL storage
-    for version in &versions {
-        debug!(
-            document_id = %document_id,
-            version_number = version.version_number,
-            file_path = %version.file_path,
-            "Deleting file from storage"
-        );
-        
-        // Delete from OpenDAL
-        // Note: If file doesn't exist, OpenDAL might return an error.
-        // We log a warning but continue deletion.
-        if let Err(e) = state.storage.delete(&version.file_path).await {
-            warn!(
-                error = ?e,
-                file_path = %version.file_path,
-                "Failed to delete file from storage (continuing anyway)"
-            );
-            // Continue deletion even if file deletion fails
-        }
-    }
+    // Audit first: with AUDIT_FAIL_CLOSED a failed audit write must stop the
+    // delete before any object is removed, as storage deletes can't be undone.
+    let mut tx = state.pool.begin().await?;
 
-    if let Err(e) = log_delete(
-        &state.pool,
-        current_user.id.to_string(),
-        document_id,
-        Some(serde_json::json!({
-            "delete_type": "hard",
-            "title": &doc.title,
-            "category": &doc.category,
-            "versions_deleted": versions.len(),
-            "files_deleted": versions.len(),
-        })),
+    // The id goes in the metadata: a row written after the commit can't
+    // reference the deleted document, and one written before loses it anyway
+    let deferred_audit = log_in_tx(
+        state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::Delete,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({
+                "delete_type": "hard",
+                "document_id": document_id,
+                "title": &doc.title,
+                "category": &doc.category,
+                "versions_deleted": versions.len(),
+            }),
+        },
     )
-    .await
-    {
-        // Log the error but don't fail the delete operation
-        // Audit logging should not break the main functionality
+    .await?;
+
+    let failed_deletes = delete_version_objects(state, document_id, &versions).await;
+
+    // In strict mode keep the DB rows (and drop the audit row with them) so
+    // the delete can be retried without orphaning objects still in storage.
+    if failed_deletes > 0 && state.config.strict_hard_delete {
         warn!(
-            error = ?e,
             document_id = %document_id,
-            user_id = %current_user.id,
-            "Failed to create audit log for hard delete"
+            failed_deletes = failed_deletes,
+            "Aborting hard delete: storage deletes failed (STRICT_HARD_DELETE)"
         );
+        return Err(AppError::BadGateway(
+            "Failed to delete document files from storage; document was not deleted",
+        ));
     }
 
     // Delete the document from database
@@ -402,7 +1075,7 @@ pub async fn hard_delete_document(
         "#,
     )
     .bind(document_id)
-    .execute(&state.pool)
+    .execute(&mut *tx)
     .await
     .map_err(AppError::Db)?
     .rows_affected();
@@ -412,10 +1085,15 @@ pub async fn hard_delete_document(
         return Err(AppError::NotFound("Document not found"));
     }
 
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(state, deferred_audit).await;
+
     info!(
         user_id = %current_user.id,
         document_id = %document_id,
         versions_deleted = versions.len(),
+        failed_deletes = failed_deletes,
         "Document hard-deleted successfully"
     );
 
@@ -426,116 +1104,1826 @@ pub async fn hard_delete_document(
     })))
 }
 
+/// Most documents a single batch delete may touch
+const BATCH_DELETE_MAX_DOCUMENTS: usize = 500;
+
+/// Soft- or hard-delete several documents in one transaction. A hard delete
+/// also removes soft-deleted documents; `already_deleted` only applies to
+/// soft deletes. Stored files are removed after the transaction commits, so
+/// no row locks are held across storage calls; STRICT_HARD_DELETE therefore
+/// does not apply, and documents whose files could not all be removed are
+/// reported as `storage_failed`.
+#[utoipa::path(
+    post,
+    path = "/documents/batch-delete",
+    tag = "documents",
+    request_body = BatchDeleteRequest,
+    responses(
+        (status = 200, description = "Every listed document was deleted", body = BatchDeleteResponse),
+        (status = 207, description = "Some documents were skipped or left files behind; see per-document statuses", body = BatchDeleteResponse),
+        (status = 400, description = "Bad request - empty or oversized id list"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn batch_delete_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<BatchDeleteRequest>,
+) -> Result<(StatusCode, Json<BatchDeleteResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Delete)?;
+
+    if request.ids.is_empty() {
+        return Err(AppError::BadRequest("ids cannot be empty"));
+    }
+    if request.ids.len() > BATCH_DELETE_MAX_DOCUMENTS {
+        return Err(AppError::BadRequest("Too many ids (max 500)"));
+    }
+
+    // Keep the caller's order but don't process a document twice
+    let mut document_ids: Vec<Uuid> = Vec::with_capacity(request.ids.len());
+    for id in request.ids {
+        if !document_ids.contains(&id) {
+            document_ids.push(id);
+        }
+    }
+
+    let delete_type = if request.hard { "hard" } else { "soft" };
+
+    let mut tx = state.pool.begin().await?;
+
+    let existing = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = ANY($1)
+        FOR UPDATE
+        "#,
+    )
+    .bind(&document_ids)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut results = Vec::with_capacity(document_ids.len());
+    let mut deferred_audits = Vec::new();
+    // Objects of hard-deleted documents, removed once the rows are gone
+    let mut orphaned_versions: Vec<(Uuid, Vec<DocumentVersion>)> = Vec::new();
+
+    for document_id in &document_ids {
+        let Some(doc) = existing.iter().find(|d| d.id == *document_id) else {
+            results.push(BatchDeleteResult { document_id: *document_id, status: BatchDeleteStatus::NotFound });
+            continue;
+        };
+
+        let mut audit_metadata = serde_json::json!({
+            "delete_type": delete_type,
+            "bulk": true,
+            "title": &doc.title,
+            "category": &doc.category,
+        });
+
+        if request.hard {
+            let versions = sqlx::query_as::<_, DocumentVersion>(
+                r#"
+                SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+                FROM document_versions
+                WHERE document_id = $1
+                "#,
+            )
+            .bind(document_id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(AppError::Db)?;
+
+            sqlx::query("DELETE FROM documents WHERE id = $1")
+                .bind(document_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::Db)?;
+
+            audit_metadata["versions_deleted"] = serde_json::json!(versions.len());
+            audit_metadata["document_id"] = serde_json::json!(document_id);
+            orphaned_versions.push((*document_id, versions));
+        } else {
+            if doc.deleted_at.is_some() {
+                results.push(BatchDeleteResult { document_id: *document_id, status: BatchDeleteStatus::AlreadyDeleted });
+                continue;
+            }
+
+            sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+                .bind(document_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::Db)?;
+        }
+
+        deferred_audits.push(
+            log_in_tx(
+                &state,
+                &mut tx,
+                NewAuditLog {
+                    user_id: current_user.id.to_string(),
+                    action: AuditAction::Delete,
+                    // As in hard_delete, a removed document is named in the metadata
+                    document_id: (!request.hard).then_some(*document_id),
+                    document_version: None,
+                    metadata: audit_metadata,
+                },
+            )
+            .await?,
+        );
+
+        info!(
+            user_id = %current_user.id,
+            document_id = %document_id,
+            delete_type = delete_type,
+            "Document deleted in batch"
+        );
+        results.push(BatchDeleteResult { document_id: *document_id, status: BatchDeleteStatus::Deleted });
+    }
+
+    tx.commit().await.map_err(|err| {
+        warn!(error = ?err, "Failed to commit batch delete transaction");
+        AppError::Db(err)
+    })?;
+
+    for deferred in deferred_audits {
+        log_deferred(&state, deferred).await;
+    }
+
+    for (document_id, versions) in &orphaned_versions {
+        let failed_deletes = delete_version_objects(&state, *document_id, versions).await;
+        if failed_deletes > 0 {
+            warn!(
+                document_id = %document_id,
+                failed_deletes = failed_deletes,
+                "Document hard-deleted in batch but some files remain in storage"
+            );
+            if let Some(result) = results.iter_mut().find(|r| r.document_id == *document_id) {
+                result.status = BatchDeleteStatus::StorageFailed;
+            }
+        }
+    }
+
+    let deleted = results
+        .iter()
+        .filter(|r| matches!(r.status, BatchDeleteStatus::Deleted | BatchDeleteStatus::StorageFailed))
+        .count();
+    let skipped = results.len() - deleted;
+    let storage_failed = results
+        .iter()
+        .any(|r| r.status == BatchDeleteStatus::StorageFailed);
+
+    info!(
+        user_id = %current_user.id,
+        delete_type = delete_type,
+        deleted = deleted,
+        skipped = skipped,
+        "Batch delete finished"
+    );
+
+    let status = if skipped == 0 && !storage_failed { StatusCode::OK } else { StatusCode::MULTI_STATUS };
+
+    Ok((status, Json(BatchDeleteResponse { results, deleted, skipped })))
+}
+
+/// Query behind `GET /documents/{id}/versions`: $1 document id. Shared with `/admin/explain`.
+pub(crate) const LIST_VERSIONS_SQL: &str = r#"
+    SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+    FROM document_versions
+    WHERE document_id = $1
+    ORDER BY version_number DESC
+    "#;
+
+/// Version history of a document, newest first
 #[utoipa::path(
     get,
-    path = "/documents",
+    path = "/documents/{id}/versions",
     tag = "documents",
     params(
-        ("page" = Option<u32>, Query, description = "Page number (default: 1)"),
-        ("page_size" = Option<u32>, Query, description = "Page size (default: 20, max: 100)"),
-        ("title" = Option<String>, Query, description = "Filter by title (partial match)"),
-        ("category" = Option<String>, Query, description = "Filter by category (exact match)")
+        ("id" = Uuid, Path, description = "Document ID")
     ),
     responses(
-        (status = 200, description = "List of documents", body = ListDocumentsResponse),
+        (status = 200, description = "Versions, newest first", body = Vec<DocumentVersion>),
+        (status = 404, description = "Document not found or has been deleted"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
         (status = 401, description = "Unauthorized")
     ),
     security(
         ("api_key" = [])
     )
 )]
-async fn list_documents(
+pub async fn list_versions(
     State(state): State<AppState>,
-    Query(params): Query<ListDocumentsQuery>,
-) -> Result<Json<ListDocumentsResponse>, AppError> {
-    let page = params.page.unwrap_or(1).max(1);
-    let page_size = params.page_size.unwrap_or(20).min(100);
-    let offset = (page - 1) as i64 * page_size as i64;
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
 
-    // Filters
-    let title_filter = params.title.unwrap_or_default();
-    let category_filter = params.category;
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.read_pool)
+    .await
+    .map_err(AppError::Db)?;
 
-    debug!(
-        page = page,
-        page_size = page_size,
-        title_filter = %title_filter,
-        category_filter = ?category_filter,
-        "Listing documents"
-    );
+    if !exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
 
-    // Count total (exclude soft-deleted documents)
-    let total: (i64,) = sqlx::query_as(
-        r#"
-        SELECT COUNT(*)
-        FROM documents d
-        WHERE d.deleted_at IS NULL
-          AND ($1 = '' OR d.title ILIKE '%' || $1 || '%')
-          AND ($2::text IS NULL OR d.category = $2)
-        "#
+    let versions = sqlx::query_as::<_, DocumentVersion>(LIST_VERSIONS_SQL)
+    .bind(document_id)
+    .fetch_all(&state.read_pool)
+    .timed("documents.list_versions", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    debug!(document_id = %document_id, versions = versions.len(), "Listed document versions");
+
+    capped_json("documents.list_versions", state.config.max_response_bytes, &versions)
+}
+
+/// Most documents a single bulk restore may touch
+const BULK_RESTORE_MAX_DOCUMENTS: usize = 500;
+
+/// Clear `deleted_at` on every listed soft-deleted document in one transaction
+#[utoipa::path(
+    post,
+    path = "/documents/restore/bulk",
+    tag = "documents",
+    request_body = BulkRestoreRequest,
+    responses(
+        (status = 200, description = "Every listed document was restored", body = BulkRestoreResponse),
+        (status = 207, description = "Some documents were skipped; see per-document statuses", body = BulkRestoreResponse),
+        (status = 400, description = "Bad request - empty or oversized id list"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn bulk_restore_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<BulkRestoreRequest>,
+) -> Result<(StatusCode, Json<BulkRestoreResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    if request.ids.is_empty() {
+        return Err(AppError::BadRequest("ids cannot be empty"));
+    }
+    if request.ids.len() > BULK_RESTORE_MAX_DOCUMENTS {
+        return Err(AppError::BadRequest("Too many ids (max 500)"));
+    }
+
+    // Keep the caller's order but don't process a document twice
+    let mut document_ids: Vec<Uuid> = Vec::with_capacity(request.ids.len());
+    for id in request.ids {
+        if !document_ids.contains(&id) {
+            document_ids.push(id);
+        }
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    let existing: Vec<(Uuid, bool)> = sqlx::query_as(
+        "SELECT id, deleted_at IS NOT NULL FROM documents WHERE id = ANY($1) FOR UPDATE"
     )
-    .bind(&title_filter)
-    .bind(&category_filter)
-    .fetch_one(&state.pool)
+    .bind(&document_ids)
+    .fetch_all(&mut *tx)
     .await
     .map_err(AppError::Db)?;
 
-    // Fetch page with latest version
-    let rows = sqlx::query_as::<_, DocumentWithLatest>(
+    let mut results = Vec::with_capacity(document_ids.len());
+    let mut deferred_audits = Vec::new();
+
+    for document_id in &document_ids {
+        let status = match existing.iter().find(|(id, _)| id == document_id) {
+            None => BulkRestoreStatus::NotFound,
+            Some((_, false)) => BulkRestoreStatus::NotDeleted,
+            Some((_, true)) => {
+                sqlx::query("UPDATE documents SET deleted_at = NULL, updated_at = NOW() WHERE id = $1")
+                    .bind(document_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(AppError::Db)?;
+
+                deferred_audits.push(
+                    log_in_tx(
+                        &state,
+                        &mut tx,
+                        NewAuditLog {
+                            user_id: current_user.id.to_string(),
+                            action: AuditAction::RestoreDocument,
+                            document_id: Some(*document_id),
+                            document_version: None,
+                            metadata: serde_json::json!({ "bulk": true }),
+                        },
+                    )
+                    .await?,
+                );
+
+                info!(document_id = %document_id, user_id = %current_user.id, "Document restored");
+                BulkRestoreStatus::Restored
+            }
+        };
+
+        results.push(BulkRestoreResult {
+            document_id: *document_id,
+            status,
+        });
+    }
+
+    tx.commit().await.map_err(|err| {
+        warn!(error = ?err, "Failed to commit bulk restore transaction");
+        AppError::Db(err)
+    })?;
+
+    for deferred in deferred_audits {
+        log_deferred(&state, deferred).await;
+    }
+
+    let restored = results
+        .iter()
+        .filter(|r| r.status == BulkRestoreStatus::Restored)
+        .count();
+    let skipped = results.len() - restored;
+
+    info!(
+        user_id = %current_user.id,
+        restored = restored,
+        skipped = skipped,
+        "Bulk restore finished"
+    );
+
+    let status = if skipped == 0 { StatusCode::OK } else { StatusCode::MULTI_STATUS };
+
+    Ok((status, Json(BulkRestoreResponse { results, restored, skipped })))
+}
+
+/// Roll back to an earlier revision by copying its content into a new
+/// version. History is preserved; the restored content becomes the latest.
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/versions/{version}/restore",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("version" = i32, Path, description = "Version number to restore")
+    ),
+    responses(
+        (status = 200, description = "New version created from the restored one", body = DocumentVersion),
+        (status = 404, description = "Document or version not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Write access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn restore_document_version(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path((document_id, version)): Path<(Uuid, i32)>,
+) -> Result<Json<DocumentVersion>, AppError> {
+    let result = restore_version(&state, &current_user, document_id, version).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::RestoreVersion, Some(document_id), e).await;
+    }
+    result.map(Json)
+}
+
+async fn restore_version(
+    state: &AppState,
+    current_user: &CurrentUser,
+    document_id: Uuid,
+    version: i32,
+) -> Result<DocumentVersion, AppError> {
+    check_permission(current_user, StorageAction::Write)?;
+
+    let source = resolve_version(state, &state.pool, document_id, Some(version)).await?;
+
+    let mut tx = state.pool.begin().await?;
+
+    // Lock the document so concurrent restores and uploads can't take the
+    // same number
+    let doc = sqlx::query_as::<_, Document>(
         r#"
-        WITH latest_versions AS (
-            SELECT DISTINCT ON (document_id)
-                document_id,
-                version_number,
-                file_name,
-                file_size,
-                mime_type,
-                created_at
-            FROM document_versions
-            ORDER BY document_id, version_number DESC
-        )
-        SELECT
-            d.id,
-            d.title,
-            d.category,
-            d.created_at,
-            d.updated_at,
-            lv.version_number AS latest_version_number,
-            lv.file_name AS latest_file_name,
-            lv.file_size AS latest_file_size,
-            lv.mime_type AS latest_mime_type,
-            lv.created_at AS latest_created_at
-        FROM documents d
-        LEFT JOIN latest_versions lv ON lv.document_id = d.id
-        WHERE d.deleted_at IS NULL
-          AND ($1 = '' OR d.title ILIKE '%' || $1 || '%')
-          AND ($2::text IS NULL OR d.category = $2)
-        ORDER BY d.created_at DESC
-        LIMIT $3 OFFSET $4
-        "#
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
     )
-    .bind(&title_filter)
-    .bind(&category_filter)
-    .bind(page_size as i64)
-    .bind(offset)
-    .fetch_all(&state.pool)
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found or has been deleted"))?;
+
+    let next_version: i32 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(version_number), 0) + 1 FROM document_versions WHERE document_id = $1",
+    )
+    .bind(document_id)
+    .fetch_one(&mut *tx)
     .await
     .map_err(AppError::Db)?;
 
-    let resp = ListDocumentsResponse {
-        data: rows,
-        page,
-        page_size,
-        total: total.0,
-    };
+    let stored_path = version_key(
+        state.config.key_strategy,
+        state.config.key_namespaces,
+        doc.category.as_deref(),
+        &doc.title,
+        document_id,
+        next_version,
+    );
 
     info!(
-        total = total.0,
-        returned = resp.data.len(),
-        page = page,
-        page_size = page_size,
-        "Documents retrieved successfully"
+        user_id = %current_user.id,
+        document_id = %document_id,
+        restored_from = version,
+        new_version = next_version,
+        stored_key = %stored_path,
+        "Restoring document version"
     );
 
-    Ok(Json(resp))
-}
\ No newline at end of file
+    state.storage.copy(&source.file_path, &stored_path).await?;
+
+    // Nothing references the copy until the transaction commits, so it is
+    // removed whatever fails from here on
+    let committed: Result<_, AppError> = async {
+        let restored = sqlx::query_as::<_, DocumentVersion>(
+            r#"
+            INSERT INTO document_versions
+            (document_id, version_number, file_name, file_path, file_size, mime_type, checksum, uploaded_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+            "#,
+        )
+        .bind(document_id)
+        .bind(next_version)
+        .bind(&source.file_name)
+        .bind(&stored_path)
+        .bind(source.file_size)
+        .bind(&source.mime_type)
+        .bind(&source.checksum)
+        .bind(current_user.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+        let audit_entry = NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::RestoreVersion,
+            document_id: Some(document_id),
+            document_version: Some(next_version),
+            metadata: serde_json::json!({
+                "restored_from": version,
+                "file_name": &source.file_name,
+                "checksum": &source.checksum,
+            }),
+        };
+        let deferred_audit = log_in_tx(state, &mut tx, audit_entry).await?;
+
+        tx.commit().await.map_err(AppError::Db)?;
+        Ok((restored, deferred_audit))
+    }
+    .await;
+
+    let (restored, deferred_audit) = match committed {
+        Ok(committed) => committed,
+        Err(e) => {
+            if let Err(e) = state.storage.delete(&stored_path).await {
+                warn!(error = ?e, key = %stored_path, "Failed to clean up object after aborted restore");
+            }
+            return Err(e);
+        }
+    };
+
+    log_deferred(state, deferred_audit).await;
+
+    if let Err(e) = notify_watchers(
+        &state.pool,
+        document_id,
+        next_version,
+        current_user.id,
+        &format!(
+            "Version {} of \"{}\" restored as version {} by {}",
+            version, doc.title, next_version, current_user.username
+        ),
+    )
+    .await
+    {
+        warn!(error = ?e, document_id = %document_id, "Failed to notify document watchers");
+    }
+
+    Ok(restored)
+}
+
+/// Make an existing version the one served when a download names no
+/// version, e.g. to publish a draft uploaded earlier or to keep serving an
+/// approved version while newer drafts are uploaded.
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/promote/{version}",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("version" = i32, Path, description = "Version number to promote")
+    ),
+    responses(
+        (status = 200, description = "Version promoted", body = PromoteVersionResponse),
+        (status = 404, description = "Document or version not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Write access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn promote_document_version(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path((document_id, version)): Path<(Uuid, i32)>,
+) -> Result<Json<PromoteVersionResponse>, AppError> {
+    let result = promote_version(&state, &current_user, document_id, version).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::PromoteVersion, Some(document_id), e).await;
+    }
+    result.map(Json)
+}
+
+async fn promote_version(
+    state: &AppState,
+    current_user: &CurrentUser,
+    document_id: Uuid,
+    version: i32,
+) -> Result<PromoteVersionResponse, AppError> {
+    check_permission(current_user, StorageAction::Write)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let previous_version: Option<i32> = sqlx::query_scalar(
+        r#"
+        SELECT current_version
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found or has been deleted"))?;
+
+    let version_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM document_versions WHERE document_id = $1 AND version_number = $2)",
+    )
+    .bind(document_id)
+    .bind(version)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !version_exists {
+        return Err(AppError::NotFound("document version not found"));
+    }
+
+    sqlx::query("UPDATE documents SET current_version = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(document_id)
+        .bind(version)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    let deferred_audit = log_in_tx(
+        state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::PromoteVersion,
+            document_id: Some(document_id),
+            document_version: Some(version),
+            metadata: serde_json::json!({
+                "previous_version": previous_version,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        version = version,
+        previous_version = ?previous_version,
+        "Promoted document version"
+    );
+
+    Ok(PromoteVersionResponse {
+        document_id,
+        current_version: version,
+        previous_version,
+    })
+}
+
+/// Prune old versions of a document, keeping the newest `keep` and the
+/// promoted one, if any. A document is never left without content: if the prune would remove every
+/// version, the latest one is kept and the document is soft-deleted instead
+/// (or the prune is refused when PRUNE_SOFT_DELETES_EMPTY is false).
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/prune",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body = PruneVersionsRequest,
+    responses(
+        (status = 200, description = "Versions pruned", body = PruneVersionsResponse),
+        (status = 400, description = "Document is deleted, or pruning to zero versions is disabled"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn prune_versions(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(req): Json<PruneVersionsRequest>,
+) -> Result<Json<PruneVersionsResponse>, AppError> {
+    let result = prune(&state, &current_user, document_id, req.keep as usize).await;
+    if let Err(e) = &result {
+        log_failure(&state, current_user.id.to_string(), AuditAction::Delete, Some(document_id), e).await;
+    }
+    result.map(Json)
+}
+
+async fn prune(
+    state: &AppState,
+    current_user: &CurrentUser,
+    document_id: Uuid,
+    keep: usize,
+) -> Result<PruneVersionsResponse, AppError> {
+    check_permission(current_user, StorageAction::Delete)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    // Locked so a concurrent promotion can't pick a version pruned here
+    let doc = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found"))?;
+
+    if doc.deleted_at.is_some() {
+        return Err(AppError::BadRequest("Document is deleted"));
+    }
+
+    let current_version: Option<i32> = sqlx::query_scalar("SELECT current_version FROM documents WHERE id = $1")
+        .bind(document_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    // Newest first, so everything past `keep` is pruned
+    let versions = sqlx::query_as::<_, DocumentVersion>(LIST_VERSIONS_SQL)
+    .bind(document_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let empties_document = keep == 0 && !versions.is_empty();
+    if empties_document && !state.config.prune_soft_deletes_empty {
+        return Err(AppError::BadRequest(
+            "Pruning would remove every version; keep at least one or delete the document",
+        ));
+    }
+
+    // Keep the latest version of a document we are about to soft-delete so it
+    // can still be restored with content.
+    let keep = if empties_document { 1 } else { keep };
+    // The promoted version is kept too; downloads without a version serve it
+    let pruned: Vec<&DocumentVersion> = versions
+        .iter()
+        .skip(keep)
+        .filter(|v| Some(v.version_number) != current_version)
+        .collect();
+
+    if pruned.is_empty() && !empties_document {
+        return Ok(PruneVersionsResponse {
+            document_id,
+            pruned_versions: Vec::new(),
+            remaining_versions: versions.len(),
+            soft_deleted: false,
+        });
+    }
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        pruning = pruned.len(),
+        soft_delete = empties_document,
+        "Pruning document versions"
+    );
+
+    let pruned_ids: Vec<Uuid> = pruned.iter().map(|v| v.id).collect();
+    sqlx::query("DELETE FROM document_versions WHERE id = ANY($1)")
+        .bind(&pruned_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    if empties_document {
+        sqlx::query(
+            r#"
+            UPDATE documents
+            SET deleted_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(document_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+    }
+
+    let pruned_versions: Vec<i32> = pruned.iter().map(|v| v.version_number).collect();
+
+    let deferred_audit = log_in_tx(
+        state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::Delete,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "delete_type": if empties_document { "prune_soft" } else { "prune" },
+                "title": &doc.title,
+                "pruned_versions": &pruned_versions,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(state, deferred_audit).await;
+
+    // Rows are gone, so a failed object delete only leaves an orphan in storage
+    for batch in pruned.chunks(state.config.hard_delete_batch_size) {
+        let paths: Vec<String> = batch.iter().map(|v| v.file_path.clone()).collect();
+        if let Err(e) = state.storage.remove(paths).await {
+            warn!(
+                error = ?e,
+                document_id = %document_id,
+                "Failed to delete pruned version files from storage"
+            );
+        }
+    }
+
+    Ok(PruneVersionsResponse {
+        document_id,
+        remaining_versions: versions.len() - pruned_versions.len(),
+        pruned_versions,
+        soft_deleted: empties_document,
+    })
+}
+
+/// WHERE clause shared by the count and page queries behind `GET /documents`,
+/// so the two can't drift: $1 title filter, $2 category, $3 required tag
+/// names, $4 include templates, $5 folder, $6/$7 created at-or-after/before,
+/// $8/$9 updated at-or-after/before, $10/$11 required metadata keys and their
+/// values, pairwise.
+const LIST_DOCUMENTS_FILTER_SQL: &str = r#"
+    WHERE d.deleted_at IS NULL
+      AND ($1 = '' OR d.title ILIKE '%' || $1 || '%')
+      AND ($2::text IS NULL OR d.category = $2)
+      AND (
+          cardinality($3::text[]) = 0
+          OR d.id IN (
+              SELECT dt.document_id
+              FROM document_tags dt
+              JOIN tags t ON t.id = dt.tag_id
+              WHERE t.name = ANY($3)
+              GROUP BY dt.document_id
+              HAVING COUNT(DISTINCT t.name) = cardinality($3::text[])
+          )
+      )
+      AND ($4 OR NOT d.is_template)
+      AND ($5::text IS NULL OR d.id IN (SELECT df.document_id FROM document_folders df WHERE df.folder = $5))
+      AND ($6::timestamptz IS NULL OR d.created_at >= $6)
+      AND ($7::timestamptz IS NULL OR d.created_at < $7)
+      AND ($8::timestamptz IS NULL OR d.updated_at >= $8)
+      AND ($9::timestamptz IS NULL OR d.updated_at < $9)
+      AND NOT EXISTS (
+          SELECT 1
+          FROM UNNEST($10::text[], $11::text[]) AS f(key, value)
+          WHERE NOT EXISTS (
+              SELECT 1 FROM document_metadata dm
+              WHERE dm.document_id = d.id AND dm.key = f.key AND dm.value = f.value
+          )
+      )
+    "#;
+
+/// Page query behind `GET /documents`, minus the filter, ORDER BY and LIMIT
+/// (see `list_documents_page_sql`)
+const LIST_DOCUMENTS_PAGE_SQL: &str = r#"
+    WITH latest_versions AS (
+        -- The promoted version while it exists, else the highest, as in resolve_version
+        SELECT DISTINCT ON (dv.document_id)
+            dv.document_id,
+            dv.version_number,
+            dv.file_name,
+            dv.file_size,
+            dv.mime_type,
+            dv.created_at
+        FROM document_versions dv
+        JOIN documents cd ON cd.id = dv.document_id
+        ORDER BY dv.document_id, (dv.version_number = cd.current_version) IS TRUE DESC, dv.version_number DESC
+    )
+    SELECT
+        d.id,
+        d.title,
+        d.category,
+        d.created_at,
+        d.updated_at,
+        lv.version_number AS latest_version_number,
+        lv.file_name AS latest_file_name,
+        lv.file_size AS latest_file_size,
+        lv.mime_type AS latest_mime_type,
+        lv.created_at AS latest_created_at
+    FROM documents d
+    LEFT JOIN latest_versions lv ON lv.document_id = d.id"#;
+
+/// Count query behind `GET /documents`, binding the same filter parameters
+fn list_documents_count_sql() -> String {
+    format!("\n    SELECT COUNT(*)\n    FROM documents d{}", LIST_DOCUMENTS_FILTER_SQL)
+}
+
+/// Full page query for the given sort: the filter parameters, then $12 limit
+/// and $13 offset. `id` breaks ties so pages stay stable. Shared with
+/// `/admin/explain`.
+pub(crate) fn list_documents_page_sql(sort_by: DocumentSortField, order: SortOrder) -> String {
+    format!(
+        "{}{}ORDER BY {} {order}, d.id {order}\n    LIMIT $12 OFFSET $13\n",
+        LIST_DOCUMENTS_PAGE_SQL,
+        LIST_DOCUMENTS_FILTER_SQL,
+        sort_by.column(),
+        order = order.keyword(),
+    )
+}
+
+/// `meta.<key>=<value>` query pairs as (key, trimmed value), in query order.
+/// Keys and values are bound as parameters, never spliced into SQL.
+fn metadata_filters(pairs: &[(String, String)]) -> Result<Vec<(String, String)>, AppError> {
+    pairs
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("meta.")?, value.trim())))
+        .map(|(key, value)| {
+            if key.is_empty() {
+                return Err(AppError::BadRequest("Metadata filters need a key, e.g. meta.department=finance"));
+            }
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/documents",
+    tag = "documents",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number (default: 1)"),
+        ("page_size" = Option<u32>, Query, description = "Page size (default: 20, max: 100)"),
+        ("title" = Option<String>, Query, description = "Filter by title (partial match)"),
+        ("category" = Option<String>, Query, description = "Filter by category (exact match)"),
+        ("tag" = Option<Vec<String>>, Query, description = "Only documents carrying this tag; repeat for several (all must match)"),
+        ("meta.{key}" = Option<String>, Query, description = "Only documents whose metadata `key` equals this value, e.g. `meta.department=finance`; repeat for several (all must match)"),
+        ("include_templates" = Option<bool>, Query, description = "Also list template documents (default: false)"),
+        ("folder" = Option<String>, Query, description = "Only documents filed in this folder (exact path)"),
+        ("sort_by" = Option<DocumentSortField>, Query, description = "Sort column: created_at (default), updated_at or title"),
+        ("order" = Option<SortOrder>, Query, description = "Sort direction: asc or desc (default)"),
+        ("created_after" = Option<String>, Query, description = "Only documents created at or after this RFC 3339 timestamp"),
+        ("created_before" = Option<String>, Query, description = "Only documents created before this RFC 3339 timestamp"),
+        ("updated_after" = Option<String>, Query, description = "Only documents updated at or after this RFC 3339 timestamp"),
+        ("updated_before" = Option<String>, Query, description = "Only documents updated before this RFC 3339 timestamp"),
+        ("include" = Option<String>, Query, description = "Comma-separated: `metadata` adds each document's key/value metadata as `metadata`, `tags` adds its tags as `tags`")
+    ),
+    responses(
+        (status = 200, description = "List of documents. With `include=metadata` each item also has a `metadata` object of key/value strings, and with `include=tags` a `tags` array of tags; both are omitted otherwise", body = ListDocumentsResponse),
+        (status = 400, description = "Invalid folder path, sort option, timestamp, include value or metadata filter, or an empty date range"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Read access required")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+async fn list_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(mut params): Query<ListDocumentsQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    // Embedded metadata and tags expose more than the listing itself, so the
+    // whole endpoint requires read access
+    check_permission(&current_user, StorageAction::Read)?;
+
+    for (key, value) in &pairs {
+        let value = value.trim();
+        if key == "tag" && !value.is_empty() && !params.tag.iter().any(|t| t == value) {
+            params.tag.push(value.to_string());
+        }
+    }
+    params.meta = metadata_filters(&pairs)?;
+
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) as i64 * page_size as i64;
+
+    // Filters
+    let title_filter = params.title.unwrap_or_default();
+    let category_filter = params.category;
+    let tag_filter = params.tag;
+    let (meta_keys, meta_values): (Vec<String>, Vec<String>) = params.meta.into_iter().unzip();
+    let include_templates = params.include_templates.unwrap_or(false);
+    let folder_filter = params
+        .folder
+        .as_deref()
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(normalize_folder_path)
+        .transpose()?;
+    let sort_by = params.sort_by.unwrap_or_default();
+    let order = params.order.unwrap_or_default();
+
+    let (mut include_metadata, mut include_tags) = (false, false);
+    for extra in params.include.as_deref().unwrap_or_default().split(',').map(str::trim) {
+        match extra {
+            "" => {}
+            "metadata" => include_metadata = true,
+            "tags" => include_tags = true,
+            _ => return Err(AppError::BadRequest("include accepts metadata and tags")),
+        }
+    }
+
+    // Malformed timestamps are already rejected by the Query extractor
+    let (created_after, created_before) = (params.created_after, params.created_before);
+    let (updated_after, updated_before) = (params.updated_after, params.updated_before);
+    if matches!((created_after, created_before), (Some(a), Some(b)) if a >= b) {
+        return Err(AppError::BadRequest("created_after must be earlier than created_before"));
+    }
+    if matches!((updated_after, updated_before), (Some(a), Some(b)) if a >= b) {
+        return Err(AppError::BadRequest("updated_after must be earlier than updated_before"));
+    }
+
+    debug!(
+        page = page,
+        page_size = page_size,
+        title_filter = %title_filter,
+        category_filter = ?category_filter,
+        tag_filter = ?tag_filter,
+        meta_keys = ?meta_keys,
+        include_templates = include_templates,
+        folder_filter = ?folder_filter,
+        sort_by = ?sort_by,
+        order = ?order,
+        created_after = ?created_after,
+        created_before = ?created_before,
+        updated_after = ?updated_after,
+        updated_before = ?updated_before,
+        include_metadata = include_metadata,
+        include_tags = include_tags,
+        "Listing documents"
+    );
+
+    // Count total (exclude soft-deleted documents)
+    let total: (i64,) = sqlx::query_as(&list_documents_count_sql())
+    .bind(&title_filter)
+    .bind(&category_filter)
+    .bind(&tag_filter)
+    .bind(include_templates)
+    .bind(&folder_filter)
+    .bind(created_after)
+    .bind(created_before)
+    .bind(updated_after)
+    .bind(updated_before)
+    .bind(&meta_keys)
+    .bind(&meta_values)
+    .fetch_one(&state.read_pool)
+    .timed("list_documents.count", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    // Fetch page with latest version
+    let page_sql = list_documents_page_sql(sort_by, order);
+    let mut rows = sqlx::query_as::<_, DocumentWithLatest>(&page_sql)
+    .bind(&title_filter)
+    .bind(&category_filter)
+    .bind(&tag_filter)
+    .bind(include_templates)
+    .bind(&folder_filter)
+    .bind(created_after)
+    .bind(created_before)
+    .bind(updated_after)
+    .bind(updated_before)
+    .bind(&meta_keys)
+    .bind(&meta_values)
+    .bind(page_size as i64)
+    .bind(offset)
+    .fetch_all(&state.read_pool)
+    .timed("list_documents.page", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    if include_metadata || include_tags {
+        attach_extras(&state, &mut rows, include_metadata, include_tags).await?;
+    }
+
+    let total_pages = total_pages(total.0, page_size);
+    let resp = ListDocumentsResponse {
+        data: rows,
+        page,
+        page_size,
+        total: total.0,
+        total_pages,
+        has_next: page < total_pages,
+        has_prev: page > 1,
+    };
+
+    info!(
+        total = total.0,
+        returned = resp.data.len(),
+        page = page,
+        page_size = page_size,
+        "Documents retrieved successfully"
+    );
+
+    capped_json("documents.list", state.config.max_response_bytes, &resp)
+}
+
+/// Fill in metadata and/or tags for a page of documents with one aggregate
+/// query over the page's ids, instead of a lookup per document
+async fn attach_extras(
+    state: &AppState,
+    rows: &mut [DocumentWithLatest],
+    include_metadata: bool,
+    include_tags: bool,
+) -> Result<(), AppError> {
+    let ids: Vec<Uuid> = rows.iter().map(|r| r.id).collect();
+
+    #[derive(sqlx::FromRow)]
+    struct Extras {
+        id: Uuid,
+        metadata: SqlJson<BTreeMap<String, String>>,
+        tags: SqlJson<Vec<Tag>>,
+    }
+
+    let extras = sqlx::query_as::<_, Extras>(
+        r#"
+        SELECT
+            page.id,
+            CASE WHEN $2 THEN (
+                SELECT COALESCE(jsonb_object_agg(dm.key, COALESCE(dm.value, '')), '{}'::jsonb)
+                FROM document_metadata dm
+                WHERE dm.document_id = page.id
+            ) ELSE '{}'::jsonb END AS metadata,
+            CASE WHEN $3 THEN (
+                SELECT COALESCE(json_agg(tag ORDER BY tag.name), '[]'::json)::jsonb
+                FROM (
+                    SELECT t.id, t.name, t.created_at
+                    FROM document_tags dt
+                    JOIN tags t ON t.id = dt.tag_id
+                    WHERE dt.document_id = page.id
+                ) tag
+            ) ELSE '[]'::jsonb END AS tags
+        FROM UNNEST($1::uuid[]) AS page(id)
+        "#,
+    )
+    .bind(&ids)
+    .bind(include_metadata)
+    .bind(include_tags)
+    .fetch_all(&state.read_pool)
+    .timed("list_documents.extras", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut extras: HashMap<Uuid, (BTreeMap<String, String>, Vec<Tag>)> = extras
+        .into_iter()
+        .map(|e| (e.id, (e.metadata.0, e.tags.0)))
+        .collect();
+    for row in rows.iter_mut() {
+        let (metadata, tags) = extras.remove(&row.id).unwrap_or_default();
+        if include_metadata {
+            row.metadata = Some(metadata);
+        }
+        if include_tags {
+            row.tags = Some(tags);
+        }
+    }
+
+    Ok(())
+}
+
+/// Activity feed: newest versions across all live documents
+#[utoipa::path(
+    get,
+    path = "/versions/recent",
+    tag = "documents",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number (default: 1)"),
+        ("page_size" = Option<u32>, Query, description = "Page size (default: 20, max: 100)")
+    ),
+    responses(
+        (status = 200, description = "Most recently created versions first", body = RecentVersionsResponse),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_recent_versions(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(params): Query<RecentVersionsQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let page = params.page.unwrap_or(1).max(1);
+    let page_size = params.page_size.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) as i64 * page_size as i64;
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM document_versions dv
+        JOIN documents d ON d.id = dv.document_id
+        WHERE d.deleted_at IS NULL
+        "#
+    )
+    .fetch_one(&state.read_pool)
+    .timed("recent_versions.count", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    let rows = sqlx::query_as::<_, RecentVersion>(
+        r#"
+        SELECT
+            dv.document_id,
+            d.title,
+            d.category,
+            dv.version_number,
+            dv.file_name,
+            dv.file_size,
+            dv.mime_type,
+            dv.created_at
+        FROM document_versions dv
+        JOIN documents d ON d.id = dv.document_id
+        WHERE d.deleted_at IS NULL
+        ORDER BY dv.created_at DESC, dv.id DESC
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(page_size as i64)
+    .bind(offset)
+    .fetch_all(&state.read_pool)
+    .timed("recent_versions.page", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    let total_pages = total_pages(total, page_size);
+    let resp = RecentVersionsResponse {
+        data: rows,
+        page,
+        page_size,
+        total,
+        total_pages,
+        has_next: page < total_pages,
+        has_prev: page > 1,
+    };
+
+    debug!(returned = resp.data.len(), page = page, "Recent versions listed");
+
+    capped_json("versions.recent", state.config.max_response_bytes, &resp)
+}
+
+/// Bytes read from storage to build a preview
+const PREVIEW_MAX_BYTES: u64 = 64 * 1024;
+
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/preview",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("version" = Option<i32>, Query, description = "Version number (optional, defaults to latest)"),
+        ("rows" = Option<u32>, Query, description = "Rows/lines to return (default: 20, max: 100)")
+    ),
+    responses(
+        (status = 200, description = "Structured preview", body = DocumentPreviewResponse),
+        (status = 404, description = "Document not found"),
+        (status = 415, description = "Version is not text/plain or text/csv"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn preview_document(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<PreviewQuery>,
+    current_user: CurrentUser,
+) -> Result<Json<DocumentPreviewResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let dv = resolve_version(&state, &state.read_pool, document_id, query.version).await?;
+    let max_rows = query.rows.unwrap_or(20).clamp(1, 100) as usize;
+
+    let mime_type = dv.mime_type.clone().unwrap_or_default();
+    let essence = mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    let is_csv = essence == "text/csv" || essence == "application/csv";
+    if !is_csv && essence != "text/plain" {
+        return Err(AppError::UnsupportedMediaType(
+            "Preview is only available for text/plain and text/csv documents",
+        ));
+    }
+
+    // Only fetch the head of the object, not the whole file
+    let bytes = read_prefix(&state.storage, &dv.file_path, PREVIEW_MAX_BYTES).await?;
+    let mut truncated = (dv.file_size as u64) > PREVIEW_MAX_BYTES;
+
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    if truncated {
+        // Drop the partial last line cut off by the range read
+        if let Some(last_newline) = text.rfind('\n') {
+            text.truncate(last_newline + 1);
+        }
+    }
+
+    let mut response = DocumentPreviewResponse {
+        document_id,
+        version_number: dv.version_number,
+        mime_type,
+        kind: if is_csv { "csv" } else { "text" }.to_string(),
+        headers: None,
+        rows: None,
+        lines: None,
+        truncated,
+    };
+
+    if is_csv {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(text.as_bytes());
+
+        let headers = reader
+            .headers()
+            .map_err(|_| AppError::BadRequest("Document is not valid CSV"))?
+            .iter()
+            .map(str::to_string)
+            .collect();
+
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            if rows.len() == max_rows {
+                truncated = true;
+                break;
+            }
+            let record = record.map_err(|_| AppError::BadRequest("Document is not valid CSV"))?;
+            rows.push(record.iter().map(str::to_string).collect());
+        }
+
+        response.headers = Some(headers);
+        response.rows = Some(rows);
+    } else {
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if lines.len() > max_rows {
+            lines.truncate(max_rows);
+            truncated = true;
+        }
+        response.lines = Some(lines);
+    }
+
+    response.truncated = truncated;
+
+    debug!(
+        document_id = %document_id,
+        version_number = dv.version_number,
+        kind = %response.kind,
+        truncated = truncated,
+        "Document preview generated"
+    );
+
+    Ok(Json(response))
+}
+
+/// Largest version, in bytes, that can be diffed
+const DIFF_MAX_BYTES: i64 = 1024 * 1024;
+
+/// Unified diff between two versions of a text document
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/versions/diff",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("from" = i32, Query, description = "Version to compare from"),
+        ("to" = i32, Query, description = "Version to compare to"),
+        ("context" = Option<u32>, Query, description = "Unchanged lines around each change (default: 3, max: 20)")
+    ),
+    responses(
+        (status = 200, description = "Unified diff", body = VersionDiffResponse),
+        (status = 404, description = "Document or version not found"),
+        (status = 413, description = "A version is larger than 1 MiB, or the diff exceeds MAX_RESPONSE_BYTES"),
+        (status = 415, description = "A version is not text"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn diff_versions(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<VersionDiffQuery>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let context = query.context.unwrap_or(3).min(20) as usize;
+
+    let from = resolve_version(&state, &state.read_pool, document_id, Some(query.from)).await?;
+    let to = resolve_version(&state, &state.read_pool, document_id, Some(query.to)).await?;
+
+    // Check both before reading either, so a binary `to` costs no storage read
+    for dv in [&from, &to] {
+        if !is_text_like(dv.mime_type.as_deref().unwrap_or_default()) {
+            return Err(AppError::UnsupportedMediaType("Only text versions can be diffed"));
+        }
+        if dv.file_size > DIFF_MAX_BYTES {
+            return Err(AppError::ResponseTooLarge("Versions larger than 1 MiB cannot be diffed"));
+        }
+    }
+
+    let old = read_text(&state, &from).await?;
+    let new = read_text(&state, &to).await?;
+
+    let diff = TextDiff::from_lines(&old, &new);
+    let (mut lines_added, mut lines_removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => lines_added += 1,
+            ChangeTag::Delete => lines_removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    let identical = lines_added == 0 && lines_removed == 0;
+
+    let unified = if identical {
+        String::new()
+    } else {
+        diff.unified_diff()
+            .context_radius(context)
+            .header(
+                &format!("{} (v{})", from.file_name, from.version_number),
+                &format!("{} (v{})", to.file_name, to.version_number),
+            )
+            .to_string()
+    };
+
+    debug!(
+        document_id = %document_id,
+        from = from.version_number,
+        to = to.version_number,
+        lines_added = lines_added,
+        lines_removed = lines_removed,
+        "Version diff generated"
+    );
+
+    capped_json(
+        "versions.diff",
+        state.config.max_response_bytes,
+        &VersionDiffResponse {
+            document_id,
+            from_version: from.version_number,
+            to_version: to.version_number,
+            identical,
+            lines_added,
+            lines_removed,
+            diff: unified,
+        },
+    )
+}
+
+async fn read_text(state: &AppState, dv: &DocumentVersion) -> Result<String, AppError> {
+    let bytes = state.storage.read(&dv.file_path).await?.to_vec();
+    String::from_utf8(bytes).map_err(|_| {
+        warn!(version_id = %dv.id, "Text version is not valid UTF-8");
+        AppError::UnsupportedMediaType("Version content is not valid UTF-8 text")
+    })
+}
+
+/// Compare the sizes recorded in the database with what storage actually holds
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/footprint",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Per-version storage footprint", body = DocumentFootprintResponse),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn document_footprint(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    current_user: CurrentUser,
+) -> Result<Json<DocumentFootprintResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let versions = sqlx::query_as::<_, DocumentVersion>(
+        r#"
+        SELECT id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+        FROM document_versions
+        WHERE document_id = $1
+        ORDER BY version_number
+        "#,
+    )
+    .bind(document_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut footprints = Vec::with_capacity(versions.len());
+
+    for version in versions {
+        let stored_size = match state.storage.stat(&version.file_path).await {
+            Ok(meta) => Some(meta.content_length()),
+            Err(e) if e.kind() == opendal::ErrorKind::NotFound => None,
+            Err(e) => return Err(AppError::from(e)),
+        };
+
+        let size_mismatch = stored_size.is_some_and(|size| size != version.file_size as u64);
+        if stored_size.is_none() || size_mismatch {
+            warn!(
+                document_id = %document_id,
+                version_number = version.version_number,
+                file_path = %version.file_path,
+                file_size = version.file_size,
+                stored_size = ?stored_size,
+                "Stored object does not match recorded size"
+            );
+        }
+
+        footprints.push(VersionFootprint {
+            version_number: version.version_number,
+            storage_key: version.file_path,
+            file_size: version.file_size,
+            stored_size,
+            missing: stored_size.is_none(),
+            size_mismatch,
+        });
+    }
+
+    let response = DocumentFootprintResponse {
+        document_id,
+        total_file_size: footprints.iter().map(|f| f.file_size).sum(),
+        total_stored_size: footprints.iter().filter_map(|f| f.stored_size).sum(),
+        mismatches: footprints.iter().filter(|f| f.missing || f.size_mismatch).count(),
+        versions: footprints,
+    };
+
+    Ok(Json(response))
+}
+
+/// Name of the chain construction reported by the integrity endpoint
+const INTEGRITY_ALGORITHM: &str = "sha256-chain-v1";
+
+/// One link of the integrity chain: `sha256("{prev}:{version_number}:{checksum}")`,
+/// starting from an empty `prev`; a missing checksum hashes as an empty string.
+fn chain_link(prev: &str, version_number: i32, checksum: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}:{}:{}", prev, version_number, checksum.unwrap_or("")));
+    hex::encode(hasher.finalize())
+}
+
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/integrity",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Hash chain over the document's version checksums", body = DocumentIntegrityResponse),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn document_integrity(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    current_user: CurrentUser,
+) -> Result<Json<DocumentIntegrityResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.read_pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let checksums: Vec<(i32, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT version_number, checksum
+        FROM document_versions
+        WHERE document_id = $1
+        ORDER BY version_number
+        "#,
+    )
+    .bind(document_id)
+    .fetch_all(&state.read_pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut chain_hash = String::new();
+    let mut versions = Vec::with_capacity(checksums.len());
+
+    for (version_number, checksum) in checksums {
+        chain_hash = chain_link(&chain_hash, version_number, checksum.as_deref());
+        versions.push(VersionIntegrity {
+            version_number,
+            checksum,
+            chain_hash: chain_hash.clone(),
+        });
+    }
+
+    let complete = versions.iter().all(|v| v.checksum.is_some());
+    if !complete {
+        debug!(document_id = %document_id, "Some versions have no recorded checksum");
+    }
+
+    Ok(Json(DocumentIntegrityResponse {
+        document_id,
+        algorithm: INTEGRITY_ALGORITHM,
+        root_hash: chain_hash,
+        versions,
+        complete,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_closed_range() {
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), ByteRange::Partial(0, 9));
+        // An end past the object is clamped to the last byte
+        assert_eq!(parse_range(Some("bytes=90-200"), 100), ByteRange::Partial(90, 99));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range(Some("bytes=-5"), 100), ByteRange::Partial(95, 99));
+        assert_eq!(parse_range(Some("bytes=-500"), 100), ByteRange::Partial(0, 99));
+    }
+
+    #[test]
+    fn parse_range_open_ended() {
+        assert_eq!(parse_range(Some("bytes=5-"), 100), ByteRange::Partial(5, 99));
+    }
+
+    #[test]
+    fn parse_range_start_past_end_is_unsatisfiable() {
+        assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=150-160"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=-0"), 100), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_malformed_serves_full_body() {
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-9"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=abc"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=9-0"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn parse_range_multi_range_serves_full_body() {
+        assert_eq!(parse_range(Some("bytes=0-9,20-29"), 100), ByteRange::Full);
+    }
+
+    fn version(checksum: Option<&str>) -> DocumentVersion {
+        DocumentVersion {
+            id: Uuid::nil(),
+            document_id: Uuid::nil(),
+            version_number: 1,
+            file_name: "report.txt".to_string(),
+            file_path: "documents/report.txt".to_string(),
+            file_size: 10,
+            mime_type: None,
+            checksum: checksum.map(str::to_string),
+            created_at: DateTime::parse_from_rfc2822("Tue, 01 Sep 2026 10:00:00 GMT")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn not_modified_when_etag_matches() {
+        let dv = version(Some("abc123"));
+        assert!(is_not_modified(&dv, Some("\"abc123\""), None));
+        assert!(is_not_modified(&dv, Some("\"other\", \"abc123\""), None));
+        assert!(!is_not_modified(&dv, Some("\"other\""), None));
+    }
+
+    #[test]
+    fn not_modified_for_weak_etag_and_wildcard() {
+        let dv = version(Some("abc123"));
+        assert!(is_not_modified(&dv, Some("W/\"abc123\""), None));
+        assert!(is_not_modified(&dv, Some("*"), None));
+    }
+
+    #[test]
+    fn not_modified_since_exact_boundary() {
+        let dv = version(Some("abc123"));
+        assert!(is_not_modified(&dv, None, Some("Tue, 01 Sep 2026 10:00:00 GMT")));
+        assert!(is_not_modified(&dv, None, Some("Tue, 01 Sep 2026 10:00:01 GMT")));
+        assert!(!is_not_modified(&dv, None, Some("Tue, 01 Sep 2026 09:59:59 GMT")));
+        assert!(!is_not_modified(&dv, None, Some("not a date")));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let dv = version(Some("abc123"));
+        let later = Some("Tue, 01 Sep 2026 11:00:00 GMT");
+        assert!(!is_not_modified(&dv, Some("\"other\""), later));
+        assert!(is_not_modified(&dv, Some("\"abc123\""), Some("Mon, 01 Jan 2024 00:00:00 GMT")));
+    }
+
+    #[test]
+    fn expected_checksum_matches() {
+        let dv = version(Some("abc123"));
+        assert!(check_expected_checksum(&dv, "abc123", true).is_ok());
+        assert!(check_expected_checksum(&dv, "\"abc123\"", true).is_ok());
+        assert!(check_expected_checksum(&dv, "\"nope\", \"abc123\"", true).is_ok());
+        assert!(check_expected_checksum(&dv, "*", true).is_ok());
+    }
+
+    #[test]
+    fn expected_checksum_mismatch_is_precondition_failed() {
+        let dv = version(Some("abc123"));
+        assert!(matches!(
+            check_expected_checksum(&dv, "\"def456\"", false),
+            Err(AppError::PreconditionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn expected_checksum_ignores_case_and_weak_prefix() {
+        let dv = version(Some("abc123"));
+        assert!(check_expected_checksum(&dv, "ABC123", true).is_ok());
+        assert!(check_expected_checksum(&dv, "W/\"AbC123\"", true).is_ok());
+    }
+
+    #[test]
+    fn expected_checksum_without_stored_checksum() {
+        let dv = version(None);
+        assert!(check_expected_checksum(&dv, "abc123", false).is_ok());
+        assert!(matches!(
+            check_expected_checksum(&dv, "abc123", true),
+            Err(AppError::PreconditionFailed(_))
+        ));
+    }
+
+    fn chain(checksums: &[Option<&str>]) -> String {
+        checksums
+            .iter()
+            .enumerate()
+            .fold(String::new(), |prev, (i, checksum)| chain_link(&prev, i as i32 + 1, *checksum))
+    }
+
+    #[test]
+    fn chain_is_stable_for_fixed_input() {
+        let versions = [Some("aa"), Some("bb"), Some("cc")];
+        assert_eq!(
+            chain(&versions),
+            "4fd9df96f7e0b58357c2a1022bacb4bc66498903dcc2f1e4f4fa4a4448dd5834"
+        );
+        assert_eq!(chain(&versions), chain(&versions));
+    }
+
+    #[test]
+    fn chain_changes_when_an_earlier_checksum_changes() {
+        let root = chain(&[Some("aa"), Some("bb"), Some("cc")]);
+        assert_ne!(chain(&[Some("ab"), Some("bb"), Some("cc")]), root);
+        assert_ne!(chain(&[Some("aa"), Some("bc"), Some("cc")]), root);
+        assert_ne!(chain(&[None, Some("bb"), Some("cc")]), root);
+    }
+
+    fn pairs(raw: &[(&str, &str)]) -> Vec<(String, String)> {
+        raw.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn metadata_filters_collects_meta_pairs_in_order() {
+        let filters = metadata_filters(&pairs(&[
+            ("meta.department", " finance "),
+            ("page", "2"),
+            ("tag", "urgent"),
+            ("meta.owner", "alice"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            filters,
+            vec![
+                ("department".to_string(), "finance".to_string()),
+                ("owner".to_string(), "alice".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_filters_keeps_repeated_keys() {
+        // Every filter must match, so a repeated key with two values can only
+        // match nothing; it is passed through rather than collapsed
+        let filters = metadata_filters(&pairs(&[("meta.a", "1"), ("meta.a", "2")])).unwrap();
+        assert_eq!(filters.len(), 2);
+    }
+
+    #[test]
+    fn metadata_filters_keeps_empty_values() {
+        let filters = metadata_filters(&pairs(&[("meta.note", "")])).unwrap();
+        assert_eq!(filters, vec![("note".to_string(), String::new())]);
+    }
+
+    #[test]
+    fn metadata_filters_rejects_empty_key() {
+        assert!(matches!(
+            metadata_filters(&pairs(&[("meta.", "x")])),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn metadata_filters_ignores_other_parameters() {
+        assert!(metadata_filters(&pairs(&[("metadata", "x"), ("title", "meta.a")])).unwrap().is_empty());
+    }
+}
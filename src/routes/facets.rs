@@ -0,0 +1,141 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::db::TimedQuery;
+use crate::dtos::{FacetValue, FacetsQuery, FacetsResponse};
+use crate::error::AppError;
+use crate::response::capped_json;
+use crate::state::AppState;
+use axum::extract::{Query, State};
+use axum::response::Response;
+use axum::{routing::get, Router};
+use tracing::info;
+
+/// Facet values returned when `limit` is not given
+const DEFAULT_FACET_LIMIT: u32 = 100;
+
+/// Upper bound on facet values per request
+const MAX_FACET_LIMIT: u32 = 1000;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/facets", get(list_facets))
+}
+
+/// Field a facet request groups documents by
+enum Facet<'a> {
+    Category,
+    Tag,
+    Meta(&'a str),
+}
+
+impl<'a> Facet<'a> {
+    fn parse(field: &'a str) -> Result<Self, AppError> {
+        match field {
+            "category" => Ok(Facet::Category),
+            "tag" => Ok(Facet::Tag),
+            _ => match field.strip_prefix("meta:") {
+                Some(key) if !key.is_empty() => Ok(Facet::Meta(key)),
+                _ => Err(AppError::BadRequest(
+                    "field must be category, tag or meta:<key>",
+                )),
+            },
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/facets",
+    tag = "metadata",
+    params(
+        ("field" = String, Query, description = "`category`, `tag` or `meta:<key>`"),
+        ("limit" = Option<u32>, Query, description = "Maximum facet values (default: 100, max: 1000)")
+    ),
+    responses(
+        (status = 200, description = "Distinct values with live document counts, most common first", body = FacetsResponse),
+        (status = 400, description = "Unknown facet field"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_facets(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<FacetsQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let facet = Facet::parse(query.field.trim())?;
+    let limit = query.limit.unwrap_or(DEFAULT_FACET_LIMIT).clamp(1, MAX_FACET_LIMIT);
+
+    // One extra row tells us whether the list was cut off
+    let fetch_limit = limit as i64 + 1;
+
+    let mut values = match facet {
+        Facet::Category => {
+            sqlx::query_as::<_, FacetValue>(
+                r#"
+                SELECT category AS value, COUNT(*) AS document_count
+                FROM documents
+                WHERE deleted_at IS NULL AND category IS NOT NULL
+                GROUP BY category
+                ORDER BY document_count DESC, value
+                LIMIT $1
+                "#,
+            )
+            .bind(fetch_limit)
+            .fetch_all(&state.read_pool)
+            .timed("facets.category", state.config.slow_query_ms)
+            .await
+        }
+        Facet::Tag => {
+            sqlx::query_as::<_, FacetValue>(
+                r#"
+                SELECT t.name AS value, COUNT(DISTINCT dt.document_id) AS document_count
+                FROM document_tags dt
+                JOIN tags t ON t.id = dt.tag_id
+                JOIN documents d ON d.id = dt.document_id
+                WHERE d.deleted_at IS NULL
+                GROUP BY t.name
+                ORDER BY document_count DESC, value
+                LIMIT $1
+                "#,
+            )
+            .bind(fetch_limit)
+            .fetch_all(&state.read_pool)
+            .timed("facets.tag", state.config.slow_query_ms)
+            .await
+        }
+        Facet::Meta(key) => {
+            sqlx::query_as::<_, FacetValue>(
+                r#"
+                SELECT m.value AS value, COUNT(DISTINCT m.document_id) AS document_count
+                FROM document_metadata m
+                JOIN documents d ON d.id = m.document_id
+                WHERE d.deleted_at IS NULL AND m.key = $1
+                GROUP BY m.value
+                ORDER BY document_count DESC, value
+                LIMIT $2
+                "#,
+            )
+            .bind(key)
+            .bind(fetch_limit)
+            .fetch_all(&state.read_pool)
+            .timed("facets.meta", state.config.slow_query_ms)
+            .await
+        }
+    }
+    .map_err(AppError::Db)?;
+
+    let truncated = values.len() > limit as usize;
+    values.truncate(limit as usize);
+
+    info!(field = %query.field, values = values.len(), truncated = truncated, "Facets listed");
+
+    let response = FacetsResponse {
+        field: query.field,
+        values,
+        truncated,
+    };
+
+    capped_json("facets.list", state.config.max_response_bytes, &response)
+}
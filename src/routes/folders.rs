@@ -1,9 +1,13 @@
 use crate::auth::{check_permission, CurrentUser, StorageAction};
-use crate::dtos::{CreateFolderRequest, CreateFolderResponse, FolderInfo, ListFoldersResponse};
+use crate::dtos::{CreateFolderRequest, CreateFolderResponse, LinkFolderRequest, LinkFolderResponse, ListFoldersQuery, ListFoldersResponse};
 use crate::error::AppError;
+use crate::response::capped_json;
 use crate::state::AppState;
+use crate::storage::{folder_metadata_key, folders_root, sanitize_segment};
 use anyhow;
-use axum::{extract::State, routing::get, routing::post, Json, Router};
+use crate::audit::log_update_metadata;
+use axum::{extract::{Path, Query, State}, response::Response, routing::get, routing::post, Json, Router};
+use std::collections::VecDeque;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
@@ -21,14 +25,23 @@ pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/folders", post(create_folder))
         .route("/folders", get(list_folders))
+        .route("/documents/:id/folder", post(link_document_folder))
 }
 
 #[utoipa::path(
     get,
     path = "/folders",
     tag = "folders",
+    params(
+        ("parent" = Option<String>, Query, description = "Folder whose descendants to list, e.g. `finance/2024` (default: the root)"),
+        ("depth" = Option<u32>, Query, description = "Levels to descend (default: 1, max: 10)"),
+        ("limit" = Option<usize>, Query, description = "Maximum folders returned (default: 1000, max: 5000)")
+    ),
     responses(
-        (status = 200, description = "List of all folders", body = ListFoldersResponse),
+        (status = 200, description = "List of folders", body = ListFoldersResponse),
+        (status = 400, description = "Invalid parent path"),
+        (status = 404, description = "Parent folder not found"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
         (status = 401, description = "Unauthorized")
     ),
     security(("api_key" = []))
@@ -36,36 +49,72 @@ pub fn routes() -> Router<AppState> {
 pub async fn list_folders(
     State(state): State<AppState>,
     current_user: CurrentUser,
-) -> Result<Json<ListFoldersResponse>, AppError> {
+    Query(query): Query<ListFoldersQuery>,
+) -> Result<Response, AppError> {
     check_permission(&current_user, StorageAction::Read)?;
 
-    info!("Listing all folders");
+    let max_depth = query.depth.unwrap_or(1).clamp(1, 10);
+    let limit = query.limit.unwrap_or(1000).clamp(1, 5000);
+
+    let parent = match query.parent.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(parent) => Some(existing_folder(&state, parent).await?),
+        None => None,
+    };
 
-    let entries = state.storage.list("").await.map_err(|e| {
-        warn!(error = ?e, "Failed to list storage entries");
-        AppError::Storage(e)
-    })?;
+    info!(parent = ?parent, depth = max_depth, limit = limit, "Listing folders");
 
+    let root = folders_root(state.config.key_namespaces);
+    let start = match &parent {
+        Some(parent) => format!("{}{}/", root, parent),
+        None => root.to_string(),
+    };
     let mut folders = Vec::new();
+    let mut truncated = false;
+
+    // Breadth-first, one non-recursive list per directory, so depth bounds the work
+    let mut pending: VecDeque<(String, u32)> = VecDeque::from([(start, 1)]);
 
-    for entry in entries {
-        let path = entry.path();
+    'walk: while let Some((dir, depth)) = pending.pop_front() {
+        let entries = state.storage.list(&dir).await.map_err(|e| {
+            warn!(error = ?e, dir = %dir, "Failed to list storage entries");
+            AppError::from(e)
+        })?;
+
+        for entry in entries {
+            let path = entry.path();
+
+            if !path.ends_with('/') || path == dir {
+                continue;
+            }
 
-        if path.ends_with('/') {
-            let folder_name = path.trim_end_matches('/').to_string();
+            let folder_name = path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .trim_end_matches('/')
+                .to_string();
 
             if folder_name.is_empty() {
                 continue;
             }
 
-            let metadata_path = format!("{}/.folder_metadata.json", folder_name);
+            if depth < max_depth {
+                pending.push_back((path.to_string(), depth + 1));
+            } else if !truncated && has_subdirectories(&state, path).await {
+                truncated = true;
+            }
+
+            let metadata_path = folder_metadata_key(state.config.key_namespaces, &folder_name);
 
             match state.storage.read(&metadata_path).await {
                 Ok(metadata_bytes) => {
                     match serde_json::from_slice::<FolderMetadata>(&metadata_bytes.to_vec()) {
                         Ok(metadata) => {
+                            if folders.len() == limit {
+                                truncated = true;
+                                break 'walk;
+                            }
                             folders.push(crate::dtos::FolderInfo {
-                                folder_name: metadata.folder_name,
+                                folder_name,
                                 created_by: metadata.created_by,
                                 created_by_username: metadata.created_by_username,
                                 created_at: metadata.created_at,
@@ -83,7 +132,7 @@ pub async fn list_folders(
                 }
                 Err(e) => {
                     // Metadata file doesn't exist - folder was created by upload without metadata
-                    warn!(
+                    debug!(
                         folder = %folder_name,
                         error = ?e,
                         "Folder metadata not found, skipping folder"
@@ -95,15 +144,58 @@ pub async fn list_folders(
 
     info!(
         total_folders = folders.len(),
+        truncated = truncated,
         "Retrieved folders successfully"
     );
 
     let len = folders.len();
 
-    Ok(Json(crate::dtos::ListFoldersResponse {
+    let response = ListFoldersResponse {
         folders,
         total: len,
-    }))
+        truncated,
+    };
+
+    capped_json("folders.list", state.config.max_response_bytes, &response)
+}
+
+/// Split a folder path like `finance/2024` into sanitized segments joined by
+/// `/`. `.` and `..` are rejected outright rather than sanitized, so a path
+/// can never climb out of the folders namespace.
+pub(crate) fn normalize_folder_path(path: &str) -> Result<String, AppError> {
+    let mut segments = Vec::new();
+    for segment in path.trim().trim_matches('/').split('/') {
+        let segment = segment.trim();
+        if segment == "." || segment == ".." {
+            return Err(AppError::BadRequest("Folder path must not contain '.' or '..' segments"));
+        }
+        let sanitized = sanitize_segment(segment);
+        if sanitized.is_empty() {
+            return Err(AppError::BadRequest("Folder path must not contain empty segments"));
+        }
+        segments.push(sanitized);
+    }
+    Ok(segments.join("/"))
+}
+
+/// Normalize `path` and check that a folder was created there
+async fn existing_folder(state: &AppState, path: &str) -> Result<String, AppError> {
+    let path = normalize_folder_path(path)?;
+    let marker = folder_metadata_key(state.config.key_namespaces, &path);
+    if state.storage.stat(&marker).await.is_err() {
+        return Err(AppError::NotFound("Folder not found"));
+    }
+    Ok(path)
+}
+
+/// Whether `dir` contains any sub-directory we did not descend into
+async fn has_subdirectories(state: &AppState, dir: &str) -> bool {
+    match state.storage.list(dir).await {
+        Ok(entries) => entries
+            .iter()
+            .any(|e| e.path() != dir && e.path().ends_with('/')),
+        Err(_) => false,
+    }
 }
 
 #[utoipa::path(
@@ -113,7 +205,9 @@ pub async fn list_folders(
     request_body = CreateFolderRequest,
     responses(
         (status = 200, description = "Folder created successfully", body = CreateFolderResponse),
-        (status = 400, description = "Bad request - folder name invalid or already exists"),
+        (status = 400, description = "Bad request - folder name or parent path invalid"),
+        (status = 404, description = "Parent folder not found"),
+        (status = 409, description = "Folder already exists, or is being created by a concurrent request"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - write permission required")
     ),
@@ -130,21 +224,27 @@ pub async fn create_folder(
     if folder_name.is_empty() {
         return Err(AppError::BadRequest("Folder name cannot be empty"));
     }
+    if folder_name == "." || folder_name == ".." {
+        return Err(AppError::BadRequest("Folder name must not be '.' or '..'"));
+    }
 
-    // Sanitize folder name
-    let sanitized_name = folder_name
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>();
+    let parent = match request.parent.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(parent) => Some(existing_folder(&state, parent).await?),
+        None => None,
+    };
+
+    // Sanitize folder name; the stored name is the full path under its parent
+    let sanitized_name = match &parent {
+        Some(parent) => format!("{}/{}", parent, sanitize_segment(folder_name)),
+        None => sanitize_segment(folder_name),
+    };
+    // folder_registry.name is VARCHAR(255)
+    if sanitized_name.len() > 255 {
+        return Err(AppError::BadRequest("Folder path is longer than 255 characters"));
+    }
 
     // Check if folder exists by listing it and checking if it has any entries
-    let folder_path = format!("{}/", sanitized_name);
+    let folder_path = format!("{}{}/", folders_root(state.config.key_namespaces), sanitized_name);
     let folder_exists = match state.storage.list(&folder_path).await {
         Ok(entries) => {
             // Folder exists if it has at least one entry
@@ -160,11 +260,33 @@ pub async fn create_folder(
     };
 
     // Also check metadata file
-    let metadata_path = format!("{}/.folder_metadata.json", sanitized_name);
+    let metadata_path = folder_metadata_key(state.config.key_namespaces, &sanitized_name);
     let metadata_exists = state.storage.stat(&metadata_path).await.is_ok();
 
     if folder_exists || metadata_exists {
-        return Err(AppError::BadRequest("Folder already exists"));
+        return Err(AppError::Conflict("Folder already exists"));
+    }
+
+    // The storage check above can't stop a concurrent create; claiming the
+    // name in the registry can. The claim commits only once the marker exists.
+    let mut claim = None;
+    if state.config.folder_registry {
+        let mut tx = state.pool.begin().await?;
+        let claimed = sqlx::query(
+            "INSERT INTO folder_registry (name, created_by) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING"
+        )
+        .bind(&sanitized_name)
+        .bind(current_user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+        if claimed == 0 {
+            warn!(folder_name = %sanitized_name, "Folder already registered");
+            return Err(AppError::Conflict("Folder already exists"));
+        }
+        claim = Some(tx);
     }
 
     debug!(folder_name = %sanitized_name, "Folder does not exist, proceeding with creation");
@@ -190,9 +312,13 @@ pub async fn create_folder(
                 folder_name = %sanitized_name,
                 "Failed to create folder metadata"
             );
-            AppError::Storage(e)
+            AppError::from(e)
         })?;
 
+    if let Some(tx) = claim {
+        tx.commit().await.map_err(AppError::Db)?;
+    }
+
     info!(
         folder_name = %sanitized_name,
         user_id = %current_user.id,
@@ -208,3 +334,90 @@ pub async fn create_folder(
 
     Ok(Json(response))
 }
+
+/// File a document under an existing folder. Linking is idempotent; a
+/// document can be in several folders.
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/folder",
+    tag = "folders",
+    params(("id" = Uuid, Path, description = "Document ID")),
+    request_body = LinkFolderRequest,
+    responses(
+        (status = 200, description = "Document is in the folder", body = LinkFolderResponse),
+        (status = 400, description = "Invalid folder path"),
+        (status = 404, description = "Document or folder not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn link_document_folder(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(request): Json<LinkFolderRequest>,
+) -> Result<Json<LinkFolderResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let folder = existing_folder(&state, &request.folder).await?;
+
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)",
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+    if !exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let linked = sqlx::query(
+        r#"
+        INSERT INTO document_folders (document_id, folder, linked_by)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (document_id, folder) DO NOTHING
+        "#,
+    )
+    .bind(document_id)
+    .bind(&folder)
+    .bind(current_user.id)
+    .execute(&state.pool)
+    .await
+    .map_err(AppError::Db)?
+    .rows_affected()
+        > 0;
+
+    if linked {
+        if let Err(e) = log_update_metadata(
+            &state,
+            current_user.id.to_string(),
+            document_id,
+            Some(serde_json::json!({ "change": "folder", "folder": folder })),
+        )
+        .await
+        {
+            warn!(
+                error = ?e,
+                document_id = %document_id,
+                user_id = %current_user.id,
+                "Failed to create audit log for folder link"
+            );
+        }
+    }
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        folder = %folder,
+        linked = linked,
+        "Document filed in folder"
+    );
+
+    Ok(Json(LinkFolderResponse {
+        document_id,
+        folder,
+        linked,
+    }))
+}
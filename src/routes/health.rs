@@ -0,0 +1,99 @@
+use crate::dtos::{DependencyCheck, HealthResponse, ReadinessResponse};
+use crate::state::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{routing::get, Json, Router};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Upper bound on each readiness check, so a hung dependency fails the probe
+/// instead of stalling it past the orchestrator's own timeout
+const READY_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+}
+
+/// Liveness probe. Does not touch the database or storage. No auth required.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "Process is alive", body = HealthResponse)
+    )
+)]
+pub async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+/// Readiness probe: pings Postgres and stats the storage root. No auth required.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "system",
+    responses(
+        (status = 200, description = "Database and storage are reachable", body = ReadinessResponse),
+        (status = 503, description = "A dependency is unavailable; see the failing check", body = ReadinessResponse)
+    )
+)]
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let (database, storage) = tokio::join!(
+        run_check("database", async {
+            sqlx::query("SELECT 1")
+                .execute(&state.pool)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }),
+        run_check("storage", async {
+            state
+                .storage
+                .stat("/")
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }),
+    );
+
+    let ok = database.ok && storage.ok;
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ok { "ready" } else { "unavailable" },
+            database,
+            storage,
+        }),
+    )
+}
+
+async fn run_check(
+    name: &'static str,
+    check: impl Future<Output = Result<(), String>>,
+) -> DependencyCheck {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(READY_CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", READY_CHECK_TIMEOUT.as_secs())),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    if let Err(e) = &result {
+        warn!(check = name, error = %e, "Readiness check failed");
+    }
+
+    DependencyCheck {
+        ok: result.is_ok(),
+        latency_ms,
+        error: result.err(),
+    }
+}
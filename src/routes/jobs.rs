@@ -0,0 +1,243 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{JobInfo, ListJobsResponse, MigrateKeyNamespacesRequest, RepairMimeTypesRequest};
+use crate::error::AppError;
+use crate::jobs::JobHandle;
+use crate::mime::OCTET_STREAM;
+use crate::routes::admin::{migrate_namespaces_batch, repair_mime_batch};
+use crate::state::AppState;
+use crate::storage::KeyNamespace;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{routing::{get, post}, Json, Router};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/jobs", get(list_jobs))
+        .route("/admin/jobs/:id/cancel", post(cancel_job))
+        .route("/admin/repair-mime-types", post(start_repair_mime_types))
+        .route("/admin/migrate-key-namespaces", post(start_migrate_key_namespaces))
+}
+
+/// Background admin jobs started since the server came up, newest first
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Running and recently finished jobs", body = ListJobsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<ListJobsResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    Ok(Json(ListJobsResponse {
+        jobs: state.jobs.list(),
+    }))
+}
+
+/// Ask a running job to stop. The job finishes its current item first, so it
+/// may still show as running for a moment after this returns.
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{id}/cancel",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Job ID")),
+    responses(
+        (status = 202, description = "Cancellation requested", body = JobInfo),
+        (status = 409, description = "Job has already finished", body = JobInfo),
+        (status = 404, description = "Unknown job"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, Json<JobInfo>), AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let job = state
+        .jobs
+        .cancel(id)
+        .ok_or(AppError::NotFound("Job not found"))?;
+
+    if !job.cancel_requested {
+        return Ok((StatusCode::CONFLICT, Json(job)));
+    }
+
+    info!(user_id = %current_user.id, job_id = %id, kind = job.kind, "Job cancellation requested");
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Sniff and store the MIME type of every version typed octet-stream or not
+/// at all, in the background, one batch at a time
+#[utoipa::path(
+    post,
+    path = "/admin/repair-mime-types",
+    tag = "admin",
+    request_body = RepairMimeTypesRequest,
+    responses(
+        (status = 202, description = "Job started; poll GET /admin/jobs", body = JobInfo),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn start_repair_mime_types(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<RepairMimeTypesRequest>,
+) -> Result<(StatusCode, Json<JobInfo>), AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let batch_size = request.batch_size.unwrap_or(100).clamp(1, 1000);
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM document_versions WHERE (mime_type IS NULL OR mime_type = $1) AND ($2::uuid IS NULL OR id > $2)",
+    )
+    .bind(OCTET_STREAM)
+    .bind(request.after_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let job = state.jobs.start("repair_mime_types", current_user.id);
+    job.set_total(total.max(0) as u64);
+    let info = started(&state, &job)?;
+
+    tokio::spawn(async move {
+        let result = repair_mime_types_job(&state, &job, request.after_id, batch_size).await;
+        finish(job, result);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(info)))
+}
+
+async fn repair_mime_types_job(
+    state: &AppState,
+    job: &JobHandle,
+    after_id: Option<Uuid>,
+    batch_size: u32,
+) -> Result<(), AppError> {
+    let mut cursor = after_id;
+    let (mut updated, mut unresolved) = (0, 0);
+    while !job.is_cancelled() {
+        let batch = repair_mime_batch(state, cursor, batch_size).await?;
+        job.advance(batch.scanned as u64);
+        updated += batch.updated;
+        unresolved += batch.unresolved;
+        match batch.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    info!(job_id = %job.id, updated, unresolved, "MIME type repair finished");
+    Ok(())
+}
+
+/// Move objects written before KEY_NAMESPACES into their namespaces in the
+/// background: legacy folder markers first, then every document whose
+/// versions still live outside `versions/`, one batch at a time
+#[utoipa::path(
+    post,
+    path = "/admin/migrate-key-namespaces",
+    tag = "admin",
+    request_body = MigrateKeyNamespacesRequest,
+    responses(
+        (status = 202, description = "Job started; poll GET /admin/jobs", body = JobInfo),
+        (status = 400, description = "KEY_NAMESPACES is disabled"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn start_migrate_key_namespaces(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<MigrateKeyNamespacesRequest>,
+) -> Result<(StatusCode, Json<JobInfo>), AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    if !state.config.key_namespaces {
+        return Err(AppError::BadRequest("KEY_NAMESPACES is disabled; nothing to migrate"));
+    }
+
+    let batch_size = request.batch_size.unwrap_or(50).clamp(1, 500);
+    let skip = request.skip.unwrap_or_default();
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT document_id) FROM document_versions WHERE file_path NOT LIKE $1 AND document_id <> ALL($2)",
+    )
+    .bind(format!("{}%", KeyNamespace::Versions.prefix()))
+    .bind(&skip)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let job = state.jobs.start("migrate_key_namespaces", current_user.id);
+    job.set_total(total.max(0) as u64);
+    let info = started(&state, &job)?;
+
+    tokio::spawn(async move {
+        let result = migrate_key_namespaces_job(&state, &job, skip, batch_size).await;
+        finish(job, result);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(info)))
+}
+
+async fn migrate_key_namespaces_job(
+    state: &AppState,
+    job: &JobHandle,
+    mut skip: Vec<Uuid>,
+    batch_size: u32,
+) -> Result<(), AppError> {
+    // Documents that failed are skipped by later batches, so every batch
+    // shrinks the set still to be picked
+    let skipped = skip.len();
+    let (mut folders_moved, mut versions_moved) = (0, 0);
+    while !job.is_cancelled() {
+        let batch = migrate_namespaces_batch(state, batch_size, &skip).await?;
+        job.advance((batch.documents_rekeyed + batch.failed.len()) as u64);
+        folders_moved += batch.folders_moved;
+        versions_moved += batch.versions_moved;
+        let picked_none = batch.documents_rekeyed == 0 && batch.failed.is_empty();
+        skip.extend(batch.failed);
+        if batch.remaining == 0 || picked_none {
+            break;
+        }
+    }
+    info!(job_id = %job.id, folders_moved, versions_moved, "Key namespace migration finished");
+    if skip.len() > skipped {
+        warn!(job_id = %job.id, failed = skip.len() - skipped, "Some documents could not be migrated");
+    }
+    Ok(())
+}
+
+fn started(state: &AppState, job: &JobHandle) -> Result<JobInfo, AppError> {
+    let info = state
+        .jobs
+        .get(job.id)
+        .ok_or(AppError::Other(anyhow::anyhow!("Job vanished from the registry")))?;
+    info!(job_id = %info.id, kind = info.kind, user_id = %info.started_by, "Job started");
+    Ok(info)
+}
+
+fn finish(job: JobHandle, result: Result<(), AppError>) {
+    match &result {
+        Ok(()) if job.is_cancelled() => info!(job_id = %job.id, "Job cancelled"),
+        Ok(()) => info!(job_id = %job.id, "Job completed"),
+        Err(e) => warn!(job_id = %job.id, error = ?e, "Job failed"),
+    }
+    job.finish(result);
+}
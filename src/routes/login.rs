@@ -1,13 +1,21 @@
-use crate::dtos::{LoginRequest, LoginResponse};
+use crate::audit::{log_deferred, log_in_tx};
+use crate::auth::{check_permission, map_user_load_error, StorageAction};
+use crate::dtos::{LoginRequest, LoginResponse, RotateKeyResponse};
 use crate::error::AppError;
-use crate::models::User;
+use crate::models::{AuditAction, NewAuditLog, User};
+use crate::password::{hash_password, is_legacy_plaintext, verify_password};
 use crate::state::AppState;
-use axum::{extract::State, routing::post, Json, Router};
+use crate::auth::CurrentUser;
+use crate::token::issue_token;
+use axum::{extract::{Path, State}, routing::post, Json, Router};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/auth/login", post(login))
+    Router::new()
+        .route("/auth/login", post(login))
+        .route("/auth/rotate-key", post(rotate_own_key))
+        .route("/users/:id/rotate-key", post(rotate_user_key))
 }
 
 #[utoipa::path(
@@ -47,13 +55,12 @@ pub async fn login(
     .bind(request.username.trim())
     .fetch_optional(&state.pool)
     .await
-    .map_err(AppError::Db)?;
+    .map_err(map_user_load_error)?;
 
     match user {
         Some(u) => {
-            // Check if password matches (plain text comparison)
             if let Some(db_password) = &u.password {
-                if db_password != request.password.trim() {
+                if !verify_password(db_password, request.password.trim()) {
                     warn!(username = %request.username, "Invalid password");
                     return Err(AppError::BadRequest("Invalid username or password"));
                 }
@@ -63,6 +70,11 @@ pub async fn login(
                 return Err(AppError::BadRequest("Invalid username or password"));
             }
 
+            // Upgrade plain-text passwords from before hashing on first good login
+            if u.password.as_deref().is_some_and(is_legacy_plaintext) {
+                upgrade_legacy_password(&state, &u, request.password.trim()).await;
+            }
+
             info!(
                 user_id = %u.id,
                 username = %u.username,
@@ -70,11 +82,29 @@ pub async fn login(
                 "User logged in successfully"
             );
 
+            let (access_token, access_token_expires_at) = match &state.config.jwt_secret {
+                Some(secret) => {
+                    let now = chrono::Utc::now();
+                    let expires_at = now + chrono::Duration::seconds(state.config.jwt_ttl_secs);
+                    let claims_user = CurrentUser {
+                        id: u.id,
+                        username: u.username.clone(),
+                        role: u.role,
+                        permissions: state.config.permission_overrides.clone(),
+                    };
+                    let token = issue_token(secret, &claims_user, now.timestamp(), expires_at.timestamp())?;
+                    (Some(token), Some(expires_at))
+                }
+                None => (None, None),
+            };
+
             Ok(Json(LoginResponse {
                 api_key: u.api_key,
                 username: u.username,
                 user_id: u.id,
                 role: u.role,
+                access_token,
+                access_token_expires_at,
             }))
         }
         None => {
@@ -83,3 +113,123 @@ pub async fn login(
         }
     }
 }
+
+/// Replace a legacy plain-text password with its hash. Failures are logged
+/// only; the user can still log in and the upgrade is retried next time.
+async fn upgrade_legacy_password(state: &AppState, user: &User, password: &str) {
+    let hashed = match hash_password(password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            warn!(error = ?e, user_id = %user.id, "Failed to hash legacy password");
+            return;
+        }
+    };
+
+    // Only overwrite the value we verified against, in case it changed meanwhile
+    let result = sqlx::query("UPDATE users SET password = $2 WHERE id = $1 AND password = $3")
+        .bind(user.id)
+        .bind(&hashed)
+        .bind(&user.password)
+        .execute(&state.pool)
+        .await;
+
+    match result {
+        Ok(_) => info!(user_id = %user.id, "Upgraded legacy plain-text password to argon2"),
+        Err(e) => warn!(error = ?e, user_id = %user.id, "Failed to store upgraded password hash"),
+    }
+}
+
+/// Replace the caller's API key. The old key stops working immediately;
+/// access tokens already issued stay valid until they expire.
+#[utoipa::path(
+    post,
+    path = "/auth/rotate-key",
+    tag = "auth",
+    responses(
+        (status = 200, description = "API key rotated", body = RotateKeyResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn rotate_own_key(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<RotateKeyResponse>, AppError> {
+    rotate_api_key(&state, &current_user, current_user.id).await.map(Json)
+}
+
+/// Replace another user's API key, e.g. after it leaked
+#[utoipa::path(
+    post,
+    path = "/users/{id}/rotate-key",
+    tag = "auth",
+    params(
+        ("id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "API key rotated", body = RotateKeyResponse),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn rotate_user_key(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<RotateKeyResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+    rotate_api_key(&state, &current_user, user_id).await.map(Json)
+}
+
+/// Store a fresh key for `user_id` and audit who rotated it
+async fn rotate_api_key(
+    state: &AppState,
+    actor: &CurrentUser,
+    user_id: Uuid,
+) -> Result<RotateKeyResponse, AppError> {
+    let new_key = Uuid::new_v4().to_string();
+
+    let mut tx = state.pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users SET api_key = $2
+        WHERE id = $1
+        RETURNING id, username, api_key, password, role, created_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(&new_key)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("User not found"))?;
+
+    let deferred_audit = log_in_tx(
+        state,
+        &mut tx,
+        NewAuditLog {
+            user_id: actor.id.to_string(),
+            action: AuditAction::RotateApiKey,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({ "target_user_id": user_id }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(state, deferred_audit).await;
+
+    info!(actor_id = %actor.id, user_id = %user.id, "API key rotated");
+
+    Ok(RotateKeyResponse {
+        api_key: user.api_key,
+        username: user.username,
+        user_id: user.id,
+        role: user.role,
+    })
+}
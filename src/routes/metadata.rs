@@ -0,0 +1,503 @@
+use crate::audit::{log_deferred, log_in_tx};
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::db::TimedQuery;
+use crate::dtos::{
+    BulkMetadataRequest, BulkMetadataResponse, BulkMetadataResult, BulkMetadataStatus,
+    DocumentMetadataResponse, MetadataKeyUsage, MetadataKeysQuery, MetadataKeysResponse,
+};
+use crate::error::AppError;
+use crate::models::{AuditAction, NewAuditLog};
+use crate::response::capped_json;
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{routing::{delete, get, post}, Json, Router};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Matches `document_metadata.key VARCHAR(255)`
+pub const METADATA_KEY_MAX_LEN: usize = 255;
+
+/// Largest metadata value accepted per key
+pub const METADATA_VALUE_MAX_BYTES: usize = 8 * 1024;
+
+/// Most documents a single bulk request may touch
+const BULK_MAX_DOCUMENTS: usize = 500;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/metadata/bulk", post(bulk_upsert_metadata))
+        .route("/metadata/keys", get(list_metadata_keys))
+        .route(
+            "/documents/:id/metadata",
+            get(get_document_metadata).patch(update_document_metadata),
+        )
+        .route("/documents/:id/metadata/:key", delete(delete_document_metadata_key))
+}
+
+/// Query behind `GET /documents/{id}/metadata`: $1 document id. Shared with `/admin/explain`.
+/// `value` is nullable; a NULL reads as an empty string, as in the listing's `include=metadata`.
+pub(crate) const DOCUMENT_METADATA_SQL: &str =
+    "SELECT key, COALESCE(value, '') FROM document_metadata WHERE document_id = $1";
+
+/// Reject metadata entries that would not fit the `document_metadata` table
+pub fn validate_metadata(metadata: &HashMap<String, String>) -> Result<(), AppError> {
+    for (key, value) in metadata {
+        if key.trim().is_empty() {
+            return Err(AppError::BadRequest("Metadata keys cannot be empty"));
+        }
+        if key.chars().count() > METADATA_KEY_MAX_LEN {
+            return Err(AppError::BadRequest("Metadata key exceeds 255 characters"));
+        }
+        if value.len() > METADATA_VALUE_MAX_BYTES {
+            return Err(AppError::BadRequest("Metadata value exceeds 8 KiB"));
+        }
+    }
+    Ok(())
+}
+
+/// All metadata of a document; a document without metadata gets an empty map
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/metadata",
+    tag = "metadata",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Metadata of the document", body = DocumentMetadataResponse),
+        (status = 404, description = "Document not found or has been deleted"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_document_metadata(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<DocumentMetadataResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.read_pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let rows: Vec<(String, String)> = sqlx::query_as(DOCUMENT_METADATA_SQL)
+    .bind(document_id)
+    .fetch_all(&state.read_pool)
+    .timed("metadata.get_document", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    Ok(Json(DocumentMetadataResponse {
+        document_id,
+        metadata: rows.into_iter().collect(),
+    }))
+}
+
+/// Upsert metadata entries on one document after upload. Keys not in the
+/// request are left untouched.
+#[utoipa::path(
+    patch,
+    path = "/documents/{id}/metadata",
+    tag = "metadata",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body(content = HashMap<String, String>, description = "Metadata keys and values to set"),
+    responses(
+        (status = 200, description = "Full metadata of the document after the update", body = DocumentMetadataResponse),
+        (status = 400, description = "Bad request - empty or invalid metadata"),
+        (status = 404, description = "Document not found or has been deleted"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn update_document_metadata(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(metadata): Json<HashMap<String, String>>,
+) -> Result<Json<DocumentMetadataResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    if metadata.is_empty() {
+        return Err(AppError::BadRequest("metadata cannot be empty"));
+    }
+    validate_metadata(&metadata)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let live: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM documents WHERE id = $1 AND deleted_at IS NULL FOR UPDATE"
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if live.is_none() {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    for (key, value) in &metadata {
+        sqlx::query(
+            r#"
+            INSERT INTO document_metadata (document_id, key, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (document_id, key)
+            DO UPDATE SET value = EXCLUDED.value
+            "#,
+        )
+        .bind(document_id)
+        .bind(key)
+        .bind(value)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| {
+            warn!(error = ?err, document_id = %document_id, meta_key = %key, "Failed to upsert metadata");
+            AppError::Db(err)
+        })?;
+    }
+
+    let rows: Vec<(String, String)> = sqlx::query_as(DOCUMENT_METADATA_SQL)
+    .bind(document_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut keys: Vec<&String> = metadata.keys().collect();
+    keys.sort();
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::UpdateMetadata,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "change": "metadata",
+                "keys": &keys,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(|err| {
+        warn!(error = ?err, "Failed to commit metadata update");
+        AppError::Db(err)
+    })?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        keys = keys.len(),
+        "Document metadata updated"
+    );
+
+    Ok(Json(DocumentMetadataResponse {
+        document_id,
+        metadata: rows.into_iter().collect::<BTreeMap<_, _>>(),
+    }))
+}
+
+/// Remove a single metadata entry from a document
+#[utoipa::path(
+    delete,
+    path = "/documents/{id}/metadata/{key}",
+    tag = "metadata",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("key" = String, Path, description = "Metadata key to remove")
+    ),
+    responses(
+        (status = 200, description = "Remaining metadata of the document", body = DocumentMetadataResponse),
+        (status = 404, description = "Document not found or has no such key"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn delete_document_metadata_key(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path((document_id, key)): Path<(Uuid, String)>,
+) -> Result<Json<DocumentMetadataResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let live: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM documents WHERE id = $1 AND deleted_at IS NULL FOR UPDATE"
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if live.is_none() {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let removed = sqlx::query("DELETE FROM document_metadata WHERE document_id = $1 AND key = $2")
+        .bind(document_id)
+        .bind(&key)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+    if removed == 0 {
+        return Err(AppError::NotFound("Metadata key not found on this document"));
+    }
+
+    let rows: Vec<(String, String)> = sqlx::query_as(DOCUMENT_METADATA_SQL)
+    .bind(document_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::UpdateMetadata,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "change": "metadata",
+                "removed_key": &key,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(|err| {
+        warn!(error = ?err, "Failed to commit metadata removal");
+        AppError::Db(err)
+    })?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        meta_key = %key,
+        "Document metadata key removed"
+    );
+
+    Ok(Json(DocumentMetadataResponse {
+        document_id,
+        metadata: rows.into_iter().collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/metadata/bulk",
+    tag = "metadata",
+    request_body = BulkMetadataRequest,
+    responses(
+        (status = 200, description = "Metadata applied to every listed document", body = BulkMetadataResponse),
+        (status = 207, description = "Some documents were skipped; see per-document statuses", body = BulkMetadataResponse),
+        (status = 400, description = "Bad request - empty lists or invalid metadata"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn bulk_upsert_metadata(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<BulkMetadataRequest>,
+) -> Result<(StatusCode, Json<BulkMetadataResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    if request.document_ids.is_empty() {
+        return Err(AppError::BadRequest("document_ids cannot be empty"));
+    }
+    if request.document_ids.len() > BULK_MAX_DOCUMENTS {
+        return Err(AppError::BadRequest("Too many document_ids (max 500)"));
+    }
+    if request.metadata.is_empty() {
+        return Err(AppError::BadRequest("metadata cannot be empty"));
+    }
+    validate_metadata(&request.metadata)?;
+
+    // Keep the caller's order but don't process a document twice
+    let mut document_ids: Vec<Uuid> = Vec::with_capacity(request.document_ids.len());
+    for id in request.document_ids {
+        if !document_ids.contains(&id) {
+            document_ids.push(id);
+        }
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    // Lock live documents so a concurrent delete can't slip in mid-batch
+    let live: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM documents WHERE id = ANY($1) AND deleted_at IS NULL FOR UPDATE"
+    )
+    .bind(&document_ids)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let mut keys: Vec<&String> = request.metadata.keys().collect();
+    keys.sort();
+
+    let mut results = Vec::with_capacity(document_ids.len());
+    let mut deferred_audits = Vec::new();
+
+    for document_id in &document_ids {
+        if !live.contains(document_id) {
+            results.push(BulkMetadataResult {
+                document_id: *document_id,
+                status: BulkMetadataStatus::NotFound,
+            });
+            continue;
+        }
+
+        for (key, value) in &request.metadata {
+            sqlx::query(
+                r#"
+                INSERT INTO document_metadata (document_id, key, value)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (document_id, key)
+                DO UPDATE SET value = EXCLUDED.value
+                "#,
+            )
+            .bind(document_id)
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| {
+                warn!(error = ?err, document_id = %document_id, meta_key = %key, "Failed to upsert metadata");
+                AppError::Db(err)
+            })?;
+        }
+
+        let deferred_audit = log_in_tx(
+            &state,
+            &mut tx,
+            NewAuditLog {
+                user_id: current_user.id.to_string(),
+                action: AuditAction::UpdateMetadata,
+                document_id: Some(*document_id),
+                document_version: None,
+                metadata: serde_json::json!({
+                    "change": "metadata",
+                    "bulk": true,
+                    "keys": &keys,
+                }),
+            },
+        )
+        .await?;
+        deferred_audits.push(deferred_audit);
+
+        results.push(BulkMetadataResult {
+            document_id: *document_id,
+            status: BulkMetadataStatus::Applied,
+        });
+    }
+
+    tx.commit().await.map_err(|err| {
+        warn!(error = ?err, "Failed to commit bulk metadata transaction");
+        AppError::Db(err)
+    })?;
+
+    for deferred_audit in deferred_audits {
+        log_deferred(&state, deferred_audit).await;
+    }
+
+    let applied = results
+        .iter()
+        .filter(|r| r.status == BulkMetadataStatus::Applied)
+        .count();
+    let skipped = results.len() - applied;
+
+    info!(
+        user_id = %current_user.id,
+        applied = applied,
+        skipped = skipped,
+        keys = keys.len(),
+        "Bulk metadata applied"
+    );
+
+    let status_code = if skipped > 0 { StatusCode::MULTI_STATUS } else { StatusCode::OK };
+
+    Ok((status_code, Json(BulkMetadataResponse { results, applied, skipped })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metadata/keys",
+    tag = "metadata",
+    params(
+        ("prefix" = Option<String>, Query, description = "Only keys starting with this prefix")
+    ),
+    responses(
+        (status = 200, description = "Metadata keys with the number of documents using each", body = MetadataKeysResponse),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_metadata_keys(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<MetadataKeysQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    // Escape LIKE wildcards so the prefix is matched literally
+    let prefix = query
+        .prefix
+        .unwrap_or_default()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    let keys = sqlx::query_as::<_, MetadataKeyUsage>(
+        r#"
+        SELECT m.key, COUNT(DISTINCT m.document_id) AS document_count
+        FROM document_metadata m
+        JOIN documents d ON d.id = m.document_id
+        WHERE d.deleted_at IS NULL
+          AND m.key LIKE $1 || '%'
+        GROUP BY m.key
+        ORDER BY document_count DESC, m.key
+        "#,
+    )
+    .bind(&prefix)
+    .fetch_all(&state.read_pool)
+    .timed("list_metadata_keys", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    info!(keys = keys.len(), "Metadata keys listed");
+
+    let response = MetadataKeysResponse {
+        total: keys.len(),
+        keys,
+    };
+
+    capped_json("metadata.list_keys", state.config.max_response_bytes, &response)
+}
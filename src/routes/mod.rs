@@ -1,36 +1,91 @@
 use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
 use crate::state::AppState;
 use tower_http::trace::TraceLayer;
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::normalize_path::NormalizePath;
+use axum::http::{HeaderName, Method};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::request_id::{self, RequestId, REQUEST_ID_HEADER};
 use utoipa_swagger_ui::SwaggerUi;
-use utoipa::OpenApi;
 
 pub mod upload;
+pub mod resumable_upload;
 pub mod documents;
 pub mod audit;
 pub mod folders;
 pub mod tags;
 pub mod login;
+pub mod watches;
+pub mod admin;
+pub mod aliases;
+pub mod visibility;
+pub mod metadata;
+pub mod backup;
+pub mod signed_links;
+pub mod version;
+pub mod facets;
+pub mod search;
+pub mod templates;
+pub mod archive;
+pub mod stats;
+pub mod health;
+pub mod jobs;
+pub mod users;
 
 use crate::openapi::openapi_with_security; 
 
 pub fn router(state: AppState) -> Router {                                                                                      
-    Router::new()
+    let mut router = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-doc/openapi.json", openapi_with_security()))
         .merge(upload::routes())
+        .merge(resumable_upload::routes())
         .merge(documents::routes())
         .merge(audit::routes())
         .merge(folders::routes())                                                                                                                                                                           
         .merge(tags::routes())
         .merge(login::routes())
-        .layer(CorsLayer::permissive()) // Allow CORS for frontend development , allow requests from UI
+        .merge(watches::routes())
+        .merge(admin::routes())
+        .merge(aliases::routes())
+        .merge(visibility::routes())
+        .merge(metadata::routes())
+        .merge(backup::routes())
+        .merge(signed_links::routes())
+        .merge(version::routes())
+        .merge(facets::routes())
+        .merge(search::routes())
+        .merge(templates::routes())
+        .merge(archive::routes())
+        .merge(stats::routes())
+        .merge(health::routes())
+        .merge(jobs::routes())
+        .merge(users::routes())
+        // Set before the layers so unmatched routes get them too
+        .fallback(not_found)
+        .layer(middleware::from_fn_with_state(state.clone(), security_headers));
+    if let Some(cors) = cors_layer(&state.config) {
+        router = router.layer(cors);
+    }
+
+    router
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|request: &axum::http::Request<_>| {
+                    let request_id = request
+                        .extensions()
+                        .get::<RequestId>()
+                        .map(|id| id.0.as_str())
+                        .unwrap_or_default();
                     tracing::info_span!(
                         "http_request",
                         method = %request.method(),
                         uri = %request.uri(),
+                        request_id = %request_id,
                     )
                 })
                 .on_request(|_request: &axum::http::Request<_>, _span: &tracing::Span| {
@@ -44,5 +99,136 @@ pub fn router(state: AppState) -> Router {
                     );
                 })
         )
+        .layer(middleware::from_fn(request_id::propagate))
         .with_state(state)
-}
\ No newline at end of file
+}
+
+/// Serve `/documents/` as `/documents` (TRIM_TRAILING_SLASH). Routing happens
+/// inside the Router, so the path must be normalized by a layer wrapped
+/// around it rather than one added with `Router::layer`.
+pub fn trim_trailing_slash(router: Router) -> NormalizePath<Router> {
+    NormalizePath::trim_trailing_slash(router)
+}
+
+/// JSON 404 for paths no route matches
+async fn not_found() -> AppError {
+    AppError::NotFound("No such route")
+}
+
+/// CORS for browser clients, limited to CORS_ALLOWED_ORIGINS. Preflight
+/// requests are answered here, before authentication runs. Without any
+/// configured origin there is no CORS layer at all.
+fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    let origins = &config.cors_allowed_origins;
+    if origins.is_empty() {
+        return None;
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let parsed: Vec<HeaderValue> = origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    tracing::warn!(origin = %origin, "Ignoring invalid CORS origin");
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(parsed)
+    };
+
+    let layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::RANGE,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            HeaderName::from_static("x-api-key"),
+            HeaderName::from_static("x-expected-checksum"),
+            REQUEST_ID_HEADER,
+        ])
+        // Let scripts read the headers the API reports results in
+        .expose_headers([
+            header::CONTENT_DISPOSITION,
+            header::CONTENT_RANGE,
+            header::ETAG,
+            header::LAST_MODIFIED,
+            header::WARNING,
+            HeaderName::from_static("x-job-id"),
+            HeaderName::from_static("x-storage-usage-pct"),
+            REQUEST_ID_HEADER,
+        ])
+        .max_age(std::time::Duration::from_secs(600));
+    Some(layer)
+}
+
+/// Adds hardening headers to every response and, when FORCE_HTTPS is set,
+/// redirects requests that a proxy reports as plain HTTP to PUBLIC_HOST.
+async fn security_headers(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = &state.config;
+
+    if config.force_https {
+        let forwarded_proto = request
+            .headers()
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok());
+
+        if forwarded_proto.is_some_and(|proto| proto.eq_ignore_ascii_case("http")) {
+            // Redirecting to the client's own Host header would make this an
+            // open redirect, so only the configured canonical host is used
+            let Some(host) = config.public_host.as_deref() else {
+                tracing::debug!("Refusing plain HTTP request; no PUBLIC_HOST to redirect to");
+                return (StatusCode::FORBIDDEN, "HTTPS is required").into_response();
+            };
+            let path = request
+                .uri()
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or("/");
+            let location = format!("https://{}{}", host, path);
+
+            tracing::debug!(location = %location, "Redirecting plain HTTP request to HTTPS");
+            return (StatusCode::PERMANENT_REDIRECT, [(header::LOCATION, location)]).into_response();
+        }
+    }
+
+    let mut response = next.run(request).await;
+
+    if !config.security_headers {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if let Ok(value) = HeaderValue::from_str(&config.frame_options) {
+        headers.insert(header::X_FRAME_OPTIONS, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&config.referrer_policy) {
+        headers.insert(header::REFERRER_POLICY, value);
+    }
+    if config.hsts_max_age_secs > 0 {
+        if let Ok(value) = HeaderValue::from_str(&format!(
+            "max-age={}; includeSubDomains",
+            config.hsts_max_age_secs
+        )) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, value);
+        }
+    }
+
+    response
+}
@@ -0,0 +1,562 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{ExpiredUploadsResponse, InitUploadRequest, UploadResponse, UploadSessionResponse};
+use crate::error::AppError;
+use crate::routes::metadata::validate_metadata;
+use crate::routes::upload::{store_upload, UploadContent, UploadInput};
+use crate::state::AppState;
+use crate::storage::KeyNamespace;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::{routing::{get, post, put}, Json, Router};
+use chrono::{DateTime, Utc};
+use sqlx::types::Json as SqlJson;
+use sqlx::{FromRow, PgExecutor};
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Largest body accepted by a single chunk PUT
+const MAX_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+
+/// Chunk numbers run from 0 to MAX_CHUNKS - 1
+const MAX_CHUNKS: i32 = 10_000;
+
+/// Largest file a resumable upload may assemble to
+const MAX_UPLOAD_BYTES: i64 = 16 * 1024 * 1024 * 1024;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/uploads/init", post(init_upload))
+        .route("/uploads/:id", get(get_upload).delete(abort_upload))
+        .route(
+            "/uploads/:id/chunk/:n",
+            put(put_chunk).layer(DefaultBodyLimit::max(MAX_CHUNK_BYTES)),
+        )
+        .route("/uploads/:id/complete", post(complete_upload))
+        .route("/admin/uploads/expire", post(expire_uploads))
+}
+
+#[derive(FromRow)]
+struct UploadSession {
+    id: Uuid,
+    document_id: Option<Uuid>,
+    title: Option<String>,
+    category: Option<String>,
+    file_name: Option<String>,
+    mime_type: Option<String>,
+    metadata: SqlJson<HashMap<String, String>>,
+    completing: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Session columns, with `completing` true while a claim younger than the
+/// timeout bound as `claim_timeout`, in seconds, holds the session
+fn session_columns(claim_timeout: &str) -> String {
+    format!(
+        "id, document_id, title, category, file_name, mime_type, metadata, \
+         {} AS completing, created_at",
+        claim_held(claim_timeout)
+    )
+}
+
+fn chunk_prefix(upload_id: Uuid) -> String {
+    format!("{}{}/", KeyNamespace::Uploads.prefix(), upload_id)
+}
+
+fn chunk_key(upload_id: Uuid, chunk_number: i32) -> String {
+    format!("{}{:06}", chunk_prefix(upload_id), chunk_number)
+}
+
+/// Condition that session `s` has seen a chunk (or was opened) within the
+/// TTL bound as `ttl`, in seconds; a TTL of 0 never expires
+fn session_live(ttl: &str) -> String {
+    format!(
+        "({ttl} = 0 OR GREATEST(s.created_at, \
+         (SELECT MAX(c.created_at) FROM upload_chunks c WHERE c.upload_id = s.id)) \
+         > NOW() - make_interval(secs => {ttl}))"
+    )
+}
+
+/// Condition that a `POST /uploads/{id}/complete` claim younger than the
+/// timeout bound as `claim_timeout`, in seconds, holds the session; older
+/// claims were left behind by a crash and no longer count
+fn claim_held(claim_timeout: &str) -> String {
+    format!("COALESCE(completing_since > NOW() - make_interval(secs => {claim_timeout}), FALSE)")
+}
+
+/// Sessions are private to the user who opened them; anyone else gets a 404,
+/// as does a session idle past UPLOAD_SESSION_TTL_SECS
+async fn load_session(
+    state: &AppState,
+    current_user: &CurrentUser,
+    upload_id: Uuid,
+) -> Result<UploadSession, AppError> {
+    fetch_session(&state.pool, state, current_user, upload_id, "").await
+}
+
+/// [`load_session`] with a row-lock clause such as `FOR SHARE` appended
+async fn fetch_session<'e, E: PgExecutor<'e>>(
+    executor: E,
+    state: &AppState,
+    current_user: &CurrentUser,
+    upload_id: Uuid,
+    lock: &str,
+) -> Result<UploadSession, AppError> {
+    sqlx::query_as::<_, UploadSession>(&format!(
+        "SELECT {} FROM upload_sessions s \
+         WHERE id = $1 AND created_by = $2 AND {} {lock}",
+        session_columns("$4"),
+        session_live("$3")
+    ))
+    .bind(upload_id)
+    .bind(current_user.id)
+    .bind(state.config.upload_session_ttl_secs as f64)
+    .bind(state.config.upload_claim_timeout_secs as f64)
+    .fetch_optional(executor)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Upload not found"))
+}
+
+async fn session_progress(
+    state: &AppState,
+    session: UploadSession,
+) -> Result<UploadSessionResponse, AppError> {
+    let chunks: Vec<(i32, i64)> = sqlx::query_as(
+        "SELECT chunk_number, size FROM upload_chunks WHERE upload_id = $1 ORDER BY chunk_number",
+    )
+    .bind(session.id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    Ok(UploadSessionResponse {
+        upload_id: session.id,
+        document_id: session.document_id,
+        title: session.title,
+        file_name: session.file_name,
+        received_bytes: chunks.iter().map(|(_, size)| size).sum(),
+        received_chunks: chunks.into_iter().map(|(n, _)| n).collect(),
+        max_chunk_bytes: MAX_CHUNK_BYTES,
+        created_at: session.created_at,
+    })
+}
+
+/// Open a resumable upload. Send the content with `PUT /uploads/{id}/chunk/{n}`
+/// in any order, then `POST /uploads/{id}/complete`.
+#[utoipa::path(
+    post,
+    path = "/uploads/init",
+    tag = "upload",
+    request_body = InitUploadRequest,
+    responses(
+        (status = 201, description = "Upload opened", body = UploadSessionResponse),
+        (status = 400, description = "Missing title, unknown document_id or invalid metadata"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn init_upload(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<InitUploadRequest>,
+) -> Result<(StatusCode, Json<UploadSessionResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    validate_metadata(&request.metadata)?;
+
+    // Fail now rather than after the client has sent every chunk
+    match request.document_id {
+        Some(document_id) => {
+            let exists: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)")
+                    .bind(document_id)
+                    .fetch_one(&state.pool)
+                    .await
+                    .map_err(AppError::Db)?;
+            if !exists {
+                return Err(AppError::BadRequest("document_id not found"));
+            }
+        }
+        None => {
+            if request.title.as_deref().map(str::trim).unwrap_or("").is_empty() {
+                return Err(AppError::BadRequest("Missing title"));
+            }
+        }
+    }
+
+    let session = sqlx::query_as::<_, UploadSession>(&format!(
+        r#"
+        INSERT INTO upload_sessions (created_by, document_id, title, category, file_name, mime_type, metadata)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {columns}
+        "#,
+        columns = session_columns("$8")
+    ))
+    .bind(current_user.id)
+    .bind(request.document_id)
+    .bind(&request.title)
+    .bind(&request.category)
+    .bind(&request.file_name)
+    .bind(&request.mime_type)
+    .bind(SqlJson(&request.metadata))
+    .bind(state.config.upload_claim_timeout_secs as f64)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    info!(upload_id = %session.id, user_id = %current_user.id, document_id = ?session.document_id, "Resumable upload opened");
+
+    let progress = session_progress(&state, session).await?;
+    Ok((StatusCode::CREATED, Json(progress)))
+}
+
+/// Chunks received so far, for resuming an interrupted upload
+#[utoipa::path(
+    get,
+    path = "/uploads/{id}",
+    tag = "upload",
+    params(("id" = Uuid, Path, description = "Upload ID")),
+    responses(
+        (status = 200, description = "Upload progress", body = UploadSessionResponse),
+        (status = 404, description = "Upload not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_upload(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Json<UploadSessionResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let session = load_session(&state, &current_user, upload_id).await?;
+    Ok(Json(session_progress(&state, session).await?))
+}
+
+/// Store one chunk of the content. Sending a chunk number again replaces it,
+/// so a chunk whose response was lost can simply be retried.
+#[utoipa::path(
+    put,
+    path = "/uploads/{id}/chunk/{n}",
+    tag = "upload",
+    params(
+        ("id" = Uuid, Path, description = "Upload ID"),
+        ("n" = i32, Path, description = "Chunk number, starting at 0")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream", description = "Raw chunk bytes, at most 64 MiB"),
+    responses(
+        (status = 200, description = "Chunk stored", body = UploadSessionResponse),
+        (status = 400, description = "Empty chunk or chunk number out of range"),
+        (status = 404, description = "Upload not found"),
+        (status = 409, description = "Upload is being completed"),
+        (status = 413, description = "Chunk larger than 64 MiB, or the upload would exceed 16 GiB"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn put_chunk(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path((upload_id, chunk_number)): Path<(Uuid, i32)>,
+    body: Bytes,
+) -> Result<Json<UploadSessionResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    if !(0..MAX_CHUNKS).contains(&chunk_number) {
+        return Err(AppError::BadRequest("Chunk number must be between 0 and 9999"));
+    }
+    if body.is_empty() {
+        return Err(AppError::BadRequest("Chunk is empty"));
+    }
+
+    // The share lock is held until the chunk is recorded, so `complete`
+    // (which takes the row for update) waits for chunks already in flight,
+    // and chunks arriving after it claimed the session see `completing`
+    let mut tx = state.pool.begin().await.map_err(AppError::Db)?;
+    let session = fetch_session(&mut *tx, &state, &current_user, upload_id, "FOR SHARE").await?;
+    if session.completing {
+        return Err(AppError::Conflict("Upload is being completed"));
+    }
+
+    let size = body.len() as i64;
+    let others: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(size), 0)::BIGINT FROM upload_chunks WHERE upload_id = $1 AND chunk_number <> $2",
+    )
+    .bind(upload_id)
+    .bind(chunk_number)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+    if others + size > MAX_UPLOAD_BYTES {
+        return Err(AppError::PayloadTooLarge("Upload exceeds the 16 GiB limit"));
+    }
+
+    state
+        .storage
+        .write(&chunk_key(upload_id, chunk_number), body)
+        .await?;
+
+    let recorded = sqlx::query(&format!(
+        r#"
+        INSERT INTO upload_chunks (upload_id, chunk_number, size)
+        SELECT $1, $2, $3
+        WHERE EXISTS (SELECT 1 FROM upload_sessions WHERE id = $1 AND NOT {held})
+        ON CONFLICT (upload_id, chunk_number)
+        DO UPDATE SET size = EXCLUDED.size, created_at = NOW()
+        "#,
+        held = claim_held("$4")
+    ))
+    .bind(upload_id)
+    .bind(chunk_number)
+    .bind(size)
+    .bind(state.config.upload_claim_timeout_secs as f64)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .rows_affected();
+    if recorded == 0 {
+        return Err(AppError::Conflict("Upload is being completed"));
+    }
+    tx.commit().await.map_err(AppError::Db)?;
+
+    debug!(upload_id = %upload_id, chunk = chunk_number, size, "Stored upload chunk");
+
+    Ok(Json(session_progress(&state, session).await?))
+}
+
+/// Join the chunks in order and store the result exactly like `POST /upload`.
+/// Chunks must be numbered 0..n with no gaps and add up to at most 16 GiB.
+/// They are streamed into the version object one after another.
+#[utoipa::path(
+    post,
+    path = "/uploads/{id}/complete",
+    tag = "upload",
+    params(("id" = Uuid, Path, description = "Upload ID")),
+    responses(
+        (status = 200, description = "Upload stored; headers as for POST /upload", body = UploadResponse),
+        (status = 400, description = "No chunks, or chunks missing from the sequence"),
+        (status = 404, description = "Upload not found"),
+        (status = 409, description = "Upload is already being completed, or matches an existing version"),
+        (status = 413, description = "Chunks add up to more than 16 GiB"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn complete_upload(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(upload_id): Path<Uuid>,
+) -> Result<(HeaderMap, Json<UploadResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    // Claim the session so a second complete can't race this one. The update
+    // waits for chunk PUTs holding the session's share lock, and any chunk
+    // after it is refused, so no chunk object changes while it is assembled.
+    // A stale claim, left by a crash mid-assembly, is taken over.
+    let claimed = sqlx::query_as::<_, UploadSession>(&format!(
+        r#"
+        UPDATE upload_sessions s SET completing_since = NOW()
+        WHERE id = $1 AND created_by = $2 AND NOT {held} AND {live}
+        RETURNING {columns}
+        "#,
+        held = claim_held("$4"),
+        live = session_live("$3"),
+        columns = session_columns("$4")
+    ))
+    .bind(upload_id)
+    .bind(current_user.id)
+    .bind(state.config.upload_session_ttl_secs as f64)
+    .bind(state.config.upload_claim_timeout_secs as f64)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let session = match claimed {
+        Some(session) => session,
+        None => {
+            load_session(&state, &current_user, upload_id).await?;
+            return Err(AppError::Conflict("Upload is already being completed"));
+        }
+    };
+
+    let (headers, response) = match assemble_and_store(&state, &current_user, session).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            // Leave the chunks in place so the client can fix the problem
+            // and complete again
+            if let Err(release) = sqlx::query("UPDATE upload_sessions SET completing_since = NULL WHERE id = $1")
+                .bind(upload_id)
+                .execute(&state.pool)
+                .await
+            {
+                warn!(error = ?release, upload_id = %upload_id, "Failed to release upload session");
+            }
+            return Err(e);
+        }
+    };
+
+    discard_session(&state, upload_id).await;
+
+    info!(
+        upload_id = %upload_id,
+        document_id = %response.document_id,
+        version_id = %response.version_id,
+        "Resumable upload completed"
+    );
+
+    Ok((headers, Json(response)))
+}
+
+async fn assemble_and_store(
+    state: &AppState,
+    current_user: &CurrentUser,
+    session: UploadSession,
+) -> Result<(HeaderMap, UploadResponse), AppError> {
+    let chunks: Vec<(i32, i64)> = sqlx::query_as(
+        "SELECT chunk_number, size FROM upload_chunks WHERE upload_id = $1 ORDER BY chunk_number",
+    )
+    .bind(session.id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if chunks.is_empty() {
+        return Err(AppError::BadRequest("No chunks uploaded"));
+    }
+    if chunks.iter().zip(0..).any(|((n, _), expected)| *n != expected) {
+        warn!(upload_id = %session.id, received = ?chunks, "Resumable upload has gaps");
+        return Err(AppError::BadRequest("Chunks are missing; see GET /uploads/{id}"));
+    }
+    // Chunk PUTs check this too, but concurrent ones can overshoot it together
+    let total: i64 = chunks.iter().map(|(_, size)| size).sum();
+    if total > MAX_UPLOAD_BYTES {
+        warn!(upload_id = %session.id, total, "Resumable upload exceeds the size limit");
+        return Err(AppError::PayloadTooLarge("Upload exceeds the 16 GiB limit"));
+    }
+
+    let chunk_keys = chunks.iter().map(|(n, _)| chunk_key(session.id, *n)).collect();
+
+    let metadata = session.metadata.0;
+    let mut metadata_keys: Vec<String> = metadata.keys().cloned().collect();
+    metadata_keys.sort();
+
+    store_upload(
+        state,
+        current_user,
+        UploadInput {
+            document_id: session.document_id,
+            title: session.title,
+            category: session.category,
+            file_name: session.file_name,
+            declared_mime_type: session.mime_type,
+            content: UploadContent::Objects(chunk_keys),
+            metadata,
+            metadata_keys,
+        },
+    )
+    .await
+}
+
+/// Abandon an upload and delete its chunks
+#[utoipa::path(
+    delete,
+    path = "/uploads/{id}",
+    tag = "upload",
+    params(("id" = Uuid, Path, description = "Upload ID")),
+    responses(
+        (status = 204, description = "Upload discarded"),
+        (status = 404, description = "Upload not found"),
+        (status = 409, description = "Upload is being completed"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn abort_upload(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(upload_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let session = load_session(&state, &current_user, upload_id).await?;
+    if session.completing {
+        return Err(AppError::Conflict("Upload is being completed"));
+    }
+
+    discard_session(&state, upload_id).await;
+
+    info!(upload_id = %upload_id, user_id = %current_user.id, "Resumable upload aborted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Drop the session row, then its chunks. Leftover chunk objects only waste
+/// space, so failures are logged rather than returned.
+async fn discard_session(state: &AppState, upload_id: Uuid) {
+    if let Err(e) = sqlx::query("DELETE FROM upload_sessions WHERE id = $1")
+        .bind(upload_id)
+        .execute(&state.pool)
+        .await
+    {
+        warn!(error = ?e, upload_id = %upload_id, "Failed to delete upload session");
+    }
+    if let Err(e) = state.storage.remove_all(&chunk_prefix(upload_id)).await {
+        warn!(error = ?e, upload_id = %upload_id, "Failed to delete upload chunks");
+    }
+}
+
+/// Discard sessions idle for longer than UPLOAD_SESSION_TTL_SECS, with their
+/// chunk objects. Sessions being completed are left alone unless their claim
+/// is older than UPLOAD_CLAIM_TIMEOUT_SECS. Does nothing when the TTL is 0.
+#[utoipa::path(
+    post,
+    path = "/admin/uploads/expire",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Expired uploads discarded", body = ExpiredUploadsResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn expire_uploads(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<ExpiredUploadsResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let ttl_secs = state.config.upload_session_ttl_secs;
+    if ttl_secs == 0 {
+        return Ok(Json(ExpiredUploadsResponse { expired: Vec::new(), ttl_secs }));
+    }
+
+    // Expired sessions are already refused by every upload route, so once
+    // the row is gone nothing can write to the chunk prefix again
+    let expired: Vec<Uuid> = sqlx::query_scalar(&format!(
+        "DELETE FROM upload_sessions s WHERE NOT {} AND NOT {} RETURNING id",
+        claim_held("$2"),
+        session_live("$1")
+    ))
+    .bind(ttl_secs as f64)
+    .bind(state.config.upload_claim_timeout_secs as f64)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    for upload_id in &expired {
+        if let Err(e) = state.storage.remove_all(&chunk_prefix(*upload_id)).await {
+            warn!(error = ?e, upload_id = %upload_id, "Failed to delete expired upload chunks");
+        }
+    }
+
+    info!(user_id = %current_user.id, expired = expired.len(), ttl_secs, "Expired resumable uploads discarded");
+
+    Ok(Json(ExpiredUploadsResponse { expired, ttl_secs }))
+}
@@ -0,0 +1,141 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::db::TimedQuery;
+use crate::dtos::{total_pages, SearchHit, SearchQuery, SearchResponse};
+use crate::error::AppError;
+use crate::response::capped_json;
+use crate::state::AppState;
+use axum::extract::{Query, State};
+use axum::response::Response;
+use axum::{routing::get, Router};
+use tracing::info;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/search", get(search_documents))
+}
+
+/// Live documents matching `$1`, with their rank. Titles weigh more than
+/// metadata values. The `simple` configuration does no stemming, so it
+/// behaves the same for every language stored.
+const MATCHES_CTE: &str = r#"
+    WITH query AS (
+        SELECT plainto_tsquery('simple', $1) AS q
+    ),
+    searchable AS (
+        SELECT
+            d.id,
+            setweight(to_tsvector('simple', d.title), 'A')
+            || setweight(to_tsvector('simple', COALESCE(
+                (SELECT string_agg(m.value, ' ') FROM document_metadata m WHERE m.document_id = d.id),
+                ''
+            )), 'B') AS doc
+        FROM documents d
+        WHERE d.deleted_at IS NULL
+    ),
+    matches AS (
+        SELECT s.id, ts_rank(s.doc, query.q) AS score
+        FROM searchable s, query
+        WHERE s.doc @@ query.q
+    )
+"#;
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "documents",
+    params(
+        ("q" = String, Query, description = "Keywords matched against titles and metadata values"),
+        ("page" = Option<u32>, Query, description = "Page number (default: 1)"),
+        ("page_size" = Option<u32>, Query, description = "Items per page (default: 20, max: 100)")
+    ),
+    responses(
+        (status = 200, description = "Matching documents, most relevant first", body = SearchResponse),
+        (status = 400, description = "Empty query"),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn search_documents(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err(AppError::BadRequest("q cannot be empty"));
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) as i64 * page_size as i64;
+
+    let total: i64 = sqlx::query_scalar(&format!("{} SELECT COUNT(*) FROM matches", MATCHES_CTE))
+        .bind(q)
+        .fetch_one(&state.read_pool)
+        .timed("search.count", state.config.slow_query_ms)
+        .await
+        .map_err(AppError::Db)?;
+
+    let page_sql = format!(
+        r#"
+        {}
+        SELECT
+            d.id,
+            d.title,
+            d.category,
+            d.created_at,
+            d.updated_at,
+            lv.version_number AS latest_version_number,
+            lv.file_name AS latest_file_name,
+            lv.file_size AS latest_file_size,
+            lv.mime_type AS latest_mime_type,
+            lv.created_at AS latest_created_at,
+            matches.score
+        FROM matches
+        JOIN documents d ON d.id = matches.id
+        LEFT JOIN LATERAL (
+            SELECT version_number, file_name, file_size, mime_type, created_at
+            FROM document_versions
+            WHERE document_id = d.id
+            ORDER BY (version_number = d.current_version) IS TRUE DESC, version_number DESC
+            LIMIT 1
+        ) lv ON TRUE
+        ORDER BY matches.score DESC, d.created_at DESC, d.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        MATCHES_CTE
+    );
+
+    let hits = sqlx::query_as::<_, SearchHit>(&page_sql)
+        .bind(q)
+        .bind(page_size as i64)
+        .bind(offset)
+        .fetch_all(&state.read_pool)
+        .timed("search.page", state.config.slow_query_ms)
+        .await
+        .map_err(AppError::Db)?;
+
+    info!(
+        user_id = %current_user.id,
+        query = %q,
+        total = total,
+        returned = hits.len(),
+        "Documents searched"
+    );
+
+    let total_pages = total_pages(total, page_size);
+    let response = SearchResponse {
+        query: q.to_string(),
+        data: hits,
+        page,
+        page_size,
+        total,
+        total_pages,
+        has_next: page < total_pages,
+        has_prev: page > 1,
+    };
+
+    capped_json("search.page", state.config.max_response_bytes, &response)
+}
@@ -0,0 +1,187 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{CreateSignedLinkRequest, SignedLinkQuery, SignedLinkResponse, SignedLinkVerification};
+use crate::error::AppError;
+use crate::routes::documents::{resolve_version, serve_version};
+use crate::signing::{sign_link, verify_link, LinkError};
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use axum::{routing::{get, post}, Json, Router};
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Audit `user_id` recorded for downloads through a signed link
+const SIGNED_LINK_USER: &str = "signed-link";
+
+/// Longest lifetime a signed link may be issued with
+const MAX_SIGNED_LINK_SECS: i64 = 7 * 24 * 3600;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/documents/:id/signed-link", post(create_signed_link))
+        .route("/documents/:id/content/signed", get(download_signed))
+        .route("/documents/:id/content/verify", get(verify_signed_link))
+}
+
+fn signing_secret(state: &AppState) -> Result<&str, AppError> {
+    state
+        .config
+        .signed_link_secret
+        .as_deref()
+        .ok_or(AppError::BadRequest("Signed links are disabled (SIGNED_LINK_SECRET is not set)"))
+}
+
+/// Validate `sig`/`exp` for `document_id` and check the document is still live
+async fn check_signed_link(
+    state: &AppState,
+    document_id: Uuid,
+    query: &SignedLinkQuery,
+) -> Result<(), AppError> {
+    let secret = signing_secret(state)?;
+
+    match verify_link(secret, document_id, query.exp, &query.sig, Utc::now().timestamp()) {
+        Ok(()) => {}
+        Err(LinkError::Expired) => {
+            return Err(AppError::Unauthorized("Signed link has expired"));
+        }
+        Err(LinkError::BadSignature) => {
+            warn!(document_id = %document_id, "Rejected signed link with invalid signature");
+            return Err(AppError::Unauthorized("Invalid signed link"));
+        }
+    }
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/signed-link",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body(content = CreateSignedLinkRequest, description = "Optional lifetime", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Signed download link", body = SignedLinkResponse),
+        (status = 400, description = "Invalid lifetime or signed links disabled"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_signed_link(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    request: Option<Json<CreateSignedLinkRequest>>,
+) -> Result<Json<SignedLinkResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+    let secret = signing_secret(&state)?;
+
+    let lifetime = request
+        .and_then(|Json(r)| r.expires_in_secs)
+        .unwrap_or(3600);
+    if lifetime <= 0 || lifetime > MAX_SIGNED_LINK_SECS {
+        return Err(AppError::BadRequest("expires_in_secs must be between 1 and 604800"));
+    }
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let exp = Utc::now().timestamp() + lifetime;
+    let sig = sign_link(secret, document_id, exp);
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        exp = exp,
+        "Signed link issued"
+    );
+
+    Ok(Json(SignedLinkResponse {
+        document_id,
+        url: format!("/documents/{}/content/signed?sig={}&exp={}", document_id, sig, exp),
+        sig,
+        exp,
+        expires_at: DateTime::from_timestamp(exp, 0).unwrap_or_else(Utc::now),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/content/signed",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("sig" = String, Query, description = "Link signature"),
+        ("exp" = i64, Query, description = "Link expiry (unix seconds)")
+    ),
+    responses(
+        (status = 200, description = "Latest version content", content_type = "application/octet-stream"),
+        (status = 401, description = "Invalid or expired signature"),
+        (status = 404, description = "Document not found")
+    )
+)]
+pub async fn download_signed(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<SignedLinkQuery>,
+) -> Result<Response, AppError> {
+    check_signed_link(&state, document_id, &query).await?;
+
+    let dv = resolve_version(&state, &state.read_pool, document_id, None).await?;
+
+    serve_version(&state, dv, SIGNED_LINK_USER.to_string(), true, None).await
+}
+
+/// Check a signed link without serving the file
+#[utoipa::path(
+    get,
+    path = "/documents/{id}/content/verify",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("sig" = String, Query, description = "Link signature"),
+        ("exp" = i64, Query, description = "Link expiry (unix seconds)")
+    ),
+    responses(
+        (status = 200, description = "Link is valid", body = SignedLinkVerification),
+        (status = 401, description = "Invalid or expired signature"),
+        (status = 404, description = "Document not found")
+    )
+)]
+pub async fn verify_signed_link(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<SignedLinkQuery>,
+) -> Result<Json<SignedLinkVerification>, AppError> {
+    check_signed_link(&state, document_id, &query).await?;
+
+    Ok(Json(SignedLinkVerification {
+        document_id,
+        valid: true,
+        expires_at: DateTime::from_timestamp(query.exp, 0).unwrap_or_else(Utc::now),
+    }))
+}
@@ -0,0 +1,76 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::db::TimedQuery;
+use crate::dtos::UploaderStats;
+use crate::error::AppError;
+use crate::response::capped_json;
+use crate::state::AppState;
+use axum::extract::State;
+use axum::response::Response;
+use axum::{routing::get, Router};
+use tracing::info;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/stats/uploaders", get(list_uploaders))
+}
+
+/// Per-user document and version counts and bytes uploaded, largest first.
+/// Soft-deleted documents and their versions are left out.
+#[utoipa::path(
+    get,
+    path = "/stats/uploaders",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Users who uploaded at least one document or version", body = Vec<UploaderStats>),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_uploaders(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let stats = sqlx::query_as::<_, UploaderStats>(
+        r#"
+        WITH document_counts AS (
+            SELECT created_by AS user_id, COUNT(*) AS documents
+            FROM documents
+            WHERE deleted_at IS NULL AND created_by IS NOT NULL
+            GROUP BY created_by
+        ),
+        version_counts AS (
+            SELECT
+                COALESCE(v.uploaded_by, d.created_by) AS user_id,
+                COUNT(*) AS versions,
+                SUM(v.file_size) AS bytes
+            FROM document_versions v
+            JOIN documents d ON d.id = v.document_id
+            WHERE d.deleted_at IS NULL
+            GROUP BY 1
+        )
+        SELECT
+            u.id AS user_id,
+            u.username,
+            u.role,
+            COALESCE(dc.documents, 0) AS document_count,
+            COALESCE(vc.versions, 0) AS version_count,
+            COALESCE(vc.bytes, 0)::BIGINT AS total_bytes
+        FROM users u
+        LEFT JOIN document_counts dc ON dc.user_id = u.id
+        LEFT JOIN version_counts vc ON vc.user_id = u.id
+        WHERE dc.user_id IS NOT NULL OR vc.user_id IS NOT NULL
+        ORDER BY total_bytes DESC, u.username
+        "#,
+    )
+    .fetch_all(&state.read_pool)
+    .timed("stats.uploaders", state.config.slow_query_ms)
+    .await
+    .map_err(AppError::Db)?;
+
+    info!(user_id = %current_user.id, uploaders = stats.len(), "Uploader stats listed");
+
+    capped_json("stats.uploaders", state.config.max_response_bytes, &stats)
+}
@@ -1,18 +1,51 @@
 use crate::error::AppError;
 use crate::auth::{CurrentUser, check_permission, StorageAction};
 use crate::state::AppState;
-use crate::dtos::{AddTagToDocumentRequest, TagInfo, AddTagToDocumentResponse};
+use crate::dtos::{AddTagToDocumentRequest, AddTagIdsRequest, TagInfo, TagStatus, AddTagToDocumentResponse, RemoveTagQuery, RemoveTagResponse};
 use tracing::{info, warn, debug};
-use axum::{routing::post, Router, extract::State, Json};
-use serde::{Deserialize, Serialize};
+use axum::{routing::{delete, get, post}, Router, extract::{Path, Query, State}, Json};
+use axum::response::Response;
+use crate::response::capped_json;
+use crate::db::TimedQuery;
+use axum::http::StatusCode;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use anyhow;
 use crate::models::Tag; 
 use sqlx;
 
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/tags", post(add_tags_to_document))
+    Router::new()
+        .route("/tags", get(list_tags).post(add_tags_to_document))
+        .route("/documents/:id/tags", post(add_tag_ids_to_document))
+        .route("/documents/:id/tags/:tag_id", delete(remove_tag_from_document))
+}
+
+/// All tags, alphabetically
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All tags", body = Vec<Tag>),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_tags(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let tags = sqlx::query_as::<_, Tag>("SELECT id, name, created_at FROM tags ORDER BY name")
+        .fetch_all(&state.read_pool)
+        .timed("tags.list", state.config.slow_query_ms)
+        .await
+        .map_err(AppError::Db)?;
+
+    debug!(tags = tags.len(), "Listed tags");
+
+    capped_json("tags.list", state.config.max_response_bytes, &tags)
 }
 
 #[utoipa::path(
@@ -22,6 +55,7 @@ pub fn routes() -> Router<AppState> {
     request_body = AddTagToDocumentRequest,
     responses(
         (status = 200, description = "Tags added to document successfully", body = AddTagToDocumentResponse),
+        (status = 207, description = "Tags processed with mixed per-tag outcomes", body = AddTagToDocumentResponse),
         (status = 400, description = "Bad request - invalid document_id or empty tags"),
         (status = 401, description = "Unauthorized"),
         (status = 403, description = "Forbidden - write permission required")
@@ -32,7 +66,7 @@ pub async fn add_tags_to_document(
     State(state): State<AppState>,
     current_user: CurrentUser,
     Json(request): Json<AddTagToDocumentRequest>,
-) -> Result<Json<AddTagToDocumentResponse>, AppError> {
+) -> Result<(StatusCode, Json<AddTagToDocumentResponse>), AppError> {
 
     check_permission(&current_user, StorageAction::Write)?;
 
@@ -58,7 +92,14 @@ pub async fn add_tags_to_document(
         let tag_name = tag_name.trim();
         
         if tag_name.is_empty() {
-            continue; // Skip empty tag names
+            // Skip empty tag names, but report them
+            tag_infos.push(TagInfo {
+                tag_id: None,
+                tag_name: tag_name.to_string(),
+                tag_created: false,
+                status: TagStatus::SkippedEmpty,
+            });
+            continue;
         }
         
         let (tag, tag_was_created): (Tag, bool) = match sqlx::query_as::<_, Tag>(
@@ -104,18 +145,35 @@ pub async fn add_tags_to_document(
             .map_err(AppError::Db)?;
         }
         
+        let status = if tag_was_created {
+            TagStatus::Created
+        } else if relationship_exists {
+            TagStatus::AlreadyPresent
+        } else {
+            TagStatus::Added
+        };
+
         tag_infos.push(TagInfo {
-            tag_id: tag.id,
+            tag_id: Some(tag.id),
             tag_name: tag.name,
             tag_created: tag_was_created,
+            status,
         });
     }
 
-    if tag_infos.is_empty() {
+    let total = tag_infos
+        .iter()
+        .filter(|t| t.status != TagStatus::SkippedEmpty)
+        .count();
+
+    if total == 0 {
         return Err(AppError::BadRequest("No valid tags were processed"));
     }
 
-    let total = tag_infos.len();
+    // 207 tells the client to inspect per-tag statuses
+    let mixed = tag_infos.iter().any(|t| t.status != tag_infos[0].status);
+    let status_code = if mixed { StatusCode::MULTI_STATUS } else { StatusCode::OK };
+
     let response = AddTagToDocumentResponse {
         document_id: request.document_id,
         tags: tag_infos,
@@ -129,6 +187,197 @@ pub async fn add_tags_to_document(
         "Tags added to document successfully"
     );
 
-    Ok(Json(response))
+    Ok((status_code, Json(response)))
+
+}
+
+/// Link existing tags to a document by id. Nothing is linked unless every id
+/// names an existing tag, so a stale or mistyped id can't half-apply.
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/tags",
+    tag = "tags",
+    params(("id" = Uuid, Path, description = "Document ID")),
+    request_body = AddTagIdsRequest,
+    responses(
+        (status = 200, description = "Tags linked to the document", body = AddTagToDocumentResponse),
+        (status = 207, description = "Some tags were already linked", body = AddTagToDocumentResponse),
+        (status = 400, description = "Bad request - empty tag_ids"),
+        (status = 404, description = "Document or one of the tags not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn add_tag_ids_to_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(request): Json<AddTagIdsRequest>,
+) -> Result<(StatusCode, Json<AddTagToDocumentResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let mut tag_ids = request.tag_ids;
+    tag_ids.sort();
+    tag_ids.dedup();
+    if tag_ids.is_empty() {
+        return Err(AppError::BadRequest("tag_ids cannot be empty"));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    let tags = sqlx::query_as::<_, Tag>(
+        "SELECT id, name, created_at FROM tags WHERE id = ANY($1) ORDER BY name"
+    )
+    .bind(&tag_ids)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if tags.len() != tag_ids.len() {
+        let missing: Vec<Uuid> = tag_ids
+            .iter()
+            .filter(|id| !tags.iter().any(|t| t.id == **id))
+            .copied()
+            .collect();
+        warn!(document_id = %document_id, missing = ?missing, "Unknown tag ids in add-tags request");
+        return Err(AppError::NotFound("Tag not found"));
+    }
+
+    let linked: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO document_tags (document_id, tag_id)
+        SELECT $1, UNNEST($2::uuid[])
+        ON CONFLICT DO NOTHING
+        RETURNING tag_id
+        "#,
+    )
+    .bind(document_id)
+    .bind(&tag_ids)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    let tag_infos: Vec<TagInfo> = tags
+        .into_iter()
+        .map(|tag| TagInfo {
+            status: if linked.contains(&tag.id) {
+                TagStatus::Added
+            } else {
+                TagStatus::AlreadyPresent
+            },
+            tag_id: Some(tag.id),
+            tag_name: tag.name,
+            tag_created: false,
+        })
+        .collect();
+
+    let mixed = tag_infos.iter().any(|t| t.status != tag_infos[0].status);
+    let status_code = if mixed { StatusCode::MULTI_STATUS } else { StatusCode::OK };
+
+    info!(
+        document_id = %document_id,
+        tags_count = tag_infos.len(),
+        linked = linked.len(),
+        user_id = %current_user.id,
+        "Tags linked to document by id"
+    );
+
+    Ok((
+        status_code,
+        Json(AddTagToDocumentResponse {
+            document_id,
+            total: tag_infos.len(),
+            tags: tag_infos,
+        }),
+    ))
+}
 
-}
\ No newline at end of file
+/// Detach a tag from a document, optionally deleting the tag once unused
+#[utoipa::path(
+    delete,
+    path = "/documents/{id}/tags/{tag_id}",
+    tag = "tags",
+    params(
+        ("id" = Uuid, Path, description = "Document ID"),
+        ("tag_id" = Uuid, Path, description = "Tag ID"),
+        ("delete_orphan" = Option<bool>, Query, description = "Delete the tag if no document uses it afterwards (default: false)")
+    ),
+    responses(
+        (status = 200, description = "Tag removed from the document", body = RemoveTagResponse),
+        (status = 404, description = "Document does not carry this tag"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn remove_tag_from_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path((document_id, tag_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<RemoveTagQuery>,
+) -> Result<Json<RemoveTagResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let removed = sqlx::query("DELETE FROM document_tags WHERE document_id = $1 AND tag_id = $2")
+        .bind(document_id)
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+    if removed == 0 {
+        return Err(AppError::NotFound("Document does not have this tag"));
+    }
+
+    let tag_deleted = if query.delete_orphan.unwrap_or(false) {
+        sqlx::query(
+            r#"
+            DELETE FROM tags
+            WHERE id = $1
+              AND NOT EXISTS (SELECT 1 FROM document_tags WHERE tag_id = $1)
+            "#,
+        )
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected()
+            > 0
+    } else {
+        false
+    };
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    info!(
+        document_id = %document_id,
+        tag_id = %tag_id,
+        tag_deleted = tag_deleted,
+        user_id = %current_user.id,
+        "Tag removed from document"
+    );
+
+    Ok(Json(RemoveTagResponse {
+        document_id,
+        tag_id,
+        tag_deleted,
+    }))
+}
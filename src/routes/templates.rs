@@ -0,0 +1,286 @@
+use crate::audit::{log_deferred, log_in_tx};
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{FromTemplateRequest, FromTemplateResponse, SetTemplateRequest, TemplateResponse};
+use crate::error::AppError;
+use crate::models::{AuditAction, Document, DocumentVersion, NewAuditLog};
+use crate::routes::documents::resolve_version;
+use crate::routes::visibility::check_visibility_permission;
+use crate::state::AppState;
+use crate::storage::version_key;
+use crate::text::normalize_name;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{routing::post, Json, Router};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/documents/:id/template", post(set_template))
+        .route("/documents/from-template/:template_id", post(create_from_template))
+}
+
+/// Mark or unmark a document as a template. Same ownership rule as visibility.
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/template",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body = SetTemplateRequest,
+    responses(
+        (status = 200, description = "Template flag updated", body = TemplateResponse),
+        (status = 403, description = "Permission denied - owner or admin required"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn set_template(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(request): Json<SetTemplateRequest>,
+) -> Result<Json<TemplateResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found or has been deleted"))?;
+
+    check_visibility_permission(&current_user, &document)?;
+
+    let was_template: bool = sqlx::query_scalar("SELECT is_template FROM documents WHERE id = $1")
+        .bind(document_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    sqlx::query("UPDATE documents SET is_template = $1 WHERE id = $2")
+        .bind(request.is_template)
+        .bind(document_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    let audit_entry = NewAuditLog {
+        user_id: current_user.id.to_string(),
+        action: AuditAction::UpdateMetadata,
+        document_id: Some(document_id),
+        document_version: None,
+        metadata: serde_json::json!({
+            "change": "template",
+            "before": { "is_template": was_template },
+            "after": { "is_template": request.is_template },
+        }),
+    };
+    let deferred_audit = log_in_tx(&state, &mut tx, audit_entry).await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        is_template = request.is_template,
+        "Document template flag updated"
+    );
+
+    Ok(Json(TemplateResponse {
+        document_id,
+        is_template: request.is_template,
+    }))
+}
+
+/// Create a document from a template: its latest version is copied as
+/// version 1, along with the template's metadata and tags
+#[utoipa::path(
+    post,
+    path = "/documents/from-template/{template_id}",
+    tag = "documents",
+    params(
+        ("template_id" = Uuid, Path, description = "Template document ID")
+    ),
+    request_body(content = FromTemplateRequest, description = "Optional title override"),
+    responses(
+        (status = 201, description = "Document created from the template", body = FromTemplateResponse),
+        (status = 400, description = "Document is not a template"),
+        (status = 404, description = "Template not found or has no versions"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - write permission required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_from_template(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(template_id): Path<Uuid>,
+    request: Option<Json<FromTemplateRequest>>,
+) -> Result<(StatusCode, Json<FromTemplateResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let template: Option<(String, Option<String>, bool)> = sqlx::query_as(
+        "SELECT title, category, is_template FROM documents WHERE id = $1 AND deleted_at IS NULL"
+    )
+    .bind(template_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let (template_title, category, is_template) =
+        template.ok_or(AppError::NotFound("Template not found or has been deleted"))?;
+    if !is_template {
+        return Err(AppError::BadRequest("Document is not a template"));
+    }
+
+    let title = match request.and_then(|Json(r)| r.title) {
+        Some(t) if state.config.normalize_text => normalize_name(&t),
+        Some(t) => t.trim().to_string(),
+        None => template_title,
+    };
+    if title.is_empty() {
+        return Err(AppError::BadRequest("title cannot be empty"));
+    }
+
+    let source = resolve_version(&state, &state.pool, template_id, None).await?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        INSERT INTO documents (title, category, created_by)
+        VALUES ($1, $2, $3)
+        RETURNING id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        "#,
+    )
+    .bind(&title)
+    .bind(&category)
+    .bind(current_user.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let stored_path = version_key(
+        state.config.key_strategy,
+        state.config.key_namespaces,
+        document.category.as_deref(),
+        &document.title,
+        document.id,
+        1,
+    );
+
+    state.storage.copy(&source.file_path, &stored_path).await?;
+
+    // Nothing references the copy until the transaction commits, so it is
+    // removed whatever fails from here on
+    let committed: Result<_, AppError> = async {
+        let version = sqlx::query_as::<_, DocumentVersion>(
+            r#"
+            INSERT INTO document_versions
+            (document_id, version_number, file_name, file_path, file_size, mime_type, checksum, uploaded_by)
+            VALUES ($1, 1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+            "#,
+        )
+        .bind(document.id)
+        .bind(&source.file_name)
+        .bind(&stored_path)
+        .bind(source.file_size)
+        .bind(&source.mime_type)
+        .bind(&source.checksum)
+        .bind(current_user.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+        let metadata_copied = sqlx::query(
+            r#"
+            INSERT INTO document_metadata (document_id, key, value)
+            SELECT $1, key, value FROM document_metadata WHERE document_id = $2
+            "#,
+        )
+        .bind(document.id)
+        .bind(template_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+        let tags_copied = sqlx::query(
+            r#"
+            INSERT INTO document_tags (document_id, tag_id)
+            SELECT $1, tag_id FROM document_tags WHERE document_id = $2
+            "#,
+        )
+        .bind(document.id)
+        .bind(template_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .rows_affected();
+
+        let audit_entry = NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::Upload,
+            document_id: Some(document.id),
+            document_version: Some(1),
+            metadata: serde_json::json!({
+                "from_template": template_id,
+                "template_version": source.version_number,
+                "file_name": &source.file_name,
+                "checksum": &source.checksum,
+            }),
+        };
+        let deferred_audit = log_in_tx(&state, &mut tx, audit_entry).await?;
+
+        tx.commit().await.map_err(AppError::Db)?;
+        Ok((version, metadata_copied, tags_copied, deferred_audit))
+    }
+    .await;
+
+    let (version, metadata_copied, tags_copied, deferred_audit) = match committed {
+        Ok(committed) => committed,
+        Err(e) => {
+            if let Err(e) = state.storage.delete(&stored_path).await {
+                warn!(error = ?e, key = %stored_path, "Failed to clean up object after aborted template instantiation");
+            }
+            return Err(e);
+        }
+    };
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        template_id = %template_id,
+        document_id = %document.id,
+        metadata_copied = metadata_copied,
+        tags_copied = tags_copied,
+        "Document created from template"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(FromTemplateResponse {
+            template_id,
+            document,
+            version,
+            metadata_copied,
+            tags_copied,
+        }),
+    ))
+}
@@ -2,20 +2,30 @@ use crate::auth::{check_permission, CurrentUser, StorageAction};
 use crate::{
     dtos::UploadResponse,
     error::AppError,
-    models::{Document, DocumentVersion},
+    models::{AuditAction, Document, DocumentVersion, NewAuditLog},
     state::AppState,
 };
 use axum::extract::{Multipart, State};
 use axum::Json;
+use axum::http::{header, HeaderMap, HeaderValue};
 use axum::{routing::post, Router};
 use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use opendal::Operator;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::{collections::HashMap, fs};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::audit::log_upload;
+use crate::audit::{log_deferred, log_in_tx};
+use crate::mime::{resolve_upload_mime, SNIFF_PREFIX_BYTES};
+use crate::storage::{folder_metadata_key, read_prefix, sanitize_segment, verify_written, version_key};
+use crate::notifications::notify_watchers;
+use crate::routes::metadata::validate_metadata;
+use crate::text::normalize_name;
+use crate::quota::storage_usage_warning;
 
 #[derive(Serialize, Deserialize)]
 struct FolderMetadata {
@@ -25,47 +35,20 @@ struct FolderMetadata {
     created_at: DateTime<Utc>,
 }
 
-fn sanitize_folder_name(name: &str) -> String {
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-}
-
 pub fn routes() -> Router<AppState> {
     Router::new().route("/upload", post(upload_file))
 }
 
-// for using the folders structure in the seaweed
-fn build_storage_path_with_folder(
-    folder_name: Option<String>,
-    document_id: Uuid,
-    version_number: i32,
-) -> String {
-    let folder_name = folder_name
-        .as_ref()
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "Uncategorized".to_string());
-
-    format!("{}/{}/v{}", folder_name, document_id, version_number)
-}
-
 #[utoipa::path(
     post,
     path = "/upload",
     tag = "upload",
     request_body(content = String, content_type = "multipart/form-data", description = "File upload with title, category, and optional metadata"),
     responses(
-        (status = 200, description = "Upload successful", body = UploadResponse),
-        (status = 400, description = "Bad request"),
-        (status = 401, description = "Unauthorized")
+        (status = 200, description = "Upload successful; `X-Storage-Usage-Pct` and `Warning` headers are added once usage passes STORAGE_WARN_PCT", body = UploadResponse),
+        (status = 400, description = "Bad request, including a missing or empty file and a truncated or malformed multipart body"),
+        (status = 401, description = "Unauthorized"),
+        (status = 409, description = "Content matches an existing version of the document (REJECT_DUPLICATE_VERSIONS)")
     ),
     security(
         ("api_key" = [])
@@ -75,7 +58,7 @@ async fn upload_file(
     State(state): State<AppState>,
     current_user: CurrentUser,
     mut multipart: Multipart,
-) -> Result<Json<UploadResponse>, AppError> {
+) -> Result<(HeaderMap, Json<UploadResponse>), AppError> {
     info!(user_id = %current_user.id, username = %current_user.username, role = %current_user.role, "File upload request received");
 
     // Check if user has write permission
@@ -96,64 +79,69 @@ async fn upload_file(
     let mut category: Option<String> = None;
     let mut file_name: Option<String> = None;
     let mut file_bytes: Option<Vec<u8>> = None;
+    let mut empty_file = false;
     let mut mime_type: Option<String> = None;
     let mut metadata: HashMap<String, String> = HashMap::new();
     let mut metadata_keys: Vec<String> = Vec::new();
 
-    while let Ok(Some(field)) = multipart.next_field().await {
+    // A read error means the body was cut off or malformed; stopping the loop
+    // quietly would store whatever fields happened to arrive first
+    while let Some(field) = multipart.next_field().await.map_err(malformed_upload)? {
         let name = field.name().unwrap_or("").to_string();
         match name.as_str() {
             "document_id" => {
-                if let Ok(text) = field.text().await {
-                    match Uuid::parse_str(text.trim()) {
-                        Ok(id) => document_id = Some(id),
-                        Err(_) => {
-                            return Err(AppError::BadRequest("Invalid document_id (must be UUID)"));
-                        }
+                let text = field.text().await.map_err(malformed_upload)?;
+                match Uuid::parse_str(text.trim()) {
+                    Ok(id) => document_id = Some(id),
+                    Err(_) => {
+                        return Err(AppError::BadRequest("Invalid document_id (must be UUID)"));
                     }
                 }
             }
             "title" => {
-                title_opt = field.text().await.ok();
+                title_opt = Some(field.text().await.map_err(malformed_upload)?);
             }
             "category" => {
-                category = field.text().await.ok();
+                category = Some(field.text().await.map_err(malformed_upload)?);
             }
             "file" => {
                 file_name = field.file_name().map(|s| s.to_string());
                 mime_type = field.content_type().map(|s| s.to_string());
-                if let Ok(bytes) = field.bytes().await {
+                let bytes = field.bytes().await.map_err(malformed_upload)?;
+                // An empty part counts as no file, so it can't replace an
+                // earlier non-empty one and never becomes a zero-length version
+                if bytes.is_empty() {
+                    empty_file = true;
+                } else {
                     file_bytes = Some(bytes.to_vec());
                 }
             }
             "metadata" => {
-                if let Ok(text) = field.text().await {
-                    match serde_json::from_str::<Value>(&text) {
-                        Ok(Value::Object(map)) => {
-                            for (k, v) in map {
-                                if let Some(val) = v.as_str() {
-                                    if !k.is_empty() {
-                                        metadata.insert(k.clone(), val.to_string());
-                                        metadata_keys.push(k);
-                                    }
+                let text = field.text().await.map_err(malformed_upload)?;
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(Value::Object(map)) => {
+                        for (k, v) in map {
+                            if let Some(val) = v.as_str() {
+                                if !k.is_empty() {
+                                    metadata.insert(k.clone(), val.to_string());
+                                    metadata_keys.push(k);
                                 }
                             }
                         }
-                        _ => {
-                            return Err(AppError::BadRequest(
-                                "Invalid metadata JSON; expected an object of string values",
-                            ));
-                        }
+                    }
+                    _ => {
+                        return Err(AppError::BadRequest(
+                            "Invalid metadata JSON; expected an object of string values",
+                        ));
                     }
                 }
             }
             name if name.starts_with("meta_") => {
-                if let Ok(val) = field.text().await {
-                    let key = name.trim_start_matches("meta_").to_string();
-                    if !key.is_empty() {
-                        metadata.insert(key.clone(), val);
-                        metadata_keys.push(key);
-                    }
+                let val = field.text().await.map_err(malformed_upload)?;
+                let key = name.trim_start_matches("meta_").to_string();
+                if !key.is_empty() {
+                    metadata.insert(key.clone(), val);
+                    metadata_keys.push(key);
                 }
             }
             _ => {}
@@ -162,13 +150,160 @@ async fn upload_file(
 
     let file_bytes = match file_bytes {
         Some(b) => b,
+        None if empty_file => {
+            warn!("File upload request has an empty file field");
+            return Err(AppError::BadRequest("File is empty"));
+        }
         None => {
             warn!("File upload request missing file field");
             return Err(AppError::BadRequest("Missing file"));
         }
     };
+
+    let input = UploadInput {
+        document_id,
+        title: title_opt,
+        category,
+        file_name,
+        declared_mime_type: mime_type,
+        content: UploadContent::Bytes(file_bytes),
+        metadata,
+        metadata_keys,
+    };
+    let (headers, response) = store_upload(&state, &current_user, input).await?;
+
+    Ok((headers, Json(response)))
+}
+
+/// An upload whose content has fully arrived, either as a single multipart
+/// POST or assembled from resumable chunks
+pub(crate) struct UploadInput {
+    /// Add a version to this document instead of creating one
+    pub document_id: Option<Uuid>,
+    pub title: Option<String>,
+    pub category: Option<String>,
+    pub file_name: Option<String>,
+    /// Content type claimed by the client; kept for the audit trail
+    pub declared_mime_type: Option<String>,
+    pub content: UploadContent,
+    pub metadata: HashMap<String, String>,
+    /// Metadata keys in the order the client sent them
+    pub metadata_keys: Vec<String>,
+}
+
+/// Where the content of an upload comes from
+pub(crate) enum UploadContent {
+    /// The whole file, as received in a multipart body
+    Bytes(Vec<u8>),
+    /// Stored objects to concatenate in order, e.g. resumable upload chunks.
+    /// They are streamed into the version object, never held in memory whole.
+    Objects(Vec<String>),
+}
+
+impl UploadContent {
+    /// The first bytes of the content, enough for MIME sniffing
+    async fn prefix(&self, storage: &Operator) -> Result<Vec<u8>, AppError> {
+        match self {
+            UploadContent::Bytes(bytes) => {
+                Ok(bytes[..bytes.len().min(SNIFF_PREFIX_BYTES as usize)].to_vec())
+            }
+            UploadContent::Objects(keys) => {
+                let Some(key) = keys.first() else {
+                    return Ok(Vec::new());
+                };
+                Ok(read_prefix(storage, key, SNIFF_PREFIX_BYTES).await?)
+            }
+        }
+    }
+}
+
+/// Write `content` to `key` through a streaming writer, hashing it on the
+/// way. Returns the size and hex SHA-256 of what was written. A failed write
+/// is aborted so no partial object is left behind.
+async fn write_content(
+    storage: &Operator,
+    key: &str,
+    content: UploadContent,
+) -> Result<(u64, String), AppError> {
+    let mut writer = storage.writer(key).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+
+    let written: Result<(), AppError> = async {
+        match content {
+            UploadContent::Bytes(bytes) => {
+                hasher.update(&bytes);
+                size = bytes.len() as u64;
+                writer.write(bytes).await?;
+            }
+            UploadContent::Objects(sources) => {
+                for source in sources {
+                    let mut stream = storage.reader(&source).await?.into_bytes_stream(..).await?;
+                    while let Some(chunk) = stream.try_next().await? {
+                        hasher.update(&chunk);
+                        size += chunk.len() as u64;
+                        writer.write(chunk).await?;
+                    }
+                }
+            }
+        }
+        writer.close().await?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = written {
+        if let Err(abort) = writer.abort().await {
+            warn!(error = ?abort, key = %key, "Failed to abort partial upload write");
+        }
+        return Err(e);
+    }
+
+    Ok((size, hex::encode(hasher.finalize())))
+}
+
+/// Create the document or its next version from a received upload: writes
+/// the object, records the version, metadata and audit entry, and notifies
+/// watchers. Returns the storage usage warning headers alongside the response.
+pub(crate) async fn store_upload(
+    state: &AppState,
+    current_user: &CurrentUser,
+    input: UploadInput,
+) -> Result<(HeaderMap, UploadResponse), AppError> {
+    let UploadInput {
+        document_id,
+        title: mut title_opt,
+        mut category,
+        file_name,
+        declared_mime_type: mime_type,
+        content,
+        metadata,
+        metadata_keys,
+    } = input;
+
+    validate_metadata(&metadata)?;
+
+    if state.config.normalize_text {
+        title_opt = title_opt.map(|t| normalize_name(&t));
+        category = category.map(|c| normalize_name(&c)).filter(|c| !c.is_empty());
+    }
+
     let file_name = file_name.unwrap_or_else(|| "upload.bin".to_string());
 
+    let category_default_mime = category
+        .as_deref()
+        .map(|c| c.trim().to_lowercase())
+        .and_then(|c| state.config.category_default_mime_types.get(&c));
+    // The client's claim is kept for the audit trail; what we store and serve
+    // comes from the content where possible.
+    let declared_mime_type = mime_type;
+    let mime_type = resolve_upload_mime(
+        state.config.sniff_mime,
+        declared_mime_type.as_deref(),
+        &content.prefix(&state.storage).await?,
+        category_default_mime.map(String::as_str),
+    );
+
     // NOTE ABOUT STORAGE KEY STRATEGIES
     //
     // Old approach (random UUID file name), kept for reference:
@@ -213,21 +348,21 @@ async fn upload_file(
     // So we delay the OpenDAL write until AFTER we decide whether we are
     // creating a new document or appending a new version.
 
-    let file_size = file_bytes.len() as i64;
-    let checksum = None::<String>;
-
     debug!("Starting database transaction");
     let mut tx = state.pool.begin().await?;
 
     // Create new document or append to existing
     let (document, next_version_number) = if let Some(doc_id) = document_id {
         debug!(document_id = %doc_id, "Adding new version to existing document");
-        // Existing document: ensure it exists
+        // Existing document: ensure it exists, and lock it until commit so
+        // concurrent uploads and restores can't take the same version number
+        // (and with it the same storage key)
         let doc_opt = sqlx::query_as::<_, Document>(
             r#"
-            SELECT id, title, category, created_at, updated_at,deleted_at
+            SELECT id, title, category, created_by, is_public, created_at, updated_at,deleted_at
             FROM documents
             WHERE id = $1
+            FOR UPDATE
             "#,
         )
         .bind(doc_id)
@@ -272,13 +407,14 @@ async fn upload_file(
 
         let doc = sqlx::query_as::<_, Document>(
             r#"
-            INSERT INTO documents (title, category)
-            VALUES ($1, $2)
-            RETURNING id, title, category, created_at, updated_at,deleted_at
+            INSERT INTO documents (title, category, created_by)
+            VALUES ($1, $2, $3)
+            RETURNING id, title, category, created_by, is_public, created_at, updated_at,deleted_at
             "#,
         )
         .bind(&title)
         .bind(&category)
+        .bind(current_user.id)
         .fetch_one(&mut *tx)
         .await?;
         (doc, 1)
@@ -349,8 +485,8 @@ async fn upload_file(
 
     // If category is provided, ensure folder metadata exists
     if let Some(ref cat) = folder_name {
-        let sanitized_name = sanitize_folder_name(cat);
-        let metadata_path = format!("{}/.folder_metadata.json", sanitized_name);
+        let sanitized_name = sanitize_segment(cat);
+        let metadata_path = folder_metadata_key(state.config.key_namespaces, &sanitized_name);
 
         // Check if metadata file already exists
         let metadata_exists = state.storage.stat(&metadata_path).await.is_ok();
@@ -388,24 +524,75 @@ async fn upload_file(
         }
     }
 
-    let stored_path = build_storage_path_with_folder(folder_name, document.id, next_version_number);
+    let stored_path = version_key(
+        state.config.key_strategy,
+        state.config.key_namespaces,
+        folder_name.as_deref(),
+        &document.title,
+        document.id,
+        next_version_number,
+    );
 
     info!(
         file_name = %file_name,
-        file_size = file_bytes.len(),
         stored_key = %stored_path,
         "Saving file via OpenDAL using document/version-based key"
     );
-    state
-        .storage
-        .write(&stored_path, file_bytes.clone())
+    // The checksum is only known once the content has been streamed, so the
+    // duplicate check below runs after the write and removes the object again
+    let (written_size, written_checksum) = write_content(&state.storage, &stored_path, content).await?;
+    let file_size = written_size as i64;
+    let checksum = Some(written_checksum);
+    debug!(stored_key = %stored_path, file_size, "Saved file via OpenDAL");
+
+    if state.config.verify_writes {
+        let expected_checksum = checksum.as_deref().unwrap_or_default();
+        if let Err(e) = verify_written(&state.storage, &stored_path, written_size, expected_checksum).await {
+            // Dropping `tx` rolls the document/version rows back; the document
+            // lock makes `stored_path` ours, so no committed version loses it
+            if let Err(e) = state.storage.delete(&stored_path).await {
+                warn!(error = ?e, key = %stored_path, "Failed to clean up object after failed write verification");
+            }
+            return Err(e);
+        }
+        debug!(stored_key = %stored_path, "Write verified by read-back");
+    }
+
+    if let (Some(doc_id), true) = (document_id, state.config.reject_duplicate_versions) {
+        let existing: Option<i32> = sqlx::query_scalar(
+            r#"
+            SELECT version_number
+            FROM document_versions
+            WHERE document_id = $1 AND checksum = $2
+            ORDER BY version_number DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(doc_id)
+        .bind(&checksum)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        if let Some(version) = existing {
+            warn!(
+                document_id = %doc_id,
+                existing_version = version,
+                "Upload matches an existing version (REJECT_DUPLICATE_VERSIONS)"
+            );
+            // The document lock makes `stored_path` ours, so no committed
+            // version can point at it
+            if let Err(e) = state.storage.delete(&stored_path).await {
+                warn!(error = ?e, key = %stored_path, "Failed to clean up object of duplicate upload");
+            }
+            return Err(AppError::DuplicateVersion(version));
+        }
+    }
+
     // Insert version with computed version number
     let version = sqlx::query_as::<_, DocumentVersion>(r#"
         INSERT INTO document_versions 
-        (document_id, version_number, file_name, file_path, file_size, mime_type, checksum)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        (document_id, version_number, file_name, file_path, file_size, mime_type, checksum, uploaded_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         RETURNING id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
         "#,
     )
@@ -418,6 +605,7 @@ async fn upload_file(
     .bind(file_size)
     .bind(&mime_type)
     .bind(&checksum)
+    .bind(current_user.id)
     .fetch_one(&mut *tx)
     .await?;
 
@@ -444,35 +632,68 @@ async fn upload_file(
         })?;
     }
 
+    let audit_entry = NewAuditLog {
+        user_id: current_user.id.to_string(),
+        action: AuditAction::Upload,
+        document_id: Some(document.id),
+        document_version: Some(next_version_number),
+        metadata: json!({
+            "new_document": document_id.is_none(),
+            "file_name": &file_name,
+            "file_size": file_size,
+            "mime_type": &mime_type,
+            "declared_mime_type": &declared_mime_type,
+            "checksum": &checksum,
+            "metadata_count": metadata_count,
+        }),
+    };
+
+    // Fail-closed auditing writes the row in this transaction; if that fails
+    // the upload is rolled back and the just-written object removed.
+    let deferred_audit = match log_in_tx(state, &mut tx, audit_entry).await {
+        Ok(deferred) => deferred,
+        Err(e) => {
+            warn!(
+                error = ?e,
+                document_id = %document.id,
+                "Audit write failed, aborting upload (AUDIT_FAIL_CLOSED)"
+            );
+            if let Err(e) = state.storage.delete(&stored_path).await {
+                warn!(error = ?e, key = %stored_path, "Failed to clean up object after aborted upload");
+            }
+            return Err(e);
+        }
+    };
+
     debug!("Committing database transaction");
     tx.commit().await.map_err(|err| {
         warn!(error = ?err, "Failed to commit transaction");
         AppError::Db(err)
     })?;
 
-    if let Err(e) = log_upload(
-        &state.pool,
-        current_user.id.to_string(),
-        document.id,
-        next_version_number,
-        Some(json!({
-            "file_name": &file_name,
-            "file_size": file_size,
-            "mime_type": &mime_type,
-            "checksum": &checksum,
-            "metadata_count": metadata_count,
-        })),
-    )
-    .await
-    {
-        // Log the error but don't fail the upload operation
-        // Audit logging should not break the main functionality
-        warn!(
-            error = ?e,
-            document_id = %document.id,
-            user_id = %current_user.id,
-            "Failed to create audit log for upload"
-        );
+    log_deferred(state, deferred_audit).await;
+
+    // Let watchers know a new version landed; like auditing, this must not
+    // fail the upload itself.
+    if document_id.is_some() {
+        if let Err(e) = notify_watchers(
+            &state.pool,
+            document.id,
+            next_version_number,
+            current_user.id,
+            &format!(
+                "New version {} of \"{}\" uploaded by {}",
+                next_version_number, document.title, current_user.username
+            ),
+        )
+        .await
+        {
+            warn!(
+                error = ?e,
+                document_id = %document.id,
+                "Failed to notify document watchers"
+            );
+        }
     }
 
     let response = UploadResponse {
@@ -500,5 +721,29 @@ async fn upload_file(
         "File uploaded successfully"
     );
 
-    Ok(Json(response))
+    // Usage warnings are advisory; failing to compute one must not fail the upload
+    let mut headers = HeaderMap::new();
+    match storage_usage_warning(state, current_user).await {
+        Ok(Some(warning)) => {
+            headers.insert("x-storage-usage-pct", HeaderValue::from(warning.usage_pct));
+            let text = format!(
+                "199 - \"{} storage {}% full ({} of {} bytes)\"",
+                warning.scope, warning.usage_pct, warning.used_bytes, warning.limit_bytes
+            );
+            if let Ok(value) = HeaderValue::from_str(&text) {
+                headers.insert(header::WARNING, value);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!(error = ?e, "Failed to compute storage usage"),
+    }
+
+    Ok((headers, response))
+}
+
+/// Map a multipart read failure (truncated body, broken boundary, client
+/// disconnect) to a 400 rather than letting the upload go through half-parsed
+fn malformed_upload(e: axum::extract::multipart::MultipartError) -> AppError {
+    warn!(error = %e, "Failed to read multipart field");
+    AppError::BadRequest("malformed upload")
 }
@@ -0,0 +1,327 @@
+use crate::audit::{log_deferred, log_in_tx};
+use crate::auth::{check_permission, CurrentUser, Role, StorageAction, TEST_USER_PREFIX};
+use crate::dtos::{CreateUserRequest, CreateUserResponse, ListUsersResponse, UpdateUserRoleRequest, UserInfo};
+use crate::error::AppError;
+use crate::models::{AuditAction, NewAuditLog};
+use crate::password::{generate_password, hash_password, validate_password};
+use crate::response::capped_json;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::{routing::{delete, get, patch}, Json, Router};
+use sqlx::{Postgres, Transaction};
+use tracing::info;
+use uuid::Uuid;
+
+/// Matches `users.username VARCHAR(100)`
+const MAX_USERNAME_LEN: usize = 100;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/:id/role", patch(update_user_role))
+        .route("/users/:id", delete(delete_user))
+}
+
+fn validate_role(role: &str) -> Result<Role, AppError> {
+    role.parse()
+        .map_err(|_| AppError::BadRequest("Role must be viewer, editor or admin"))
+}
+
+/// Refuse a change that would leave no admin able to manage users. Locks the
+/// admin rows so two concurrent demotions can't both pass.
+async fn ensure_other_admin(tx: &mut Transaction<'_, Postgres>, user_id: Uuid) -> Result<(), AppError> {
+    let others: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM users WHERE role = 'admin' AND id <> $1 FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    if others.is_empty() {
+        return Err(AppError::Conflict("Cannot remove the last admin"));
+    }
+    Ok(())
+}
+
+/// All accounts, oldest first. API keys and passwords are never returned.
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "All users", body = ListUsersResponse),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_users(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let users = sqlx::query_as::<_, UserInfo>(
+        "SELECT id, username, role, created_at FROM users ORDER BY created_at, username",
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let total = users.len();
+    capped_json(
+        "users.list",
+        state.config.max_response_bytes,
+        &ListUsersResponse { users, total },
+    )
+}
+
+/// Create an account with a generated API key. The key, and the password if
+/// none was supplied, are only returned in this response.
+#[utoipa::path(
+    post,
+    path = "/users",
+    tag = "users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "User created", body = CreateUserResponse),
+        (status = 400, description = "Invalid username, role or password"),
+        (status = 409, description = "Username already taken"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn create_user(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<CreateUserResponse>), AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let username = request.username.trim();
+    if username.is_empty() {
+        return Err(AppError::BadRequest("Username cannot be empty"));
+    }
+    if username.chars().count() > MAX_USERNAME_LEN {
+        return Err(AppError::BadRequest("Username is longer than 100 characters"));
+    }
+    if username.starts_with(TEST_USER_PREFIX) {
+        return Err(AppError::BadRequest("Usernames starting with ~test- are reserved"));
+    }
+    let role = validate_role(&request.role)?;
+
+    let min_len = state.config.password_min_length;
+    let (password, generated) = match request.password {
+        Some(password) => {
+            validate_password(&password, min_len)?;
+            (password, false)
+        }
+        None => (generate_password(min_len), true),
+    };
+    let hashed = hash_password(&password)?;
+    let api_key = Uuid::new_v4().to_string();
+
+    let mut tx = state.pool.begin().await?;
+
+    let user = sqlx::query_as::<_, UserInfo>(
+        r#"
+        INSERT INTO users (username, api_key, password, role)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING id, username, role, created_at
+        "#,
+    )
+    .bind(username)
+    .bind(&api_key)
+    .bind(&hashed)
+    .bind(role)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::Conflict("Username already taken"))?;
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::CreateUser,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({
+                "target_user_id": user.id,
+                "username": user.username,
+                "role": user.role,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(admin_id = %current_user.id, user_id = %user.id, role = %user.role, "User created");
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateUserResponse {
+            user,
+            api_key,
+            generated_password: generated.then_some(password),
+        }),
+    ))
+}
+
+/// Change a user's role. Takes effect on the user's next request; bearer
+/// tokens issued before the change are refused, so the user logs in again.
+#[utoipa::path(
+    patch,
+    path = "/users/{id}/role",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "User ID")),
+    request_body = UpdateUserRoleRequest,
+    responses(
+        (status = 200, description = "Role updated", body = UserInfo),
+        (status = 400, description = "Invalid role"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Would leave no admin"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn update_user_role(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(user_id): Path<Uuid>,
+    Json(request): Json<UpdateUserRoleRequest>,
+) -> Result<Json<UserInfo>, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let role = validate_role(&request.role)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    // Read as text so a row with a broken role can still be repaired here
+    let previous: String = sqlx::query_scalar("SELECT role FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::Db)?
+        .ok_or(AppError::NotFound("User not found"))?;
+
+    if previous == Role::Admin.as_str() && role != Role::Admin {
+        ensure_other_admin(&mut tx, user_id).await?;
+    }
+
+    let user = sqlx::query_as::<_, UserInfo>(
+        "UPDATE users SET role = $2 WHERE id = $1 RETURNING id, username, role, created_at",
+    )
+    .bind(user_id)
+    .bind(role)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::Db)?;
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::UpdateUserRole,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({
+                "target_user_id": user_id,
+                "before": previous,
+                "after": role,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(admin_id = %current_user.id, user_id = %user_id, before = %previous, after = %role, "User role updated");
+
+    Ok(Json(user))
+}
+
+/// Delete an account. Its API key and bearer tokens stop working
+/// immediately; documents and versions it created stay, with their creator
+/// set to NULL.
+#[utoipa::path(
+    delete,
+    path = "/users/{id}",
+    tag = "users",
+    params(("id" = Uuid, Path, description = "User ID")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Would leave no admin"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    check_permission(&current_user, StorageAction::Admin)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let user = sqlx::query_as::<_, UserInfo>(
+        "SELECT id, username, role, created_at FROM users WHERE id = $1 FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("User not found"))?;
+
+    if user.role == Role::Admin {
+        ensure_other_admin(&mut tx, user_id).await?;
+    }
+
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::DeleteUser,
+            document_id: None,
+            document_version: None,
+            metadata: serde_json::json!({
+                "target_user_id": user_id,
+                "username": user.username,
+                "role": user.role,
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(admin_id = %current_user.id, user_id = %user_id, username = %user.username, "User deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
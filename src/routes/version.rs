@@ -0,0 +1,30 @@
+use crate::dtos::VersionResponse;
+use crate::state::AppState;
+use axum::{routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/version", get(version))
+}
+
+/// Build information for confirming which release is deployed. No auth required.
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "system",
+    responses(
+        (status = 200, description = "Server build information", body = VersionResponse)
+    )
+)]
+pub async fn version() -> Json<VersionResponse> {
+    let built_at = env!("DMS_BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0));
+
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("DMS_GIT_COMMIT"),
+        built_at,
+    })
+}
@@ -0,0 +1,162 @@
+use crate::audit::{log_deferred, log_in_tx};
+use crate::auth::{check_permission, CurrentUser, Role, StorageAction};
+use crate::dtos::{SetVisibilityRequest, VisibilityResponse};
+use crate::error::AppError;
+use crate::models::{AuditAction, Document, NewAuditLog};
+use crate::routes::documents::{resolve_version, serve_version};
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::{routing::{get, post}, Json, Router};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Audit `user_id` recorded for unauthenticated downloads
+pub const ANONYMOUS_USER: &str = "anonymous";
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/documents/:id/visibility", post(set_visibility))
+        .route("/public/documents/:id/content", get(download_public_document))
+}
+
+/// Whether a non-deleted document is public; 404 when it doesn't exist
+pub async fn is_public_document(state: &AppState, document_id: Uuid) -> Result<bool, AppError> {
+    let is_public: Option<bool> = sqlx::query_scalar(
+        "SELECT is_public FROM documents WHERE id = $1 AND deleted_at IS NULL"
+    )
+    .bind(document_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    is_public.ok_or(AppError::NotFound("Document not found or has been deleted"))
+}
+
+/// Only the owner or an admin may publish/unpublish a document
+pub fn check_visibility_permission(user: &CurrentUser, document: &Document) -> Result<(), AppError> {
+    if user.role == Role::Admin || document.created_by == Some(user.id) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(
+            "Permission denied: owner or admin access required",
+        ))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/visibility",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    request_body = SetVisibilityRequest,
+    responses(
+        (status = 200, description = "Visibility updated", body = VisibilityResponse),
+        (status = 403, description = "Permission denied - owner or admin required"),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn set_visibility(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+    Json(request): Json<SetVisibilityRequest>,
+) -> Result<Json<VisibilityResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Write)?;
+
+    let mut tx = state.pool.begin().await?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT id, title, category, created_by, is_public, deleted_at, created_at, updated_at
+        FROM documents
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+    )
+    .bind(document_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::Db)?
+    .ok_or(AppError::NotFound("Document not found or has been deleted"))?;
+
+    if let Err(e) = check_visibility_permission(&current_user, &document) {
+        warn!(
+            user_id = %current_user.id,
+            document_id = %document_id,
+            "Visibility change denied: not owner or admin"
+        );
+        return Err(e);
+    }
+
+    sqlx::query("UPDATE documents SET is_public = $1 WHERE id = $2")
+        .bind(request.is_public)
+        .bind(document_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::Db)?;
+
+    // With AUDIT_FAIL_CLOSED a failed audit write rolls the change back
+    let deferred_audit = log_in_tx(
+        &state,
+        &mut tx,
+        NewAuditLog {
+            user_id: current_user.id.to_string(),
+            action: AuditAction::UpdateMetadata,
+            document_id: Some(document_id),
+            document_version: None,
+            metadata: serde_json::json!({
+                "change": "visibility",
+                "before": { "is_public": document.is_public },
+                "after": { "is_public": request.is_public },
+            }),
+        },
+    )
+    .await?;
+
+    tx.commit().await.map_err(AppError::Db)?;
+
+    log_deferred(&state, deferred_audit).await;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        is_public = request.is_public,
+        "Document visibility updated"
+    );
+
+    Ok(Json(VisibilityResponse {
+        document_id,
+        is_public: request.is_public,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/public/documents/{id}/content",
+    tag = "documents",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Latest version content", content_type = "application/octet-stream"),
+        (status = 401, description = "Document is not public"),
+        (status = 404, description = "Document not found")
+    )
+)]
+pub async fn download_public_document(
+    State(state): State<AppState>,
+    Path(document_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    if !is_public_document(&state, document_id).await? {
+        return Err(AppError::Unauthorized("Document is not public"));
+    }
+
+    let dv = resolve_version(&state, &state.pool, document_id, None).await?;
+
+    serve_version(&state, dv, ANONYMOUS_USER.to_string(), true, None).await
+}
@@ -0,0 +1,162 @@
+use crate::auth::{check_permission, CurrentUser, StorageAction};
+use crate::dtos::{NotificationsResponse, WatchResponse};
+use crate::error::AppError;
+use crate::response::capped_json;
+use crate::models::Notification;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::{routing::{get, post}, Json, Router};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/documents/:id/watch", post(watch_document).delete(unwatch_document))
+        .route("/me/notifications", get(list_notifications))
+}
+
+#[utoipa::path(
+    post,
+    path = "/documents/{id}/watch",
+    tag = "watches",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "Now watching the document", body = WatchResponse),
+        (status = 404, description = "Document not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn watch_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<WatchResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let document_exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NULL)"
+    )
+    .bind(document_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    if !document_exists {
+        return Err(AppError::NotFound("Document not found or has been deleted"));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO document_watches (user_id, document_id)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id, document_id) DO NOTHING
+        "#,
+    )
+    .bind(current_user.id)
+    .bind(document_id)
+    .execute(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        "User is now watching document"
+    );
+
+    Ok(Json(WatchResponse {
+        document_id,
+        watching: true,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/documents/{id}/watch",
+    tag = "watches",
+    params(
+        ("id" = Uuid, Path, description = "Document ID")
+    ),
+    responses(
+        (status = 200, description = "No longer watching the document", body = WatchResponse),
+        (status = 404, description = "Not watching this document"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn unwatch_document(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<WatchResponse>, AppError> {
+    check_permission(&current_user, StorageAction::Read)?;
+
+    let rows_affected = sqlx::query(
+        "DELETE FROM document_watches WHERE user_id = $1 AND document_id = $2"
+    )
+    .bind(current_user.id)
+    .bind(document_id)
+    .execute(&state.pool)
+    .await
+    .map_err(AppError::Db)?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(AppError::NotFound("Not watching this document"));
+    }
+
+    info!(
+        user_id = %current_user.id,
+        document_id = %document_id,
+        "User stopped watching document"
+    );
+
+    Ok(Json(WatchResponse {
+        document_id,
+        watching: false,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/notifications",
+    tag = "watches",
+    responses(
+        (status = 200, description = "Notifications for the current user", body = NotificationsResponse),
+        (status = 413, description = "Response exceeds MAX_RESPONSE_BYTES"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("api_key" = []))
+)]
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Response, AppError> {
+    debug!(user_id = %current_user.id, "Listing notifications");
+
+    let notifications = sqlx::query_as::<_, Notification>(
+        r#"
+        SELECT id, user_id, document_id, document_version, message, created_at
+        FROM notifications
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(current_user.id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(AppError::Db)?;
+
+    let total = notifications.len();
+
+    let response = NotificationsResponse {
+        data: notifications,
+        total,
+    };
+
+    capped_json("watches.list_notifications", state.config.max_response_bytes, &response)
+}
@@ -0,0 +1,102 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Message covered by a signed link: the document and the expiry (unix seconds)
+fn signed_message(document_id: Uuid, expires_at: i64) -> String {
+    format!("{}:{}", document_id, expires_at)
+}
+
+/// Hex HMAC-SHA256 signature for a download link
+pub fn sign_link(secret: &str, document_id: Uuid, expires_at: i64) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(signed_message(document_id, expires_at).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Why a signed link was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkError {
+    Expired,
+    BadSignature,
+}
+
+/// Check a link's signature (constant time) and expiry against `now` (unix seconds)
+pub fn verify_link(
+    secret: &str,
+    document_id: Uuid,
+    expires_at: i64,
+    signature: &str,
+    now: i64,
+) -> Result<(), LinkError> {
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return Err(LinkError::BadSignature);
+    };
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(signed_message(document_id, expires_at).as_bytes());
+    mac.verify_slice(&signature).map_err(|_| LinkError::BadSignature)?;
+
+    if expires_at <= now {
+        return Err(LinkError::Expired);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+    const NOW: i64 = 1_700_000_000;
+
+    fn document() -> Uuid {
+        Uuid::from_u128(0x1234)
+    }
+
+    #[test]
+    fn good_signature_verifies() {
+        let exp = NOW + 60;
+        let sig = sign_link(SECRET, document(), exp);
+        assert_eq!(verify_link(SECRET, document(), exp, &sig, NOW), Ok(()));
+    }
+
+    #[test]
+    fn tampered_expiry_is_rejected() {
+        let exp = NOW + 60;
+        let sig = sign_link(SECRET, document(), exp);
+        assert_eq!(
+            verify_link(SECRET, document(), exp + 3600, &sig, NOW),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn tampered_document_is_rejected() {
+        let exp = NOW + 60;
+        let sig = sign_link(SECRET, document(), exp);
+        assert_eq!(
+            verify_link(SECRET, Uuid::from_u128(0x5678), exp, &sig, NOW),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn non_hex_signature_is_rejected() {
+        assert_eq!(
+            verify_link(SECRET, document(), NOW + 60, "not-hex", NOW),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn link_expiring_now_is_expired() {
+        let sig = sign_link(SECRET, document(), NOW);
+        assert_eq!(verify_link(SECRET, document(), NOW, &sig, NOW), Err(LinkError::Expired));
+    }
+}
@@ -1,8 +1,18 @@
+use std::sync::Arc;
+
 use sqlx::PgPool;
 use opendal::Operator;
 
+use crate::config::Config;
+use crate::jobs::JobRegistry;
+
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
+    /// Pool for read-heavy queries; same as `pool` unless DATABASE_READ_URL is set
+    pub read_pool: PgPool,
     pub storage: Operator,
-}
\ No newline at end of file
+    pub config: Arc<Config>,
+    /// Background admin jobs, see `GET /admin/jobs`
+    pub jobs: JobRegistry,
+}
@@ -0,0 +1,243 @@
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// How version objects are laid out in storage (KEY_STRATEGY)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStrategy {
+    /// `{category}/{document_id}/v{n}`, `Uncategorized` when there is no category (default)
+    Category,
+    /// `{sanitized_title}/{document_id}/v{n}`
+    TitleBased,
+}
+
+impl FromStr for KeyStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "category" => Ok(KeyStrategy::Category),
+            "title-based" | "title" => Ok(KeyStrategy::TitleBased),
+            other => Err(format!("unknown key strategy: {}", other)),
+        }
+    }
+}
+
+/// Where objects are stored (STORAGE_BACKEND)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// SeaweedFS through its S3 API (default)
+    S3,
+    /// Local directory at UPLOAD_DIR, for development without SeaweedFS
+    Fs,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "s3" => Ok(StorageBackend::S3),
+            "fs" => Ok(StorageBackend::Fs),
+            other => Err(format!("unknown storage backend: {}", other)),
+        }
+    }
+}
+
+/// Top-level prefixes that keep each feature's objects apart (KEY_NAMESPACES),
+/// so e.g. a folder named like a document id can never shadow a version key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyNamespace {
+    /// Document version content
+    Versions,
+    /// Folder markers (`.folder_metadata.json`)
+    Folders,
+    /// Chunks of resumable uploads that have not been completed yet
+    Uploads,
+}
+
+impl KeyNamespace {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            KeyNamespace::Versions => "versions/",
+            KeyNamespace::Folders => "folders/",
+            KeyNamespace::Uploads => "uploads/",
+        }
+    }
+
+    /// Place `key` in this namespace, or leave it at the bucket root when
+    /// namespacing is disabled
+    pub fn key(self, namespaced: bool, key: &str) -> String {
+        if namespaced {
+            format!("{}{}", self.prefix(), key)
+        } else {
+            key.to_string()
+        }
+    }
+}
+
+/// Directory that folders are listed from
+pub fn folders_root(namespaced: bool) -> &'static str {
+    if namespaced {
+        KeyNamespace::Folders.prefix()
+    } else {
+        ""
+    }
+}
+
+/// Key of a folder's `.folder_metadata.json` marker
+pub fn folder_metadata_key(namespaced: bool, folder_name: &str) -> String {
+    KeyNamespace::Folders.key(namespaced, &format!("{}/.folder_metadata.json", folder_name))
+}
+
+/// Make a string safe to use as a single storage path segment
+pub fn sanitize_segment(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+}
+
+/// Storage key for a document version under the given strategy
+pub fn version_key(
+    strategy: KeyStrategy,
+    namespaced: bool,
+    category: Option<&str>,
+    title: &str,
+    document_id: Uuid,
+    version_number: i32,
+) -> String {
+    let prefix = match strategy {
+        // for using the folders structure in the seaweed
+        KeyStrategy::Category => category
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "Uncategorized".to_string()),
+        KeyStrategy::TitleBased => {
+            let title = sanitize_segment(title.trim());
+            if title.is_empty() {
+                "Untitled".to_string()
+            } else {
+                title
+            }
+        }
+    };
+
+    KeyNamespace::Versions.key(
+        namespaced,
+        &format!("{}/{}/v{}", prefix, document_id, version_number),
+    )
+}
+
+/// Up to the first `max_bytes` of an object. The range is clamped to the
+/// object's size first, since not every backend tolerates reading past the end.
+pub async fn read_prefix(storage: &opendal::Operator, key: &str, max_bytes: u64) -> opendal::Result<Vec<u8>> {
+    let len = storage.stat(key).await?.content_length().min(max_bytes);
+    Ok(storage.read_with(key).range(0..len).await?.to_vec())
+}
+
+/// Read an object back right after writing it and check it has the expected
+/// size and SHA-256 (VERIFY_WRITES). Some backends acknowledge writes they
+/// have not persisted; this catches them before the version row is committed.
+pub async fn verify_written(
+    storage: &opendal::Operator,
+    key: &str,
+    expected_size: u64,
+    expected_checksum: &str,
+) -> Result<(), AppError> {
+    let meta = storage.stat(key).await.map_err(|e| {
+        warn!(error = ?e, key = %key, "Written object not found on read-back");
+        AppError::BadGateway("storage did not persist the uploaded object")
+    })?;
+    if meta.content_length() != expected_size {
+        warn!(
+            key = %key,
+            expected = expected_size,
+            actual = meta.content_length(),
+            "Written object has the wrong size"
+        );
+        return Err(AppError::BadGateway("stored object size does not match the upload"));
+    }
+
+    // Hash the object as it streams in rather than holding a copy of it
+    let mut stream = storage
+        .reader(key)
+        .await
+        .map_err(|e| read_back_failed(key, e))?
+        .into_bytes_stream(..)
+        .await
+        .map_err(|e| read_back_failed(key, e))?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.try_next().await.map_err(|e| read_back_failed(key, e))? {
+        hasher.update(&chunk);
+    }
+    let actual = hex::encode(hasher.finalize());
+    if actual != expected_checksum {
+        warn!(key = %key, expected = %expected_checksum, actual = %actual, "Written object has the wrong checksum");
+        return Err(AppError::BadGateway("stored object checksum does not match the upload"));
+    }
+
+    Ok(())
+}
+
+fn read_back_failed(key: &str, error: impl std::fmt::Debug) -> AppError {
+    warn!(error = ?error, key = %key, "Written object could not be read back");
+    AppError::BadGateway("storage did not persist the uploaded object")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMESPACES: [KeyNamespace; 3] = [
+        KeyNamespace::Versions,
+        KeyNamespace::Folders,
+        KeyNamespace::Uploads,
+    ];
+
+    #[test]
+    fn namespace_prefixes_are_disjoint() {
+        for a in NAMESPACES {
+            for b in NAMESPACES {
+                if a != b {
+                    assert!(!a.prefix().starts_with(b.prefix()), "{:?} nests in {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn folder_and_version_keys_never_overlap() {
+        let document_id = Uuid::from_u128(42);
+        // Names chosen to mimic the other namespace or a version path
+        let names = [
+            "Uncategorized".to_string(),
+            "versions".to_string(),
+            "folders".to_string(),
+            format!("Uncategorized/{}", document_id),
+            format!("versions/Uncategorized/{}/v1", document_id),
+        ];
+
+        for strategy in [KeyStrategy::Category, KeyStrategy::TitleBased] {
+            for name in &names {
+                let version = version_key(strategy, true, Some(name), name, document_id, 1);
+                let folder = folder_metadata_key(true, name);
+                assert!(version.starts_with(KeyNamespace::Versions.prefix()), "{}", version);
+                assert!(folder.starts_with(KeyNamespace::Folders.prefix()), "{}", folder);
+                for other in &names {
+                    assert_ne!(version, folder_metadata_key(true, other));
+                }
+            }
+        }
+    }
+}
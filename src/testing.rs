@@ -0,0 +1,234 @@
+//! Fixtures for integration tests, compiled only with the `test-helpers`
+//! feature. API keys are derived from a seed so a test can recompute the key
+//! of a user it seeded without threading it through.
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::models::{Document, DocumentVersion, User};
+use crate::state::AppState;
+use crate::storage::version_key;
+use opendal::raw::{
+    Access, Layer, LayeredAccess, OpBatch, OpCopy, OpDelete, OpList, OpRead, OpWrite, RpBatch, RpCopy,
+    RpDelete, RpList, RpRead, RpWrite,
+};
+use opendal::Operator;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Deterministic API key for a fixture user
+pub fn fixture_api_key(seed: &str) -> String {
+    hex::encode(Sha256::digest(format!("dms-fixture:{}", seed).as_bytes()))
+}
+
+/// In-memory OpenDAL operator; contents vanish with the operator
+pub fn memory_storage() -> Result<Operator, AppError> {
+    Ok(Operator::new(opendal::services::Memory::default())?.finish())
+}
+
+/// Filesystem operator over a fresh directory under the system temp dir, for
+/// tests that need operations the memory service lacks, such as `copy`
+pub fn temp_dir_storage() -> Result<Operator, AppError> {
+    let root = std::env::temp_dir().join(format!("dms-test-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&root)?;
+    let builder = opendal::services::Fs::default().root(&root.to_string_lossy());
+    Ok(Operator::new(builder)?.finish())
+}
+
+/// Failures [`faulty_storage`] injects into an operator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageFaults {
+    /// Every delete fails
+    pub fail_deletes: bool,
+    /// Every copy fails
+    pub fail_copies: bool,
+    /// Writes report success but the object never shows up under its key,
+    /// like a backend acknowledging a write it did not persist
+    pub forget_writes: bool,
+}
+
+/// Wrap `storage` so the operations picked in `faults` misbehave
+pub fn faulty_storage(storage: Operator, faults: StorageFaults) -> Operator {
+    storage.layer(FaultLayer(faults))
+}
+
+struct FaultLayer(StorageFaults);
+
+impl<A: Access> Layer<A> for FaultLayer {
+    type LayeredAccess = FaultAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        FaultAccessor { inner, faults: self.0 }
+    }
+}
+
+#[derive(Debug)]
+struct FaultAccessor<A> {
+    inner: A,
+    faults: StorageFaults,
+}
+
+fn injected(operation: &str) -> opendal::Error {
+    opendal::Error::new(opendal::ErrorKind::Unexpected, format!("{} failed (injected fault)", operation))
+}
+
+impl<A: Access> LayeredAccess for FaultAccessor<A> {
+    type Inner = A;
+    type Reader = A::Reader;
+    type BlockingReader = A::BlockingReader;
+    type Writer = A::Writer;
+    type BlockingWriter = A::BlockingWriter;
+    type Lister = A::Lister;
+    type BlockingLister = A::BlockingLister;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        self.inner.read(path, args).await
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        if self.faults.forget_writes {
+            return self.inner.write(&format!(".forgotten/{}", path), args).await;
+        }
+        self.inner.write(path, args).await
+    }
+
+    async fn copy(&self, from: &str, to: &str, args: OpCopy) -> opendal::Result<RpCopy> {
+        if self.faults.fail_copies {
+            return Err(injected("copy"));
+        }
+        self.inner.copy(from, to, args).await
+    }
+
+    async fn delete(&self, path: &str, args: OpDelete) -> opendal::Result<RpDelete> {
+        if self.faults.fail_deletes {
+            return Err(injected("delete"));
+        }
+        self.inner.delete(path, args).await
+    }
+
+    async fn batch(&self, args: OpBatch) -> opendal::Result<RpBatch> {
+        if self.faults.fail_deletes {
+            return Err(injected("batch delete"));
+        }
+        self.inner.batch(args).await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        self.inner.list(path, args).await
+    }
+
+    fn blocking_read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::BlockingReader)> {
+        self.inner.blocking_read(path, args)
+    }
+
+    fn blocking_write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::BlockingWriter)> {
+        self.inner.blocking_write(path, args)
+    }
+
+    fn blocking_list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::BlockingLister)> {
+        self.inner.blocking_list(path, args)
+    }
+}
+
+/// App state over `pool` with in-memory storage and config from the environment
+pub fn test_state(pool: PgPool) -> Result<AppState, AppError> {
+    test_state_with(pool, |_| {})
+}
+
+/// Like [`test_state`], with `configure` applied to the config so a test can
+/// change a setting without touching the process environment
+pub fn test_state_with(pool: PgPool, configure: impl FnOnce(&mut Config)) -> Result<AppState, AppError> {
+    let mut config = Config::from_env();
+    configure(&mut config);
+    Ok(AppState {
+        read_pool: pool.clone(),
+        pool,
+        storage: memory_storage()?,
+        config: Arc::new(config),
+        jobs: Default::default(),
+    })
+}
+
+/// Insert (or re-role) a user whose API key is `fixture_api_key(username)`
+pub async fn seed_user(pool: &PgPool, username: &str, role: &str) -> Result<User, AppError> {
+    sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (username, api_key, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO UPDATE SET api_key = EXCLUDED.api_key, role = EXCLUDED.role
+        RETURNING id, username, api_key, password, role, created_at
+        "#,
+    )
+    .bind(username)
+    .bind(fixture_api_key(username))
+    .bind(role)
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::Db)
+}
+
+/// Create a document with one version per entry of `versions`, writing the
+/// bytes to storage under the configured key layout
+pub async fn seed_document(
+    state: &AppState,
+    owner: &User,
+    title: &str,
+    category: Option<&str>,
+    versions: &[&[u8]],
+) -> Result<(Document, Vec<DocumentVersion>), AppError> {
+    let mut tx = state.pool.begin().await?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        INSERT INTO documents (title, category, created_by)
+        VALUES ($1, $2, $3)
+        RETURNING id, title, category, created_by, is_public, created_at, updated_at, deleted_at
+        "#,
+    )
+    .bind(title)
+    .bind(category)
+    .bind(owner.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mut rows = Vec::with_capacity(versions.len());
+    for (index, bytes) in versions.iter().enumerate() {
+        let version_number = index as i32 + 1;
+        let key = version_key(
+            state.config.key_strategy,
+            state.config.key_namespaces,
+            category,
+            title,
+            document.id,
+            version_number,
+        );
+        state.storage.write(&key, bytes.to_vec()).await?;
+
+        let row = sqlx::query_as::<_, DocumentVersion>(
+            r#"
+            INSERT INTO document_versions
+            (document_id, version_number, file_name, file_path, file_size, mime_type, checksum)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, document_id, version_number, file_name, file_path, file_size, mime_type, checksum, created_at
+            "#,
+        )
+        .bind(document.id)
+        .bind(version_number)
+        .bind(format!("{}-v{}.bin", title, version_number))
+        .bind(&key)
+        .bind(bytes.len() as i64)
+        .bind(crate::mime::OCTET_STREAM)
+        .bind(hex::encode(Sha256::digest(bytes)))
+        .fetch_one(&mut *tx)
+        .await?;
+        rows.push(row);
+    }
+
+    tx.commit().await?;
+
+    Ok((document, rows))
+}
@@ -0,0 +1,65 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Invisible characters that make otherwise identical names compare unequal
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' // zero width space
+            | '\u{200C}' // zero width non-joiner
+            | '\u{200D}' // zero width joiner
+            | '\u{2060}' // word joiner
+            | '\u{FEFF}' // byte order mark / zero width no-break space
+    )
+}
+
+/// Normalize user-entered names (titles, categories): NFC-compose, drop
+/// control and zero-width characters and trim surrounding whitespace, so
+/// names that look the same are stored the same (NORMALIZE_TEXT).
+pub fn normalize_name(input: &str) -> String {
+    input
+        .nfc()
+        .filter(|c| !c.is_control() && !is_zero_width(*c))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_name;
+
+    #[test]
+    fn composes_to_nfc() {
+        // "e" followed by a combining acute accent becomes a single "é"
+        assert_eq!(normalize_name("Cafe\u{301}"), "Caf\u{e9}");
+        assert_eq!(normalize_name("Caf\u{e9}"), "Caf\u{e9}");
+    }
+
+    #[test]
+    fn does_not_apply_compatibility_folding() {
+        // NFKC would turn the ligature into "fi" and the fullwidth digit into "1"
+        assert_eq!(normalize_name("\u{FB01}le \u{FF11}"), "\u{FB01}le \u{FF11}");
+    }
+
+    #[test]
+    fn keeps_case() {
+        assert_eq!(normalize_name("Quarterly REPORT"), "Quarterly REPORT");
+    }
+
+    #[test]
+    fn trims_but_does_not_collapse_whitespace() {
+        assert_eq!(normalize_name("  Annual  Report \u{a0}"), "Annual  Report");
+    }
+
+    #[test]
+    fn drops_control_and_zero_width_characters() {
+        assert_eq!(normalize_name("Re\u{200B}port\u{7}"), "Report");
+        assert_eq!(normalize_name("\u{FEFF}Invoice"), "Invoice");
+    }
+
+    #[test]
+    fn empty_and_blank_input_become_empty() {
+        assert_eq!(normalize_name(""), "");
+        assert_eq!(normalize_name(" \u{200B} "), "");
+    }
+}
@@ -0,0 +1,69 @@
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::{CurrentUser, PermissionMatrix, Role};
+use crate::error::AppError;
+
+/// Claims carried by an access token issued at login
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// User id
+    pub sub: Uuid,
+    pub username: String,
+    pub role: Role,
+    /// Issued at (unix seconds)
+    pub iat: i64,
+    /// Expiry (unix seconds)
+    pub exp: i64,
+}
+
+/// Sign an HS256 access token for `user`, valid until `expires_at` (unix seconds)
+pub fn issue_token(
+    secret: &str,
+    user: &CurrentUser,
+    issued_at: i64,
+    expires_at: i64,
+) -> Result<String, AppError> {
+    let claims = Claims {
+        sub: user.id,
+        username: user.username.clone(),
+        role: user.role,
+        iat: issued_at,
+        exp: expires_at,
+    };
+
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|e| AppError::Other(anyhow::anyhow!("failed to sign access token: {}", e)))
+}
+
+/// Check signature and expiry of a bearer token; no database access, so the
+/// caller still has to confirm the account and its role
+pub fn verify_token(
+    secret: &str,
+    token: &str,
+    permissions: Arc<PermissionMatrix>,
+) -> Result<CurrentUser, AppError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+
+    let data = decode::<Claims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)
+        .map_err(|e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
+                AppError::Unauthorized("Access token has expired")
+            }
+            _ => AppError::Unauthorized("Invalid access token"),
+        })?;
+
+    Ok(CurrentUser {
+        id: data.claims.sub,
+        username: data.claims.username,
+        role: data.claims.role,
+        permissions,
+    })
+}
@@ -0,0 +1,3186 @@
+//! End-to-end checks of the HTTP routes over the in-memory storage fixture.
+//! Most tests need a migrated database at DATABASE_URL and are skipped when
+//! that is unset; the middleware tests use a pool that never connects. Tests
+//! share the database and run concurrently, so each one seeds its own
+//! uniquely named users and documents.
+
+use axum::body::{to_bytes, Body, Bytes};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use rust_dms::auth::{check_permission, Role, StorageAction};
+use rust_dms::models::User;
+use rust_dms::testing::{
+    faulty_storage, fixture_api_key, memory_storage, seed_document, seed_user, temp_dir_storage, test_state,
+    test_state_with, StorageFaults,
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const BOUNDARY: &str = "dms-smoke-boundary";
+
+/// Pool for DATABASE_URL, or None when the smoke tests should be skipped
+async fn database() -> Option<PgPool> {
+    let Ok(database_url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL is not set; skipping smoke test");
+        return None;
+    };
+    Some(PgPool::connect(&database_url).await.expect("connect to DATABASE_URL"))
+}
+
+/// `prefix` plus a random suffix, for names that must not collide across tests
+fn unique(prefix: &str) -> String {
+    format!("{}-{}", prefix, Uuid::new_v4().simple())
+}
+
+/// Seed a user with a unique name and return it with its API key
+async fn user(pool: &PgPool, role: &str) -> (User, String) {
+    let user = seed_user(pool, &unique(&format!("smoke-{}", role)), role)
+        .await
+        .expect("seed user");
+    let api_key = fixture_api_key(&user.username);
+    (user, api_key)
+}
+
+/// Username prefix whose audit rows are rejected once [`reject_audit_rows`] ran
+const AUDIT_FAIL_PREFIX: &str = "smoke-auditfail";
+
+/// Install a trigger failing every audit insert made by a user whose name
+/// starts with [`AUDIT_FAIL_PREFIX`], so the audit write itself can fail
+async fn reject_audit_rows(pool: &PgPool) {
+    let mut tx = pool.begin().await.unwrap();
+    // Concurrent CREATE OR REPLACE of the same objects can conflict
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('smoke_reject_audit'))")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    sqlx::query(&format!(
+        r#"
+        CREATE OR REPLACE FUNCTION smoke_reject_audit() RETURNS trigger AS $$
+        BEGIN
+            IF EXISTS (SELECT 1 FROM users WHERE id::text = NEW.user_id AND username LIKE '{}-%') THEN
+                RAISE EXCEPTION 'audit insert rejected by smoke test';
+            END IF;
+            RETURN NEW;
+        END
+        $$ LANGUAGE plpgsql
+        "#,
+        AUDIT_FAIL_PREFIX
+    ))
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE OR REPLACE TRIGGER smoke_reject_audit BEFORE INSERT ON audit_logs \
+         FOR EACH ROW EXECUTE FUNCTION smoke_reject_audit()",
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+}
+
+/// Seed a user whose audit rows are rejected; see [`reject_audit_rows`]
+async fn audit_failing_user(pool: &PgPool, role: &str) -> (User, String) {
+    reject_audit_rows(pool).await;
+    let user = seed_user(pool, &unique(AUDIT_FAIL_PREFIX), role).await.expect("seed user");
+    let api_key = fixture_api_key(&user.username);
+    (user, api_key)
+}
+
+async fn audit_rows_by(pool: &PgPool, user: &User) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM audit_logs WHERE user_id = $1")
+        .bind(user.id.to_string())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, body)
+}
+
+/// Send `request` and parse the body as JSON (Null when it is empty or not JSON)
+async fn send_json(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let (status, _, body) = send(app, request).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Bodiless request authenticated with `api_key`
+fn request(method: &str, uri: impl AsRef<str>, api_key: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri.as_ref())
+        .header("X-API-Key", api_key)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn get(uri: impl AsRef<str>, api_key: &str) -> Request<Body> {
+    request("GET", uri, api_key)
+}
+
+fn post(uri: impl AsRef<str>, api_key: &str) -> Request<Body> {
+    request("POST", uri, api_key)
+}
+
+fn json_request(method: &str, uri: impl AsRef<str>, api_key: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri.as_ref())
+        .header("X-API-Key", api_key)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn multipart_body(fields: &[(&str, &str)], file_name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n",
+                b = BOUNDARY,
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+             Content-Type: text/plain\r\n\r\n",
+            b = BOUNDARY,
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(contents);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+/// `POST /upload` with the given text fields and a text/plain file part
+fn upload(api_key: &str, fields: &[(&str, &str)], file_name: &str, contents: &[u8]) -> Request<Body> {
+    Request::post("/upload")
+        .header("X-API-Key", api_key)
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(multipart_body(fields, file_name, contents)))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn upload_then_download_round_trips() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool).expect("test state"));
+
+    let contents = b"hello from the smoke test";
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", "smoke")], "smoke.txt", contents)).await;
+    assert_eq!(status, StatusCode::OK);
+    let document_id = uploaded["document_id"].as_str().expect("document_id in response");
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], contents);
+}
+
+#[tokio::test]
+async fn declared_type_the_content_disagrees_with_is_replaced_by_default() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+
+    // Declared text/plain by the multipart helper, but the bytes are a PDF
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", &unique("spoofed"))], "a.txt", b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n")).await;
+    assert_eq!(status, StatusCode::OK, "{}", uploaded);
+    let document_id: Uuid = uploaded["document_id"].as_str().unwrap().parse().unwrap();
+
+    let stored: String = sqlx::query_scalar("SELECT mime_type FROM document_versions WHERE document_id = $1")
+        .bind(document_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, "application/pdf");
+    let declared: Option<String> = sqlx::query_scalar(
+        "SELECT metadata->>'declared_mime_type' FROM audit_logs WHERE user_id = $1 AND document_id = $2",
+    )
+    .bind(editor.id.to_string())
+    .bind(document_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(declared.as_deref(), Some("text/plain"));
+}
+
+#[tokio::test]
+async fn resumable_upload_streams_chunks_into_one_version() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool).expect("test state"));
+
+    let init = serde_json::json!({"title": "chunked smoke", "file_name": "chunked.txt", "metadata": {}});
+    let (status, session) = send_json(&app, json_request("POST", "/uploads/init", &api_key, init)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let upload_id = session["upload_id"].as_str().expect("upload_id in response");
+
+    let chunks: [&[u8]; 2] = [b"first half, ", b"second half"];
+    for (n, chunk) in chunks.iter().enumerate() {
+        let put = Request::put(format!("/uploads/{}/chunk/{}", upload_id, n))
+            .header("X-API-Key", &api_key)
+            .body(Body::from(chunk.to_vec()))
+            .unwrap();
+        let (status, _, _) = send(&app, put).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, uploaded) = send_json(&app, post(format!("/uploads/{}/complete", upload_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    let document_id = uploaded["document_id"].as_str().expect("document_id in response");
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"first half, second half");
+}
+
+#[tokio::test]
+async fn idle_uploads_expire_and_are_swept_with_their_chunks() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let (_, admin_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.upload_session_ttl_secs = 3600).expect("test state");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let init = serde_json::json!({"title": "abandoned", "file_name": "abandoned.txt", "metadata": {}});
+    let (status, session) = send_json(&app, json_request("POST", "/uploads/init", &api_key, init)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let upload_id: Uuid = session["upload_id"].as_str().unwrap().parse().unwrap();
+    let put = Request::put(format!("/uploads/{}/chunk/0", upload_id))
+        .header("X-API-Key", &api_key)
+        .body(Body::from("never completed"))
+        .unwrap();
+    let (status, _, _) = send(&app, put).await;
+    assert_eq!(status, StatusCode::OK);
+    let chunk_prefix = format!("uploads/{}/", upload_id);
+    assert!(!storage.list(&chunk_prefix).await.unwrap().is_empty());
+
+    // Last activity two hours ago, past the one-hour TTL
+    sqlx::query("UPDATE upload_sessions SET created_at = NOW() - INTERVAL '2 hours' WHERE id = $1")
+        .bind(upload_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE upload_chunks SET created_at = NOW() - INTERVAL '2 hours' WHERE upload_id = $1")
+        .bind(upload_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (status, _) = send_json(&app, get(format!("/uploads/{}", upload_id), &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send_json(&app, post(format!("/uploads/{}/complete", upload_id), &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send_json(&app, post("/admin/uploads/expire", &api_key)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send_json(&app, post("/admin/uploads/expire", &admin_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["expired"].as_array().unwrap().contains(&Value::from(upload_id.to_string())));
+
+    let sessions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM upload_sessions WHERE id = $1")
+        .bind(upload_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(sessions, 0);
+    assert!(storage.list(&chunk_prefix).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn chunks_are_refused_once_the_upload_is_being_completed() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+
+    let init = serde_json::json!({"title": "claimed", "file_name": "claimed.txt", "metadata": {}});
+    let (_, session) = send_json(&app, json_request("POST", "/uploads/init", &api_key, init)).await;
+    let upload_id: Uuid = session["upload_id"].as_str().unwrap().parse().unwrap();
+
+    // As `complete` leaves it while it assembles the chunks
+    sqlx::query("UPDATE upload_sessions SET completing_since = NOW() WHERE id = $1")
+        .bind(upload_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let put = Request::put(format!("/uploads/{}/chunk/0", upload_id))
+        .header("X-API-Key", &api_key)
+        .body(Body::from("too late"))
+        .unwrap();
+    let (status, _, _) = send(&app, put).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM upload_chunks WHERE upload_id = $1")
+        .bind(upload_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(chunks, 0);
+}
+
+#[tokio::test]
+async fn stale_completion_claims_are_taken_over_and_expired() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let (_, admin_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| {
+        c.upload_session_ttl_secs = 3600;
+        c.upload_claim_timeout_secs = 600;
+    })
+    .expect("test state");
+    let app = rust_dms::routes::router(state);
+
+    let mut sessions = Vec::new();
+    for _ in 0..2 {
+        let init = serde_json::json!({"title": unique("crashed"), "file_name": "crashed.txt", "metadata": {}});
+        let (_, session) = send_json(&app, json_request("POST", "/uploads/init", &api_key, init)).await;
+        let upload_id: Uuid = session["upload_id"].as_str().unwrap().parse().unwrap();
+        let put = Request::put(format!("/uploads/{}/chunk/0", upload_id))
+            .header("X-API-Key", &api_key)
+            .body(Body::from("assembled once"))
+            .unwrap();
+        let (status, _, _) = send(&app, put).await;
+        assert_eq!(status, StatusCode::OK);
+        sessions.push(upload_id);
+    }
+    let (retried, idle) = (sessions[0], sessions[1]);
+
+    // As a complete that crashed mid-assembly leaves it: claimed long ago
+    sqlx::query("UPDATE upload_sessions SET completing_since = NOW() - INTERVAL '20 minutes' WHERE id = ANY($1)")
+        .bind(&sessions)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (status, body) = send_json(&app, post(format!("/uploads/{}/complete", retried), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Idle past the TTL as well, so the sweep removes it despite the claim
+    sqlx::query("UPDATE upload_sessions SET created_at = NOW() - INTERVAL '2 hours' WHERE id = $1")
+        .bind(idle)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE upload_chunks SET created_at = NOW() - INTERVAL '2 hours' WHERE upload_id = $1")
+        .bind(idle)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, body) = send_json(&app, post("/admin/uploads/expire", &admin_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["expired"].as_array().unwrap().contains(&Value::from(idle.to_string())));
+}
+
+#[tokio::test]
+async fn uploads_to_soft_deleted_documents_are_refused_at_init() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("binned"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    sqlx::query("UPDATE documents SET deleted_at = NOW() WHERE id = $1")
+        .bind(document.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let init = serde_json::json!({"document_id": document.id, "file_name": "late.txt", "metadata": {}});
+    let (status, _) = send_json(&app, json_request("POST", "/uploads/init", &api_key, init)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn public_route_serves_only_public_documents() {
+    let Some(pool) = database().await else { return };
+    let (user, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool).expect("test state");
+    let (document, _) = seed_document(&state, &user, &unique("visibility"), None, &[b"public bytes"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let public_url = format!("/public/documents/{}/content", document.id);
+
+    let (status, _, _) = send(&app, Request::get(&public_url).body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let publish = json_request(
+        "POST",
+        format!("/documents/{}/visibility", document.id),
+        &api_key,
+        serde_json::json!({"is_public": true}),
+    );
+    let (status, _, _) = send(&app, publish).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send(&app, Request::get(&public_url).body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"public bytes");
+}
+
+#[tokio::test]
+async fn new_version_notifies_watchers_but_new_document_does_not() {
+    let Some(pool) = database().await else { return };
+    let (editor, editor_key) = user(&pool, "editor").await;
+    let (_, watcher_key) = user(&pool, "viewer").await;
+    let state = test_state(pool).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("watched"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, post(format!("/documents/{}/watch", document.id), &watcher_key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let document_id = document.id.to_string();
+    let (status, _) = send_json(
+        &app,
+        upload(&editor_key, &[("document_id", &document_id)], "watched.txt", b"v2"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send_json(
+        &app,
+        upload(&editor_key, &[("title", &unique("unwatched"))], "new.txt", b"fresh"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, notifications) = send_json(&app, get("/me/notifications", &watcher_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    let data = notifications["data"].as_array().expect("notification list");
+    assert_eq!(data.len(), 1, "only the new version is notified: {:?}", data);
+    assert_eq!(data[0]["document_id"], document_id.as_str());
+    assert_eq!(data[0]["document_version"], 2);
+
+    // The uploader is not notified of their own change
+    let (_, notifications) = send_json(&app, get("/me/notifications", &editor_key)).await;
+    assert_eq!(notifications["data"].as_array().map(Vec::len), Some(0));
+}
+
+#[tokio::test]
+async fn fail_closed_upload_is_rolled_back_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = audit_failing_user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = true).expect("test state");
+    let app = rust_dms::routes::router(state);
+
+    let title = unique("audit-closed-upload");
+    let (status, _, _) = send(&app, upload(&api_key, &[("title", &title)], "a.txt", b"unaudited")).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let documents: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE title = $1")
+        .bind(&title)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(documents, 0);
+    assert_eq!(audit_rows_by(&pool, &editor).await, 0);
+}
+
+#[tokio::test]
+async fn fail_closed_template_flag_is_rolled_back_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = audit_failing_user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = true).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("audit-closed-template"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let flag = serde_json::json!({"is_template": true});
+    let uri = format!("/documents/{}/template", document.id);
+    let (status, _) = send_json(&app, json_request("POST", uri, &api_key, flag)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let is_template: bool = sqlx::query_scalar("SELECT is_template FROM documents WHERE id = $1")
+        .bind(document.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!is_template);
+    assert_eq!(audit_rows_by(&pool, &editor).await, 0);
+}
+
+#[tokio::test]
+async fn fail_open_upload_succeeds_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = audit_failing_user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = false).expect("test state");
+    let app = rust_dms::routes::router(state);
+
+    let (status, uploaded) = send_json(
+        &app,
+        upload(&api_key, &[("title", &unique("audit-open-upload"))], "a.txt", b"unaudited"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let document_id = uploaded["document_id"].as_str().unwrap();
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"unaudited");
+    assert_eq!(audit_rows_by(&pool, &editor).await, 0);
+}
+
+#[tokio::test]
+async fn fail_closed_hard_delete_keeps_rows_and_files_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = audit_failing_user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = true).expect("test state");
+    let (document, versions) = seed_document(&state, &admin, &unique("audit-closed-delete"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, request("DELETE", format!("/documents/{}/hard", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document_versions WHERE document_id = $1")
+        .bind(document.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 2);
+    for version in &versions {
+        assert!(storage.stat(&version.file_path).await.is_ok(), "{} was deleted", version.file_path);
+    }
+    assert_eq!(audit_rows_by(&pool, &admin).await, 0);
+}
+
+#[tokio::test]
+async fn fail_open_hard_delete_completes_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = audit_failing_user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = false).expect("test state");
+    let (document, versions) = seed_document(&state, &admin, &unique("audit-open-delete"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, request("DELETE", format!("/documents/{}/hard", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE id = $1")
+        .bind(document.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+    for version in &versions {
+        assert!(storage.stat(&version.file_path).await.is_err(), "{} was kept", version.file_path);
+    }
+    assert_eq!(audit_rows_by(&pool, &admin).await, 0);
+}
+
+#[tokio::test]
+async fn permission_matrix_in_the_state_lets_an_editor_delete() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("matrix"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let hard_delete = format!("/documents/{}/hard", document.id);
+    let app = rust_dms::routes::router(state);
+    let (status, _, _) = send(&app, request("DELETE", &hard_delete, &api_key)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let matrix = rust_dms::config::parse_permission_matrix("delete=editor|admin").unwrap();
+    let state = test_state_with(pool, |c| c.permission_overrides = std::sync::Arc::new(matrix)).expect("test state");
+    let app = rust_dms::routes::router(state);
+    let (status, _, _) = send(&app, request("DELETE", &hard_delete, &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+/// State whose read pool is a separate, lazily connected pool on the same
+/// database, so `read_pool.size()` shows whether anything was read through it
+async fn state_with_separate_read_pool(pool: &PgPool) -> rust_dms::state::AppState {
+    let read_pool = sqlx::postgres::PgPoolOptions::new()
+        .connect_lazy(&std::env::var("DATABASE_URL").unwrap())
+        .expect("lazy read pool");
+    rust_dms::state::AppState {
+        read_pool,
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state(pool.clone()).expect("test state")
+    }
+}
+
+#[tokio::test]
+async fn downloads_and_listings_read_from_the_read_pool() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = state_with_separate_read_pool(&pool).await;
+    let (document, _) = seed_document(&state, &editor, &unique("replica-read"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let read_pool = state.read_pool.clone();
+    let app = rust_dms::routes::router(state);
+    assert_eq!(read_pool.size(), 0);
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"v1");
+    assert!(read_pool.size() > 0, "download did not use the read pool");
+}
+
+#[tokio::test]
+async fn restore_resolves_the_source_version_on_the_primary() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = state_with_separate_read_pool(&pool).await;
+    let (document, _) = seed_document(&state, &editor, &unique("replica-restore"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let read_pool = state.read_pool.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, restored) = send_json(
+        &app,
+        post(format!("/documents/{}/versions/1/restore", document.id), &api_key),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", restored);
+    assert_eq!(read_pool.size(), 0, "restore read through the read pool");
+}
+
+#[tokio::test]
+async fn audit_event_export_carries_the_request_id_and_normalized_fields() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let app = rust_dms::routes::router(test_state(pool).expect("test state"));
+    let since = (chrono::Utc::now() - chrono::Duration::seconds(5)).format("%Y-%m-%dT%H:%M:%SZ");
+
+    let request_id = unique("smoke-request");
+    let mut upload = upload(&api_key, &[("title", &unique("audit-event"))], "event.txt", b"event");
+    upload.headers_mut().insert("X-Request-Id", request_id.parse().unwrap());
+    let (status, uploaded) = send_json(&app, upload).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, headers, body) = send(&app, get(format!("/audit/export.jsonl?since={}", since), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/x-ndjson");
+
+    let events: Vec<Value> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is one JSON event"))
+        .collect();
+    let event = events
+        .iter()
+        .find(|e| e["trace_id"] == request_id.as_str())
+        .expect("event tagged with the upload's request id");
+
+    assert_eq!(event["schema_version"], 1);
+    assert_eq!(event["actor"]["id"], admin.id.to_string());
+    assert_eq!(event["action"], "UPLOAD");
+    assert_eq!(event["resource"]["type"], "document");
+    assert_eq!(event["resource"]["id"], uploaded["document_id"]);
+    assert_eq!(event["resource"]["version"], 1);
+    assert_eq!(event["outcome"], "success");
+    assert!(event["event_id"].is_string() && event["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn oversized_audit_metadata_is_stored_as_a_placeholder() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_metadata_max_bytes = 64).expect("test state");
+    let app = rust_dms::routes::router(state);
+
+    let (status, uploaded) = send_json(
+        &app,
+        upload(&api_key, &[("title", &unique("big-audit"))], &format!("{}.txt", "long-name-".repeat(20)), b"x"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let metadata: Value = sqlx::query_scalar(
+        "SELECT metadata FROM audit_logs WHERE user_id = $1 AND document_id = $2::uuid",
+    )
+    .bind(editor.id.to_string())
+    .bind(uploaded["document_id"].as_str().unwrap())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(metadata["truncated"], true);
+    assert_eq!(metadata["max_bytes"], 64);
+    assert!(metadata["original_size_bytes"].as_u64().unwrap() > 64);
+    assert!(metadata.get("file_name").is_none());
+}
+
+/// Start the admin job at `uri` and wait for it to stop running
+async fn run_job(app: &Router, jobs: &rust_dms::jobs::JobRegistry, uri: &str, api_key: &str, body: Value) -> rust_dms::dtos::JobInfo {
+    let (status, started) = send_json(app, json_request("POST", uri, api_key, body)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", started);
+    let id: Uuid = serde_json::from_value(started["id"].clone()).unwrap();
+    loop {
+        let job = jobs.get(id).expect("job in the registry");
+        if job.status != rust_dms::dtos::JobStatus::Running {
+            return job;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+}
+
+const PNG_BYTES: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+
+#[tokio::test]
+async fn repair_mime_types_corrects_a_mistyped_version() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    // Seeded versions are typed application/octet-stream whatever their content
+    let (_, versions) = seed_document(&state, &admin, &unique("mistyped"), None, &[PNG_BYTES])
+        .await
+        .expect("seed document");
+    let jobs = state.jobs.clone();
+    let app = rust_dms::routes::router(state);
+
+    // Other tests' candidates live in other storages and stay unresolved
+    let body = serde_json::json!({"batch_size": 1000});
+    let job = run_job(&app, &jobs, "/admin/repair-mime-types", &api_key, body).await;
+    assert_eq!(job.status, rust_dms::dtos::JobStatus::Completed);
+
+    let mime_type: Option<String> = sqlx::query_scalar("SELECT mime_type FROM document_versions WHERE id = $1")
+        .bind(versions[0].id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(mime_type.as_deref(), Some("image/png"));
+}
+
+#[tokio::test]
+async fn adding_mixed_tags_reports_each_tag_status() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("tagged"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let (other, _) = seed_document(&state, &editor, &unique("other"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (present, elsewhere, fresh) = (unique("present"), unique("elsewhere"), unique("fresh"));
+    for (doc, tag) in [(document.id, &present), (other.id, &elsewhere)] {
+        let body = serde_json::json!({"document_id": doc, "tags": [tag]});
+        let (status, _) = send_json(&app, json_request("POST", "/tags", &api_key, body)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let body = serde_json::json!({"document_id": document.id, "tags": [&fresh, &present, "  ", &elsewhere]});
+    let (status, response) = send_json(&app, json_request("POST", "/tags", &api_key, body)).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+
+    let statuses: Vec<(&str, &str)> = response["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| (t["tag_name"].as_str().unwrap(), t["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            (fresh.as_str(), "created"),
+            (present.as_str(), "already_present"),
+            ("", "skipped_empty"),
+            (elsewhere.as_str(), "added"),
+        ]
+    );
+    assert!(response["tags"][2]["tag_id"].is_null());
+    assert_eq!(response["total"], 3);
+}
+
+#[tokio::test]
+async fn adding_only_blank_tags_is_rejected() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("blank-tags"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let body = serde_json::json!({"document_id": document.id, "tags": ["", "   "]});
+    let (status, _) = send_json(&app, json_request("POST", "/tags", &api_key, body)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn document_audit_csv_lists_the_documents_rows_in_order() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "admin").await;
+    let app = rust_dms::routes::router(test_state(pool).expect("test state"));
+
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", &unique("audited"))], "a.txt", b"v1")).await;
+    assert_eq!(status, StatusCode::OK);
+    let document_id = uploaded["document_id"].as_str().unwrap().to_string();
+    let (status, _) = send_json(&app, upload(&api_key, &[("document_id", &document_id)], "a.txt", b"v2")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = send(&app, get(format!("/documents/{}/content?version=1", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, headers, body) = send(&app, get(format!("/documents/{}/audit/export", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers["content-type"].to_str().unwrap().starts_with("text/csv"));
+
+    let mut reader = csv::Reader::from_reader(&body[..]);
+    let rows: Vec<(String, String)> = reader
+        .records()
+        .map(|r| {
+            let r = r.unwrap();
+            (r[3].to_string(), r[4].to_string())
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("Upload".to_string(), "1".to_string()),
+            ("Upload".to_string(), "2".to_string()),
+            ("Download".to_string(), "1".to_string()),
+        ]
+    );
+}
+
+/// Hard-delete a two-version document over storage that refuses every delete;
+/// returns the status and whether the document row survived
+async fn hard_delete_with_failing_storage(strict: bool) -> Option<(StatusCode, bool)> {
+    let pool = database().await?;
+    let (admin, api_key) = user(&pool, "admin").await;
+    let faults = StorageFaults { fail_deletes: true, ..Default::default() };
+    let state = rust_dms::state::AppState {
+        storage: faulty_storage(memory_storage().unwrap(), faults),
+        ..test_state_with(pool.clone(), |c| c.strict_hard_delete = strict).expect("test state")
+    };
+    let (document, versions) = seed_document(&state, &admin, &unique("undeletable"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, request("DELETE", format!("/documents/{}/hard", document.id), &api_key)).await;
+
+    for version in &versions {
+        assert!(storage.stat(&version.file_path).await.is_ok(), "the fixture deleted {}", version.file_path);
+    }
+    let document_kept: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1)")
+        .bind(document.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    Some((status, document_kept))
+}
+
+#[tokio::test]
+async fn strict_hard_delete_keeps_the_document_when_storage_deletes_fail() {
+    let Some((status, document_kept)) = hard_delete_with_failing_storage(true).await else { return };
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(document_kept);
+}
+
+#[tokio::test]
+async fn lenient_hard_delete_removes_the_document_when_storage_deletes_fail() {
+    let Some((status, document_kept)) = hard_delete_with_failing_storage(false).await else { return };
+    assert_eq!(status, StatusCode::OK);
+    assert!(!document_kept);
+}
+
+#[tokio::test]
+async fn documents_without_versions_are_reported_and_cleaned_up() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (orphan, _) = seed_document(&state, &admin, &unique("orphan"), None, &[])
+        .await
+        .expect("seed document");
+    let (complete, _) = seed_document(&state, &admin, &unique("complete"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let reported = |report: &Value, id: Uuid| {
+        report["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|d| d["id"] == id.to_string())
+    };
+
+    let (status, report) = send_json(&app, get("/admin/documents/orphaned", &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(reported(&report, orphan.id));
+    assert!(!reported(&report, complete.id));
+    assert_eq!(report["cleaned_up"], 0);
+
+    let (status, report) = send_json(&app, get("/admin/documents/orphaned?cleanup=true", &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(reported(&report, orphan.id));
+    assert!(report["cleaned_up"].as_u64().unwrap() >= 1);
+
+    let deleted: bool = sqlx::query_scalar("SELECT deleted_at IS NOT NULL FROM documents WHERE id = $1")
+        .bind(orphan.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(deleted);
+
+    let (_, report) = send_json(&app, get("/admin/documents/orphaned", &api_key)).await;
+    assert!(!reported(&report, orphan.id));
+}
+
+/// Router whose pool never connects, for middleware checks on routes that
+/// don't touch the database
+fn app_without_database(configure: impl FnOnce(&mut rust_dms::config::Config)) -> Router {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .connect_lazy("postgres://unused@127.0.0.1:1/unused")
+        .unwrap();
+    rust_dms::routes::router(test_state_with(pool, configure).expect("test state"))
+}
+
+fn anonymous(method: &str, uri: &str) -> axum::http::request::Builder {
+    Request::builder().method(method).uri(uri).header("Host", "dms.example")
+}
+
+#[tokio::test]
+async fn version_reports_the_build_without_credentials() {
+    let app = app_without_database(|_| {});
+    let (status, body) = send_json(&app, anonymous("GET", "/version").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(!body["version"].as_str().unwrap().is_empty());
+    assert!(!body["git_commit"].as_str().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn responses_carry_the_hardening_headers() {
+    let app = app_without_database(|c| {
+        c.security_headers = true;
+        c.hsts_max_age_secs = 3600;
+        c.frame_options = "DENY".to_string();
+        c.referrer_policy = "no-referrer".to_string();
+    });
+
+    for uri in ["/version", "/no-such-route"] {
+        let (status, headers, _) = send(&app, anonymous("GET", uri).body(Body::empty()).unwrap()).await;
+        assert_eq!(status == StatusCode::OK, uri == "/version");
+        assert_eq!(headers["x-content-type-options"], "nosniff", "{}", uri);
+        assert_eq!(headers["x-frame-options"], "DENY", "{}", uri);
+        assert_eq!(headers["referrer-policy"], "no-referrer", "{}", uri);
+        assert_eq!(headers["strict-transport-security"], "max-age=3600; includeSubDomains", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn hsts_defaults_to_a_year_and_zero_opts_out() {
+    let app = app_without_database(|c| c.security_headers = true);
+    let (_, headers, _) = send(&app, anonymous("GET", "/version").body(Body::empty()).unwrap()).await;
+    assert_eq!(headers["strict-transport-security"], "max-age=31536000; includeSubDomains");
+
+    let app = app_without_database(|c| {
+        c.security_headers = true;
+        c.hsts_max_age_secs = 0;
+    });
+    let (_, headers, _) = send(&app, anonymous("GET", "/version").body(Body::empty()).unwrap()).await;
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert!(headers.get("strict-transport-security").is_none());
+}
+
+#[tokio::test]
+async fn unmatched_routes_get_a_json_404() {
+    let app = app_without_database(|_| {});
+    let request = anonymous("GET", "/no-such-route").header("X-Request-Id", "missing-1").body(Body::empty()).unwrap();
+    let (status, body) = send_json(&app, request).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["request_id"], "missing-1");
+}
+
+#[tokio::test]
+async fn hardening_headers_can_be_turned_off() {
+    let app = app_without_database(|c| c.security_headers = false);
+    let (status, headers, _) = send(&app, anonymous("GET", "/version").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("x-content-type-options").is_none());
+    assert!(headers.get("strict-transport-security").is_none());
+}
+
+#[tokio::test]
+async fn plain_http_is_redirected_when_https_is_forced() {
+    let app = app_without_database(|c| {
+        c.force_https = true;
+        c.public_host = Some("dms.example".to_string());
+    });
+
+    let plain = Request::get("/version?x=1")
+        .header("Host", "attacker.example")
+        .header("x-forwarded-proto", "http")
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, _) = send(&app, plain).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(headers["location"], "https://dms.example/version?x=1", "redirect followed the client's Host");
+
+    let secure = anonymous("GET", "/version")
+        .header("x-forwarded-proto", "https")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, _) = send(&app, secure).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn plain_http_is_refused_when_https_is_forced_without_a_public_host() {
+    let app = app_without_database(|c| c.force_https = true);
+
+    let plain = anonymous("GET", "/version")
+        .header("x-forwarded-proto", "http")
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, _) = send(&app, plain).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(headers.get("location").is_none());
+}
+
+fn preflight(origin: &str) -> Request<Body> {
+    anonymous("OPTIONS", "/documents")
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "GET")
+        .header("Access-Control-Request-Headers", "x-api-key")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn cors_preflight_is_answered_only_for_listed_origins() {
+    let app = app_without_database(|c| c.cors_allowed_origins = vec!["https://app.example".to_string()]);
+
+    let (status, headers, _) = send(&app, preflight("https://app.example")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["access-control-allow-origin"], "https://app.example");
+    assert!(headers["access-control-allow-headers"].to_str().unwrap().contains("x-api-key"));
+    assert!(headers["access-control-allow-methods"].to_str().unwrap().contains("GET"));
+
+    let (_, headers, _) = send(&app, preflight("https://evil.example")).await;
+    assert!(headers.get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn cors_is_off_without_configured_origins() {
+    let app = app_without_database(|c| c.cors_allowed_origins = Vec::new());
+    let (_, headers, _) = send(&app, preflight("https://app.example")).await;
+    assert!(headers.get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn request_ids_are_echoed_generated_and_included_in_errors() {
+    let app = app_without_database(|_| {});
+
+    let tagged = anonymous("GET", "/version").header("X-Request-Id", "client-id-1").body(Body::empty()).unwrap();
+    let (_, headers, _) = send(&app, tagged).await;
+    assert_eq!(headers["x-request-id"], "client-id-1");
+
+    // A missing or unusable id is replaced by a generated one
+    for supplied in [None, Some("has spaces"), Some(&*"x".repeat(200))] {
+        let mut request = anonymous("GET", "/version");
+        if let Some(id) = supplied {
+            request = request.header("X-Request-Id", id);
+        }
+        let (_, headers, _) = send(&app, request.body(Body::empty()).unwrap()).await;
+        let id = headers["x-request-id"].to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok(), "{:?} became {}", supplied, id);
+    }
+
+    // Errors name the request they belong to
+    let unauthenticated = anonymous("GET", "/documents").header("X-Request-Id", "client-id-2").body(Body::empty()).unwrap();
+    let (status, body) = send_json(&app, unauthenticated).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["request_id"], "client-id-2");
+}
+
+/// Title-keyed state over a temp dir, since rekeying copies objects
+fn title_keyed_state(pool: &PgPool) -> rust_dms::state::AppState {
+    rust_dms::state::AppState {
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state_with(pool.clone(), |c| c.key_strategy = rust_dms::storage::KeyStrategy::TitleBased)
+            .expect("test state")
+    }
+}
+
+async fn rename_document(pool: &PgPool, document_id: Uuid, title: &str) {
+    sqlx::query("UPDATE documents SET title = $2 WHERE id = $1")
+        .bind(document_id)
+        .bind(title)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn version_paths(pool: &PgPool, document_id: Uuid) -> Vec<String> {
+    sqlx::query_scalar("SELECT file_path FROM document_versions WHERE document_id = $1 ORDER BY version_number")
+        .bind(document_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn rekey_moves_version_objects_to_the_new_title() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = title_keyed_state(&pool);
+    let (document, versions) = seed_document(&state, &admin, &unique("before"), None, &[b"first", b"second"])
+        .await
+        .expect("seed document");
+    let new_title = unique("after");
+    rename_document(&pool, document.id, &new_title).await;
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, rekeyed) = send_json(&app, post(format!("/admin/documents/{}/rekey", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", rekeyed);
+    assert_eq!(rekeyed["rekeyed"].as_array().unwrap().len(), 2);
+    assert_eq!(rekeyed["unchanged"], 0);
+
+    let paths = version_paths(&pool, document.id).await;
+    for (path, old) in paths.iter().zip(&versions) {
+        assert!(path.starts_with(&new_title), "{} was not moved", path);
+        assert!(storage.stat(path).await.is_ok());
+        assert!(storage.stat(&old.file_path).await.is_err(), "{} was left behind", old.file_path);
+    }
+
+    for (version, contents) in [(1, &b"first"[..]), (2, &b"second"[..])] {
+        let uri = format!("/documents/{}/content?version={}", document.id, version);
+        let (status, _, body) = send(&app, get(uri, &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&body[..], contents);
+    }
+
+    // A second run has nothing left to move
+    let (status, rekeyed) = send_json(&app, post(format!("/admin/documents/{}/rekey", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rekeyed["rekeyed"].as_array().unwrap().len(), 0);
+    assert_eq!(rekeyed["unchanged"], 2);
+}
+
+#[tokio::test]
+async fn rekey_rolls_back_copies_when_a_later_copy_fails() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = title_keyed_state(&pool);
+    let (document, versions) = seed_document(&state, &admin, &unique("before"), None, &[b"first", b"second"])
+        .await
+        .expect("seed document");
+    // The first copy succeeds, the second has no source
+    state.storage.delete(&versions[1].file_path).await.unwrap();
+    let new_title = unique("after");
+    rename_document(&pool, document.id, &new_title).await;
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, _) = send_json(&app, post(format!("/admin/documents/{}/rekey", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    let paths = version_paths(&pool, document.id).await;
+    assert_eq!(paths, versions.iter().map(|v| v.file_path.clone()).collect::<Vec<_>>());
+    assert!(storage.stat(&versions[0].file_path).await.is_ok());
+    let copied = versions[0].file_path.replacen(&document.title, &new_title, 1);
+    assert!(storage.stat(&copied).await.is_err(), "the copy to {} was not rolled back", copied);
+}
+
+#[tokio::test]
+async fn namespace_migration_skips_failures_and_counts_what_remains() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let legacy = rust_dms::state::AppState {
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state(pool.clone()).expect("test state")
+    };
+    let (movable, _) = seed_document(&legacy, &admin, &unique("movable"), None, &[b"first", b"second"])
+        .await
+        .expect("seed document");
+    let (broken, broken_versions) = seed_document(&legacy, &admin, &unique("broken"), None, &[b"gone"])
+        .await
+        .expect("seed document");
+    legacy.storage.delete(&broken_versions[0].file_path).await.unwrap();
+    let jobs = legacy.jobs.clone();
+    let app = rust_dms::routes::router(rust_dms::state::AppState {
+        config: std::sync::Arc::new(rust_dms::config::Config { key_namespaces: true, ..(*legacy.config).clone() }),
+        ..legacy
+    });
+
+    // Other tests' legacy documents live in other storages; leave them out
+    let others: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT document_id FROM document_versions WHERE file_path NOT LIKE 'versions/%' AND document_id <> ALL($1)",
+    )
+    .bind(vec![movable.id, broken.id])
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+
+    let job = run_job(&app, &jobs, "/admin/migrate-key-namespaces", &api_key, serde_json::json!({"batch_size": 500, "skip": others.clone()})).await;
+    assert_eq!(job.status, rust_dms::dtos::JobStatus::Completed);
+    // The failed document is counted as processed but left in place
+    assert_eq!((job.processed, job.total), (2, Some(2)));
+
+    for path in version_paths(&pool, movable.id).await {
+        assert!(path.starts_with("versions/"), "{} was not moved", path);
+    }
+    assert_eq!(version_paths(&pool, broken.id).await, vec![broken_versions[0].file_path.clone()]);
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", movable.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"second");
+
+    // Skipped documents are not retried
+    let skip: Vec<Uuid> = others.iter().copied().chain([broken.id]).collect();
+    let job = run_job(&app, &jobs, "/admin/migrate-key-namespaces", &api_key, serde_json::json!({"skip": skip})).await;
+    assert_eq!(job.status, rust_dms::dtos::JobStatus::Completed);
+    assert_eq!(job.processed, 0);
+}
+
+#[tokio::test]
+async fn namespace_migration_moves_nested_folder_markers() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "admin").await;
+    let legacy = rust_dms::state::AppState {
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state(pool.clone()).expect("test state")
+    };
+    let namespaced = rust_dms::state::AppState {
+        config: std::sync::Arc::new(rust_dms::config::Config { key_namespaces: true, ..(*legacy.config).clone() }),
+        ..legacy.clone()
+    };
+    let legacy = rust_dms::routes::router(legacy);
+    let top = unique("top");
+    for (name, parent) in [(top.clone(), None), ("child".to_string(), Some(top.clone())), ("leaf".to_string(), Some(format!("{}/child", top)))] {
+        let create = serde_json::json!({"name": name, "parent": parent});
+        let (status, body) = send_json(&legacy, json_request("POST", "/folders", &api_key, create)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    let jobs = namespaced.jobs.clone();
+    let app = rust_dms::routes::router(namespaced);
+
+    // Only the folders are under test; leave every document's keys alone
+    let skip: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM documents").fetch_all(&pool).await.unwrap();
+    let job = run_job(&app, &jobs, "/admin/migrate-key-namespaces", &api_key, serde_json::json!({"skip": skip})).await;
+    assert_eq!(job.status, rust_dms::dtos::JobStatus::Completed);
+
+    let (status, listed) = send_json(&app, get("/folders?depth=3", &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", listed);
+    let mut names: Vec<&str> = listed["folders"].as_array().unwrap().iter().map(|f| f["folder_name"].as_str().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, vec![top.clone(), format!("{}/child", top), format!("{}/child/leaf", top)]);
+}
+
+#[tokio::test]
+async fn dedup_report_counts_each_duplicated_object_once() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    // Large and unique, so the group sorts onto the first page
+    let contents = format!("{}{}", unique("dedup"), "x".repeat(256 * 1024)).into_bytes();
+    let (first, _) = seed_document(&state, &admin, &unique("copy"), None, &[&contents])
+        .await
+        .expect("seed document");
+    let (second, versions) = seed_document(&state, &admin, &unique("copy"), None, &[&contents, &contents])
+        .await
+        .expect("seed document");
+    // Two versions sharing one object are one copy, not two
+    sqlx::query("UPDATE document_versions SET file_path = $2 WHERE id = $1")
+        .bind(versions[1].id)
+        .bind(&versions[0].file_path)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let (status, report) = send_json(&app, get("/admin/dedup-report?page_size=100", &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    let checksum = versions[0].checksum.clone().unwrap();
+    let group = report["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|g| g["checksum"] == checksum.as_str())
+        .expect("duplicate group on the first page");
+    assert_eq!(group["object_count"], 2);
+    assert_eq!(group["file_size"], contents.len());
+    assert_eq!(group["reclaimable_bytes"], contents.len());
+    let mut document_ids: Vec<Uuid> = serde_json::from_value(group["document_ids"].clone()).unwrap();
+    document_ids.sort();
+    let mut expected = vec![first.id, second.id];
+    expected.sort();
+    assert_eq!(document_ids, expected);
+    assert!(report["total"].as_i64().unwrap() >= 1);
+    assert!(report["total_reclaimable_bytes"].as_i64().unwrap() >= contents.len() as i64);
+
+    // Later runs' groups are just as large; keep them from crowding the page
+    sqlx::query("DELETE FROM documents WHERE id = ANY($1)")
+        .bind(expected)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn simulated_access_matches_the_permission_checks() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (owner, _) = user(&pool, "editor").await;
+    let (document, _) = seed_document(&state, &owner, &unique("simulated"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (viewer, _) = user(&pool, "viewer").await;
+    let (editor, _) = user(&pool, "editor").await;
+    for (simulated, owns_document) in [(&viewer, false), (&editor, false), (&owner, true), (&admin, false)] {
+        let uri = format!("/admin/simulate-access?user_id={}&document_id={}", simulated.id, document.id);
+        let (status, report) = send_json(&app, get(uri, &api_key)).await;
+        assert_eq!(status, StatusCode::OK, "{}", report);
+        assert_eq!(report["document_available"], true);
+
+        let current = rust_dms::auth::CurrentUser {
+            id: simulated.id,
+            username: simulated.username.clone(),
+            role: serde_json::from_value(report["role"].clone()).unwrap(),
+            permissions: Default::default(),
+        };
+        let actions = report["actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 7);
+        for action in actions {
+            let name = action["action"].as_str().unwrap();
+            let expected = match name {
+                "set_visibility" => {
+                    check_permission(&current, StorageAction::Write).is_ok()
+                        && (current.role == Role::Admin || owns_document)
+                }
+                _ => check_permission(&current, name.parse().unwrap()).is_ok(),
+            };
+            assert_eq!(action["allowed"], expected, "{} for {}", name, simulated.role);
+            assert_eq!(action["reason"].is_null(), expected, "{} for {}", name, simulated.role);
+        }
+    }
+
+    let uri = format!("/admin/simulate-access?user_id={}&document_id={}", Uuid::new_v4(), document.id);
+    let (status, _) = send_json(&app, get(uri, &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+async fn login(app: &Router, username: &str, password: &str) -> StatusCode {
+    let body = serde_json::json!({"username": username, "password": password});
+    let request = Request::builder()
+        .method("POST")
+        .uri("/auth/login")
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(app, request).await.0
+}
+
+#[tokio::test]
+async fn bearer_tokens_stop_working_after_a_role_change_or_deletion() {
+    let Some(pool) = database().await else { return };
+    let (editor, _) = user(&pool, "editor").await;
+    let secret = "smoke-jwt-secret";
+    let state = test_state_with(pool.clone(), |c| c.jwt_secret = Some(secret.to_string())).expect("test state");
+    let current = rust_dms::auth::CurrentUser {
+        id: editor.id,
+        username: editor.username.clone(),
+        role: Role::Editor,
+        permissions: state.config.permission_overrides.clone(),
+    };
+    let now = chrono::Utc::now().timestamp();
+    let token = rust_dms::token::issue_token(secret, &current, now, now + 600).expect("issue token");
+    let app = rust_dms::routes::router(state);
+    let with_token = || {
+        Request::get("/documents")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let (status, _, _) = send(&app, with_token()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    sqlx::query("UPDATE users SET role = 'viewer' WHERE id = $1")
+        .bind(editor.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _, _) = send(&app, with_token()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(editor.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _, _) = send(&app, with_token()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn auth_bypass_users_are_reserved_and_never_re_roled() {
+    let Some(pool) = database().await else { return };
+    let (_, admin_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.test_auth_bypass = true).expect("test state");
+    let app = rust_dms::routes::router(state);
+    let as_test_viewer = || {
+        Request::get("/documents")
+            .header("X-Test-User", "viewer")
+            .body(Body::empty())
+            .unwrap()
+    };
+    let reset = || sqlx::query("DELETE FROM users WHERE username = '~test-viewer'").execute(&pool);
+    reset().await.unwrap();
+
+    let (status, _, _) = send(&app, as_test_viewer()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A row under the reserved name with another role is refused, not overwritten
+    sqlx::query("UPDATE users SET role = 'editor' WHERE username = '~test-viewer'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _, _) = send(&app, as_test_viewer()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let role: String = sqlx::query_scalar("SELECT role FROM users WHERE username = '~test-viewer'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(role, "editor");
+    reset().await.unwrap();
+
+    let create = serde_json::json!({"username": "~test-admin", "role": "viewer"});
+    let (status, _) = send_json(&app, json_request("POST", "/users", &admin_key, create)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn admin_password_reset_returns_a_generated_password_once() {
+    let Some(pool) = database().await else { return };
+    let (_, admin_key) = user(&pool, "admin").await;
+    let (_, editor_key) = user(&pool, "editor").await;
+    let (target, _) = user(&pool, "viewer").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+    let reset = |api_key: &str, body: Value| json_request("POST", format!("/admin/users/{}/reset-password", target.id), api_key, body);
+
+    let (status, _) = send_json(&app, reset(&editor_key, serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, generated) = send_json(&app, reset(&admin_key, serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", generated);
+    assert_eq!(generated["user_id"], target.id.to_string());
+    let password = generated["generated_password"].as_str().expect("generated password").to_string();
+    assert_eq!(login(&app, &target.username, &password).await, StatusCode::OK);
+
+    // A supplied password is never echoed back
+    let (status, supplied) = send_json(&app, reset(&admin_key, serde_json::json!({"password": "Chosen-Passw0rd"}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", supplied);
+    assert!(supplied["generated_password"].is_null());
+    assert_eq!(login(&app, &target.username, "Chosen-Passw0rd").await, StatusCode::OK);
+    assert_ne!(login(&app, &target.username, &password).await, StatusCode::OK);
+
+    let (status, _) = send_json(&app, reset(&admin_key, serde_json::json!({"password": "short"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let missing = json_request("POST", format!("/admin/users/{}/reset-password", Uuid::new_v4()), &admin_key, serde_json::json!({}));
+    let (status, _) = send_json(&app, missing).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn explain_returns_the_plan_of_the_documents_listing() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "admin").await;
+
+    let disabled = rust_dms::routes::router(test_state_with(pool.clone(), |c| c.admin_explain = false).expect("test state"));
+    let (status, _) = send_json(&disabled, get("/admin/explain?endpoint=list_documents", &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let app = rust_dms::routes::router(test_state_with(pool.clone(), |c| c.admin_explain = true).expect("test state"));
+    // Postgres rejects the statement if the bound values don't match its placeholders
+    let (status, explained) = send_json(&app, get("/admin/explain?endpoint=list_documents", &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", explained);
+    assert_eq!(explained["endpoint"], "list_documents");
+    assert_eq!(explained["params"].as_array().unwrap().len(), 13);
+    assert!(explained["plan"][0]["Plan"].is_object(), "{}", explained["plan"]);
+
+    for endpoint in ["list_versions", "document_metadata"] {
+        let (status, explained) = send_json(&app, get(format!("/admin/explain?endpoint={}", endpoint), &api_key)).await;
+        assert_eq!(status, StatusCode::OK, "{}", explained);
+        assert_eq!(explained["params"].as_array().unwrap().len(), 1);
+    }
+
+    let (status, _) = send_json(&app, get("/admin/explain?endpoint=users", &api_key)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn promoted_version_is_the_default_download_and_listed_latest() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let title = unique("promoted");
+    let (document, _) = seed_document(&state, &editor, &title, None, &[b"draft one", b"published", b"draft three"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (status, promoted) = send_json(&app, post(format!("/documents/{}/promote/2", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", promoted);
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"published");
+
+    let (status, listing) = send_json(&app, get(format!("/documents?title={}", title), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", listing);
+    assert_eq!(listing["data"][0]["latest_version_number"], 2);
+    assert_eq!(listing["data"][0]["latest_file_size"], b"published".len());
+
+    // An explicit version still wins
+    let (_, _, body) = send(&app, get(format!("/documents/{}/content?version=3", document.id), &api_key)).await;
+    assert_eq!(&body[..], b"draft three");
+}
+
+#[tokio::test]
+async fn paging_through_identical_timestamps_has_no_duplicates_or_gaps() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let category = unique("same-instant");
+    let mut seeded = Vec::new();
+    for _ in 0..11 {
+        let (document, _) = seed_document(&state, &editor, &unique("tied"), Some(&category), &[b"v1"])
+            .await
+            .expect("seed document");
+        seeded.push(document.id);
+    }
+    sqlx::query("UPDATE documents SET created_at = '2024-01-01T00:00:00Z', updated_at = '2024-01-01T00:00:00Z' WHERE category = $1")
+        .bind(&category)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    // Ties fall back to the id, in the same direction as the sort
+    for (sort, descending) in [("", true), ("&sort_by=updated_at&order=asc", false)] {
+        let mut listed = Vec::new();
+        for page in 1..=4 {
+            let uri = format!("/documents?category={}&page_size=3&page={}{}", category, page, sort);
+            let (status, body) = send_json(&app, get(uri, &api_key)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(body["total"], 11);
+            let ids: Vec<Uuid> = body["data"].as_array().unwrap().iter().map(|d| serde_json::from_value(d["id"].clone()).unwrap()).collect();
+            listed.extend(ids);
+        }
+        let mut expected = seeded.clone();
+        expected.sort();
+        if descending {
+            expected.reverse();
+        }
+        assert_eq!(listed, expected, "{}", sort);
+    }
+}
+
+#[tokio::test]
+async fn zero_page_size_is_raised_to_one() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool).expect("test state");
+    let category = unique("paged");
+    for _ in 0..2 {
+        seed_document(&state, &editor, &unique("paged"), Some(&category), &[b"v1"])
+            .await
+            .expect("seed document");
+    }
+    let app = rust_dms::routes::router(state);
+
+    let uri = format!("/documents?category={}&page_size=0", category);
+    let (status, body) = send_json(&app, get(uri, &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["page_size"], 1);
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["total_pages"], 2);
+    assert_eq!(body["has_next"], true);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+async fn is_soft_deleted(pool: &PgPool, document_id: Uuid) -> bool {
+    sqlx::query_scalar("SELECT deleted_at IS NOT NULL FROM documents WHERE id = $1")
+        .bind(document_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pruning_every_version_soft_deletes_the_document() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, versions) = seed_document(&state, &admin, &unique("pruned"), None, &[b"v1", b"v2", b"v3"])
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let prune = json_request("POST", format!("/documents/{}/prune", document.id), &api_key, serde_json::json!({"keep": 0}));
+    let (status, pruned) = send_json(&app, prune).await;
+    assert_eq!(status, StatusCode::OK, "{}", pruned);
+    assert_eq!(pruned["soft_deleted"], true);
+    assert_eq!(pruned["pruned_versions"], serde_json::json!([2, 1]));
+    assert_eq!(pruned["remaining_versions"], 1);
+    assert!(is_soft_deleted(&pool, document.id).await);
+
+    // The newest version stays, so a restore brings the document back with content
+    assert_eq!(version_paths(&pool, document.id).await, vec![versions[2].file_path.clone()]);
+    assert!(storage.stat(&versions[2].file_path).await.is_ok());
+    for version in &versions[..2] {
+        assert!(storage.stat(&version.file_path).await.is_err(), "{} was not removed", version.file_path);
+    }
+}
+
+#[tokio::test]
+async fn pruning_keeps_the_promoted_version() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &admin, &unique("promoted"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let document_id = document.id.to_string();
+
+    let (status, _) = send_json(&app, post(format!("/documents/{}/promote/1", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    for contents in [b"v2", b"v3"] {
+        let (status, _) = send_json(&app, upload(&api_key, &[("document_id", &document_id)], "next.txt", contents)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let prune = json_request("POST", format!("/documents/{}/prune", document_id), &api_key, serde_json::json!({"keep": 1}));
+    let (status, pruned) = send_json(&app, prune).await;
+    assert_eq!(status, StatusCode::OK, "{}", pruned);
+    assert_eq!(pruned["pruned_versions"], serde_json::json!([2]));
+    assert_eq!(pruned["remaining_versions"], 2);
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"v1");
+}
+
+#[tokio::test]
+async fn pruning_every_version_is_refused_when_soft_delete_is_off() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.prune_soft_deletes_empty = false).expect("test state");
+    let (document, _) = seed_document(&state, &admin, &unique("kept"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let prune = json_request("POST", format!("/documents/{}/prune", document.id), &api_key, serde_json::json!({"keep": 0}));
+    let (status, _) = send_json(&app, prune).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(!is_soft_deleted(&pool, document.id).await);
+    assert_eq!(version_paths(&pool, document.id).await.len(), 2);
+}
+
+#[tokio::test]
+async fn large_downloads_stream_whole_and_truncated_objects_abort() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    // The memory service panics on reads past the end of an object
+    let state = rust_dms::state::AppState {
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state(pool.clone()).expect("test state")
+    };
+    let contents: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let (document, versions) = seed_document(&state, &editor, &unique("large"), None, &[&contents])
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, headers, body) = send(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-length"], contents.len().to_string().as_str());
+    assert!(body[..] == contents[..], "streamed body differs from the stored object");
+
+    // The headers are already out when storage comes up short, so the body errors
+    storage.write(&versions[0].file_path, contents[..1024].to_vec()).await.unwrap();
+    let response = app.clone().oneshot(get(format!("/documents/{}/content", document.id), &api_key)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(to_bytes(response.into_body(), usize::MAX).await.is_err());
+
+    // A missing object fails before any headers are sent
+    storage.delete(&versions[0].file_path).await.unwrap();
+    let (status, _, _) = send(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert!(status.is_client_error() || status.is_server_error(), "{}", status);
+}
+
+#[tokio::test]
+async fn verified_upload_fails_when_storage_forgets_the_write() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let faults = StorageFaults { forget_writes: true, ..Default::default() };
+    let forgetful = |verify: bool| rust_dms::state::AppState {
+        storage: faulty_storage(memory_storage().unwrap(), faults),
+        ..test_state_with(pool.clone(), |c| c.verify_writes = verify).expect("test state")
+    };
+
+    let title = unique("forgotten");
+    let app = rust_dms::routes::router(forgetful(true));
+    let (status, body) = send_json(&app, upload(&api_key, &[("title", &title)], "lost.txt", b"never stored")).await;
+    assert!(status.is_server_error(), "{} {}", status, body);
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE title = $1 AND created_by = $2")
+        .bind(&title)
+        .bind(editor.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0, "the upload was committed");
+
+    // Without verification the lost write goes unnoticed
+    let app = rust_dms::routes::router(forgetful(false));
+    let (status, _) = send_json(&app, upload(&api_key, &[("title", &title)], "lost.txt", b"never stored")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn failed_verification_never_deletes_a_concurrent_upload() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let storage = memory_storage().unwrap();
+    let state = |forget_writes: bool| rust_dms::state::AppState {
+        storage: faulty_storage(storage.clone(), StorageFaults { forget_writes, ..Default::default() }),
+        ..test_state_with(pool.clone(), |c| c.verify_writes = true).expect("test state")
+    };
+    let (healthy, forgetful) = (rust_dms::routes::router(state(false)), rust_dms::routes::router(state(true)));
+
+    let (status, uploaded) = send_json(&healthy, upload(&api_key, &[("title", &unique("verified"))], "a.txt", b"v1")).await;
+    assert_eq!(status, StatusCode::OK, "{}", uploaded);
+    let document_id = uploaded["document_id"].as_str().expect("document_id in response").to_string();
+
+    // Each round races a good upload against one whose write is lost and
+    // fails verification; the failed one must only clean up its own key
+    for round in 0..5 {
+        let fields = [("document_id", document_id.as_str())];
+        let contents = format!("round {}", round);
+        let good = send(&healthy, upload(&api_key, &fields, "a.txt", contents.as_bytes()));
+        let lost = send(&forgetful, upload(&api_key, &fields, "b.txt", format!("lost {}", contents).as_bytes()));
+        let ((good, _, _), (lost, _, _)) = tokio::join!(good, lost);
+        assert_eq!(good, StatusCode::OK);
+        assert!(lost.is_server_error(), "{}", lost);
+    }
+
+    let paths: Vec<String> = sqlx::query_scalar("SELECT file_path FROM document_versions WHERE document_id = $1::uuid")
+        .bind(&document_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(paths.len(), 6);
+    for path in paths {
+        assert!(storage.stat(&path).await.is_ok(), "{} was deleted", path);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_identical_folder_creates_have_one_winner() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state_with(pool.clone(), |c| c.folder_registry = true).expect("test state"));
+    let name = unique("contended");
+
+    let attempts: Vec<_> = (0..8)
+        .map(|_| {
+            let app = app.clone();
+            let create = json_request("POST", "/folders", &api_key, serde_json::json!({"name": name}));
+            tokio::spawn(async move { send(&app, create).await.0 })
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for attempt in attempts {
+        statuses.push(attempt.await.unwrap());
+    }
+
+    assert_eq!(statuses.iter().filter(|s| **s == StatusCode::OK).count(), 1, "{:?}", statuses);
+    assert!(statuses.iter().all(|s| *s == StatusCode::OK || *s == StatusCode::CONFLICT), "{:?}", statuses);
+}
+
+fn batch_statuses(response: &Value) -> Vec<(Uuid, String)> {
+    response["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (serde_json::from_value(r["document_id"].clone()).unwrap(), r["status"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn batch_soft_delete_reports_each_document() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (live, _) = seed_document(&state, &admin, &unique("live"), None, &[b"v1"]).await.expect("seed document");
+    let (gone, _) = seed_document(&state, &admin, &unique("gone"), None, &[b"v1"]).await.expect("seed document");
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(gone.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let missing = Uuid::new_v4();
+    let app = rust_dms::routes::router(state);
+
+    let ids = serde_json::json!({"ids": [live.id, gone.id, missing, live.id]});
+    let (status, response) = send_json(&app, json_request("POST", "/documents/batch-delete", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS, "{}", response);
+    assert_eq!(
+        batch_statuses(&response),
+        vec![
+            (live.id, "deleted".to_string()),
+            (gone.id, "already_deleted".to_string()),
+            (missing, "not_found".to_string()),
+        ]
+    );
+    assert_eq!(response["deleted"], 1);
+    assert_eq!(response["skipped"], 2);
+    assert!(is_soft_deleted(&pool, live.id).await);
+    // One audit entry per document actually deleted
+    assert_eq!(audit_rows_by(&pool, &admin).await, 1);
+}
+
+#[tokio::test]
+async fn batch_hard_delete_removes_rows_and_files() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let mut documents = Vec::new();
+    let mut objects = Vec::new();
+    for _ in 0..3 {
+        let (document, versions) = seed_document(&state, &admin, &unique("purged"), None, &[b"v1", b"v2"])
+            .await
+            .expect("seed document");
+        documents.push(document.id);
+        objects.extend(versions.into_iter().map(|v| v.file_path));
+    }
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let ids = serde_json::json!({"ids": documents, "hard": true});
+    let (status, response) = send_json(&app, json_request("POST", "/documents/batch-delete", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    assert!(batch_statuses(&response).iter().all(|(_, status)| status == "deleted"));
+    assert_eq!(response["deleted"], 3);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE id = ANY($1)")
+        .bind(&documents)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+    for object in &objects {
+        assert!(storage.stat(object).await.is_err(), "{} was left in storage", object);
+    }
+
+    // One audit entry per document, naming it although its row is gone
+    let mut audited: Vec<Uuid> = sqlx::query_scalar("SELECT (metadata->>'document_id')::uuid FROM audit_logs WHERE user_id = $1")
+        .bind(admin.id.to_string())
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    audited.sort();
+    documents.sort();
+    assert_eq!(audited, documents);
+}
+
+#[tokio::test]
+async fn fail_open_hard_delete_audits_the_deleted_document() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = false).expect("test state");
+    let (document, _) = seed_document(&state, &admin, &unique("audited-delete"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, request("DELETE", format!("/documents/{}/hard", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Written after the commit, when the document row is already gone
+    let audited: Option<String> = sqlx::query_scalar(
+        "SELECT metadata->>'document_id' FROM audit_logs WHERE user_id = $1 AND metadata->>'delete_type' = 'hard'",
+    )
+    .bind(admin.id.to_string())
+    .fetch_optional(&pool)
+    .await
+    .unwrap();
+    assert_eq!(audited, Some(document.id.to_string()));
+}
+
+#[tokio::test]
+async fn truncated_multipart_upload_is_rejected_without_storing_anything() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+
+    // Cut off inside the file part: no closing boundary
+    let title = unique("truncated");
+    let mut body = multipart_body(&[("title", &title)], "cut.txt", b"the whole file");
+    body.truncate(body.len() - 20);
+    let request = Request::post("/upload")
+        .header("X-API-Key", &api_key)
+        .header("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY))
+        .body(Body::from(body))
+        .unwrap();
+
+    let (status, error) = send_json(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", error);
+    assert!(error["error"].as_str().unwrap_or_default().to_lowercase().contains("malformed"), "{}", error);
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE created_by = $1")
+        .bind(editor.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+}
+
+#[tokio::test]
+async fn bad_credentials_are_401_and_missing_permissions_403() {
+    let Some(pool) = database().await else { return };
+    let (viewer, api_key) = user(&pool, "viewer").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &viewer, &unique("viewed"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let anonymous = Request::get("/documents").body(Body::empty()).unwrap();
+    let (status, _) = send_json(&app, anonymous).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send_json(&app, get("/documents", "not-a-real-key")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send_json(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send_json(&app, upload(&api_key, &[("title", "denied")], "denied.txt", b"no")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send_json(&app, request("DELETE", format!("/documents/{}", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+/// Seed a one-version document and retype its version as `mime_type`
+async fn seed_typed(state: &rust_dms::state::AppState, owner: &User, contents: &[u8], mime_type: &str) -> Uuid {
+    let (document, versions) = seed_document(state, owner, &unique("typed"), None, &[contents])
+        .await
+        .expect("seed document");
+    sqlx::query("UPDATE document_versions SET mime_type = $2 WHERE id = $1")
+        .bind(versions[0].id)
+        .bind(mime_type)
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    document.id
+}
+
+#[tokio::test]
+async fn csv_preview_returns_headers_and_sample_rows() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let csv = seed_typed(&state, &editor, b"name,amount\nalpha,1\n\"beta, inc\",2\ngamma,3\n", "text/csv").await;
+    let text = seed_typed(&state, &editor, b"first line\nsecond line\n", "text/plain").await;
+    let binary = seed_typed(&state, &editor, PNG_BYTES, "image/png").await;
+    let app = rust_dms::routes::router(state);
+
+    let (status, preview) = send_json(&app, get(format!("/documents/{}/preview?rows=2", csv), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", preview);
+    assert_eq!(preview["kind"], "csv");
+    assert_eq!(preview["headers"], serde_json::json!(["name", "amount"]));
+    assert_eq!(preview["rows"], serde_json::json!([["alpha", "1"], ["beta, inc", "2"]]));
+    assert_eq!(preview["truncated"], true);
+
+    let (status, preview) = send_json(&app, get(format!("/documents/{}/preview", text), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", preview);
+    assert_eq!(preview["kind"], "text");
+    assert_eq!(preview["lines"], serde_json::json!(["first line", "second line"]));
+    assert_eq!(preview["truncated"], false);
+
+    let (status, _) = send_json(&app, get(format!("/documents/{}/preview", binary), &api_key)).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn footprint_flags_objects_that_differ_from_the_database() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, versions) = seed_document(&state, &admin, &unique("footprint"), None, &[b"intact", b"resized", b"missing"])
+        .await
+        .expect("seed document");
+    state.storage.write(&versions[1].file_path, b"resized on disk".to_vec()).await.unwrap();
+    state.storage.delete(&versions[2].file_path).await.unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let (status, footprint) = send_json(&app, get(format!("/documents/{}/footprint", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", footprint);
+    let by_version = |n: i32| footprint["versions"].as_array().unwrap().iter().find(|v| v["version_number"] == n).unwrap().clone();
+
+    let intact = by_version(1);
+    assert_eq!(intact["stored_size"], 6);
+    assert_eq!(intact["size_mismatch"], false);
+    assert_eq!(intact["missing"], false);
+
+    let resized = by_version(2);
+    assert_eq!(resized["file_size"], 7);
+    assert_eq!(resized["stored_size"], 15);
+    assert_eq!(resized["size_mismatch"], true);
+
+    let missing = by_version(3);
+    assert_eq!(missing["missing"], true);
+    assert!(missing["stored_size"].is_null());
+
+    assert_eq!(footprint["total_file_size"], 6 + 7 + 7);
+    assert_eq!(footprint["total_stored_size"], 6 + 15);
+    // Missing objects count as mismatches too
+    assert_eq!(footprint["mismatches"], 2);
+}
+
+/// Viewer attempts to soft-delete a document; returns the viewer's audit rows
+async fn forbidden_delete_audit_rows(pool: &PgPool, audit_failures: bool) -> Vec<(Option<Uuid>, Value)> {
+    let (viewer, api_key) = user(pool, "viewer").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_failures = audit_failures).expect("test state");
+    let (document, _) = seed_document(&state, &viewer, &unique("protected"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (status, _) = send_json(&app, request("DELETE", format!("/documents/{}", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!is_soft_deleted(pool, document.id).await);
+
+    sqlx::query_as::<_, (Option<Uuid>, sqlx::types::Json<Value>)>("SELECT document_id, metadata FROM audit_logs WHERE user_id = $1")
+        .bind(viewer.id.to_string())
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(document_id, metadata)| (document_id, metadata.0))
+        .collect()
+}
+
+#[tokio::test]
+async fn forbidden_delete_is_audited_when_failures_are() {
+    let Some(pool) = database().await else { return };
+    let rows = forbidden_delete_audit_rows(&pool, true).await;
+    assert_eq!(rows.len(), 1, "{:?}", rows);
+    assert!(rows[0].0.is_some());
+    assert_eq!(rows[0].1["outcome"], "failure");
+    assert!(rows[0].1["reason"].as_str().unwrap().contains("Permission denied"), "{}", rows[0].1);
+
+    assert!(forbidden_delete_audit_rows(&pool, false).await.is_empty());
+}
+
+#[tokio::test]
+async fn aliases_resolve_until_they_expire() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("aliased"), None, &[b"old", b"shared content"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let create = |body: Value| json_request("POST", format!("/documents/{}/alias", document.id), &api_key, body);
+
+    let (status, alias) = send_json(&app, create(serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", alias);
+    assert!(alias["expires_at"].is_null());
+    let url = alias["url"].as_str().unwrap().to_string();
+    let (status, _, body) = send(&app, get(&url, &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"shared content");
+    // The document is private, so the link alone is not enough
+    let (status, _) = send_json(&app, Request::get(&url).body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, expiring) = send_json(&app, create(serde_json::json!({"expires_in_secs": 3600}))).await;
+    assert_eq!(status, StatusCode::OK, "{}", expiring);
+    let slug = expiring["slug"].as_str().unwrap();
+    let (status, _, _) = send(&app, get(format!("/d/{}", slug), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    sqlx::query("UPDATE document_aliases SET expires_at = CURRENT_TIMESTAMP - INTERVAL '1 second' WHERE slug = $1")
+        .bind(slug)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _) = send_json(&app, get(format!("/d/{}", slug), &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send_json(&app, get("/d/no-such-slug", &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn fail_closed_bulk_metadata_is_rolled_back_when_the_audit_insert_fails() {
+    let Some(pool) = database().await else { return };
+    let (owner, _) = user(&pool, "editor").await;
+    let (editor, api_key) = audit_failing_user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.audit_fail_closed = true).expect("test state");
+    let (document, _) = seed_document(&state, &owner, &unique("audit-closed-bulk"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let bulk = serde_json::json!({"document_ids": [document.id], "metadata": {"department": "finance"}});
+    let (status, _) = send_json(&app, json_request("POST", "/metadata/bulk", &api_key, bulk)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document_metadata WHERE document_id = $1")
+        .bind(document.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(keys, 0);
+    assert_eq!(audit_rows_by(&pool, &editor).await, 0);
+}
+
+#[tokio::test]
+async fn null_metadata_values_read_as_empty_strings() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("nullable"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    sqlx::query("INSERT INTO document_metadata (document_id, key, value) VALUES ($1, 'reviewer', NULL)")
+        .bind(document.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let metadata_url = format!("/documents/{}/metadata", document.id);
+    let (status, body) = send_json(&app, get(&metadata_url, &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["metadata"]["reviewer"], "");
+
+    let patch = json_request("PATCH", &metadata_url, &api_key, serde_json::json!({"status": "draft"}));
+    let (status, body) = send_json(&app, patch).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["metadata"], serde_json::json!({"reviewer": "", "status": "draft"}));
+}
+
+#[tokio::test]
+async fn bulk_metadata_is_applied_to_every_listed_document() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let mut documents = Vec::new();
+    for _ in 0..3 {
+        let (document, _) = seed_document(&state, &editor, &unique("tagged"), None, &[b"v1"]).await.expect("seed document");
+        documents.push(document.id);
+    }
+    let app = rust_dms::routes::router(state);
+
+    // An existing value is overwritten, other keys are kept
+    let existing = serde_json::json!({"department": "sales", "owner": "kim"});
+    let patch = json_request("PATCH", format!("/documents/{}/metadata", documents[0]), &api_key, existing);
+    let (status, body) = send_json(&app, patch).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let missing = Uuid::new_v4();
+    let bulk = serde_json::json!({
+        "document_ids": [documents[0], documents[1], missing, documents[2]],
+        "metadata": {"department": "finance", "year": "2024"},
+    });
+    let (status, response) = send_json(&app, json_request("POST", "/metadata/bulk", &api_key, bulk)).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS, "{}", response);
+    assert_eq!(response["applied"], 3);
+    assert_eq!(response["skipped"], 1);
+    let not_found: Vec<&Value> = response["results"].as_array().unwrap().iter().filter(|r| r["status"] == "not_found").collect();
+    assert_eq!(not_found.len(), 1);
+    assert_eq!(not_found[0]["document_id"], missing.to_string());
+
+    for (i, document_id) in documents.iter().enumerate() {
+        let (status, body) = send_json(&app, get(format!("/documents/{}/metadata", document_id), &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["metadata"]["department"], "finance");
+        assert_eq!(body["metadata"]["year"], "2024");
+        assert_eq!(body["metadata"]["owner"].is_string(), i == 0);
+    }
+}
+
+#[tokio::test]
+async fn every_served_range_is_audited_with_its_offsets() {
+    let Some(pool) = database().await else { return };
+    let (viewer, viewer_key) = user(&pool, "viewer").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &viewer, &unique("ranged"), None, &[b"0123456789"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let ranged = Request::get(format!("/documents/{}/content", document.id))
+        .header("X-API-Key", &viewer_key)
+        .header("Range", "bytes=4-")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, body) = send(&app, ranged).await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(&body[..], b"456789");
+
+    let metadata: Vec<Value> = sqlx::query_scalar(
+        "SELECT metadata FROM audit_logs WHERE user_id = $1 AND action = 'DOWNLOAD'",
+    )
+    .bind(viewer.id.to_string())
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(metadata.len(), 1);
+    assert_eq!(metadata[0]["range_start"], 4);
+    assert_eq!(metadata[0]["range_end"], 9);
+    assert_eq!(metadata[0]["bytes_served"], 6);
+}
+
+#[tokio::test]
+async fn downloads_past_the_role_limit_are_throttled() {
+    let Some(pool) = database().await else { return };
+    let (viewer, viewer_key) = user(&pool, "viewer").await;
+    let (_, editor_key) = user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| {
+        c.download_limits = [("viewer".to_string(), 2)].into_iter().collect();
+        c.download_bandwidth_limits.clear();
+    })
+    .expect("test state");
+    let (document, _) = seed_document(&state, &viewer, &unique("popular"), None, &[b"v1"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let content = format!("/documents/{}/content", document.id);
+
+    for _ in 0..2 {
+        let (status, _, _) = send(&app, get(&content, &viewer_key)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = send_json(&app, get(&content, &viewer_key)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    // Other roles have no limit
+    for _ in 0..3 {
+        let (status, _, _) = send(&app, get(&content, &editor_key)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn ranged_downloads_are_charged_the_bytes_they_serve() {
+    let Some(pool) = database().await else { return };
+    let (viewer, viewer_key) = user(&pool, "viewer").await;
+    let state = test_state_with(pool.clone(), |c| {
+        c.download_limits.clear();
+        c.download_bandwidth_limits = [("viewer".to_string(), 10)].into_iter().collect();
+    })
+    .expect("test state");
+    let (document, _) = seed_document(&state, &viewer, &unique("metered"), None, &[b"0123456789"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let ranged = |range: &str| {
+        Request::get(format!("/documents/{}/content", document.id))
+            .header("X-API-Key", &viewer_key)
+            .header("Range", range)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // A one-byte probe and the rest of the file fit the ten-byte budget
+    let (status, _, _) = send(&app, ranged("bytes=0-0")).await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    let (status, _, _) = send(&app, ranged("bytes=1-9")).await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+
+    // The budget is spent, however the next byte is asked for
+    let (status, _, _) = send(&app, ranged("bytes=9-9")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+/// Pool whose connections only see `schema`
+async fn pool_in_schema(schema: &str) -> PgPool {
+    let options: sqlx::postgres::PgConnectOptions = std::env::var("DATABASE_URL").unwrap().parse().unwrap();
+    sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(options.options([("search_path", schema)]))
+        .await
+        .expect("connect to schema")
+}
+
+#[tokio::test]
+async fn audit_schema_check_detects_a_missing_enum() {
+    let Some(pool) = database().await else { return };
+    assert_eq!(rust_dms::audit::verify_schema(&pool).await, Ok(()));
+
+    let schema = format!("smoke_{}", Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&pool).await.unwrap();
+    let drifted = pool_in_schema(&schema).await;
+
+    let problem = rust_dms::audit::verify_schema(&drifted).await.unwrap_err();
+    assert_eq!(problem, "enum type audit_action is missing");
+
+    sqlx::query("CREATE TYPE audit_action AS ENUM ('UPLOAD')").execute(&drifted).await.unwrap();
+    let problem = rust_dms::audit::verify_schema(&drifted).await.unwrap_err();
+    assert!(problem.starts_with("audit_action enum has no value"), "{}", problem);
+
+    drifted.close().await;
+    sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&pool).await.unwrap();
+}
+
+/// Start a backup, waiting out one another test may still be streaming
+async fn start_backup(app: &Router, api_key: &str) -> axum::response::Response {
+    loop {
+        let response = app.clone().oneshot(post("/admin/backup", api_key)).await.unwrap();
+        if response.status() != StatusCode::CONFLICT {
+            assert_eq!(response.status(), StatusCode::OK);
+            return response;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn backup_tarball_holds_the_objects_and_a_manifest() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, versions) = seed_document(&state, &admin, &unique("backed-up"), None, &[b"first", b"second", b"lost"])
+        .await
+        .expect("seed document");
+    state.storage.delete(&versions[2].file_path).await.unwrap();
+    sqlx::query("INSERT INTO document_metadata (document_id, key, value) VALUES ($1, 'department', 'finance')")
+        .bind(document.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let response = start_backup(&app, &api_key).await;
+    assert!(response.headers().get("x-job-id").is_some());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    // Other tests' objects live in other storages, so only ours are archived
+    let mut archive = tar::Archive::new(&body[..]);
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut contents).unwrap();
+        entries.push((path, contents));
+    }
+    let paths: Vec<&str> = entries.iter().map(|(p, _)| p.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "manifest.json".to_string(),
+            format!("documents/{}/v1", document.id),
+            format!("documents/{}/v2", document.id),
+        ]
+    );
+    assert_eq!(entries[1].1, b"first");
+    assert_eq!(entries[2].1, b"second");
+
+    let manifest: Value = serde_json::from_slice(&entries[0].1).unwrap();
+    assert_eq!(manifest["created_by"], admin.id.to_string());
+    let ours = manifest["documents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["document"]["id"] == document.id.to_string())
+        .expect("document in the manifest");
+    assert_eq!(ours["metadata"], serde_json::json!({"department": "finance"}));
+    let included: Vec<bool> = ours["versions"].as_array().unwrap().iter().map(|v| v["included"].as_bool().unwrap()).collect();
+    assert_eq!(included, vec![true, true, false]);
+}
+
+#[tokio::test]
+async fn backup_aborts_when_an_object_changes_size_mid_stream() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool).expect("test state");
+    let contents: [&[u8]; 6] = [b"v1", b"v2", b"v3", b"v4", b"v5", b"v6"];
+    let (_, versions) = seed_document(&state, &admin, &unique("rewritten"), None, &contents)
+        .await
+        .expect("seed document");
+    let (storage, jobs) = (state.storage.clone(), state.jobs.clone());
+    let app = rust_dms::routes::router(state);
+
+    // The stream blocks on the unread body well before the last object, so
+    // rewriting it now lands between its stat and its read
+    let response = start_backup(&app, &api_key).await;
+    let job_id: Uuid = response.headers()["x-job-id"].to_str().unwrap().parse().unwrap();
+    storage.write(&versions[5].file_path, b"rewritten and longer".to_vec()).await.unwrap();
+
+    assert!(to_bytes(response.into_body(), usize::MAX).await.is_err());
+    let job = jobs.get(job_id).expect("backup job");
+    assert_eq!(job.status, rust_dms::dtos::JobStatus::Failed);
+    assert!(job.error.unwrap().contains("changed size"));
+}
+
+#[tokio::test]
+async fn folder_listing_stops_at_the_requested_depth() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+    let top = unique("tree");
+    for (name, parent) in [(top.as_str(), None), ("mid", Some(top.clone())), ("other", Some(top.clone())), ("leaf", Some(format!("{}/mid", top)))] {
+        let body = serde_json::json!({"name": name, "parent": parent});
+        let (status, created) = send_json(&app, json_request("POST", "/folders", &api_key, body)).await;
+        assert_eq!(status, StatusCode::OK, "{}", created);
+    }
+
+    let list = |query: String| {
+        let app = app.clone();
+        let api_key = api_key.clone();
+        async move {
+            let (status, listing) = send_json(&app, get(format!("/folders?{}", query), &api_key)).await;
+            assert_eq!(status, StatusCode::OK, "{}", listing);
+            let mut names: Vec<String> = listing["folders"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|f| f["folder_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            (names, listing["truncated"].as_bool().unwrap())
+        }
+    };
+    let names = |suffixes: &[&str]| -> Vec<String> { suffixes.iter().map(|s| format!("{}{}", top, s)).collect() };
+
+    assert_eq!(list("depth=1".into()).await, (names(&[""]), true));
+    assert_eq!(list("depth=2".into()).await, (names(&["", "/mid", "/other"]), true));
+    assert_eq!(list("depth=3".into()).await, (names(&["", "/mid", "/mid/leaf", "/other"]), false));
+    assert_eq!(list(format!("parent={}&depth=1", top)).await, (names(&["/mid", "/other"]), true));
+    assert_eq!(list(format!("parent={}&depth=2", top)).await, (names(&["/mid", "/mid/leaf", "/other"]), false));
+    let (limited, truncated) = list("depth=3&limit=2".into()).await;
+    assert_eq!((limited.len(), truncated), (2, true));
+}
+
+#[tokio::test]
+async fn hard_delete_removes_every_object_across_batches() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state_with(pool.clone(), |c| c.hard_delete_batch_size = 10).expect("test state");
+    let contents: Vec<Vec<u8>> = (0..25).map(|i| format!("version {}", i).into_bytes()).collect();
+    let contents: Vec<&[u8]> = contents.iter().map(Vec::as_slice).collect();
+    let (document, versions) = seed_document(&state, &admin, &unique("many-versions"), None, &contents)
+        .await
+        .expect("seed document");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, deleted) = send_json(&app, request("DELETE", format!("/documents/{}/hard", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", deleted);
+    assert_eq!(deleted["versions_deleted"], 25);
+    for version in &versions {
+        assert!(storage.stat(&version.file_path).await.is_err(), "{} was left in storage", version.file_path);
+    }
+    assert!(version_paths(&pool, document.id).await.is_empty());
+}
+
+#[tokio::test]
+async fn metadata_keys_are_counted_per_live_document() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let prefix = unique("key").replace('-', "_");
+    let mut documents = Vec::new();
+    for _ in 0..4 {
+        let (document, _) = seed_document(&state, &editor, &unique("described"), None, &[b"v1"]).await.expect("seed document");
+        documents.push(document.id);
+    }
+    let app = rust_dms::routes::router(state);
+
+    let common = format!("{}_common", prefix);
+    let rare = format!("{}_rare", prefix);
+    for (i, document_id) in documents.iter().enumerate() {
+        let mut metadata = serde_json::Map::new();
+        metadata.insert(common.clone(), Value::from(format!("value {}", i)));
+        if i == 0 {
+            metadata.insert(rare.clone(), Value::from("only here"));
+        }
+        let patch = json_request("PATCH", format!("/documents/{}/metadata", document_id), &api_key, Value::Object(metadata));
+        let (status, body) = send_json(&app, patch).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    // Deleted documents don't count
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(documents[3])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (status, keys) = send_json(&app, get(format!("/metadata/keys?prefix={}", prefix), &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", keys);
+    assert_eq!(
+        keys["keys"],
+        serde_json::json!([
+            {"key": common, "document_count": 3},
+            {"key": rare, "document_count": 1},
+        ])
+    );
+    assert_eq!(keys["total"], 2);
+}
+
+#[tokio::test]
+async fn audit_polling_returns_only_rows_after_the_cursor() {
+    let Some(pool) = database().await else { return };
+    let (_, admin_key) = user(&pool, "admin").await;
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("polled"), None, &[b"v1", b"v2"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let (status, _, _) = send(&app, get(format!("/documents/{}/content", document.id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    let cursor: Uuid = sqlx::query_scalar("SELECT id FROM audit_logs WHERE user_id = $1")
+        .bind(editor.id.to_string())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    for version in [1, 2] {
+        let (status, _, _) = send(&app, get(format!("/documents/{}/content?version={}", document.id, version), &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, polled) = send_json(&app, get(format!("/audit?since_id={}&limit=1000", cursor), &admin_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", polled);
+    // Other tests write audit rows concurrently; look at this user's only
+    let ours: Vec<&Value> = polled["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|row| row["user_id"] == editor.id.to_string())
+        .collect();
+    let versions: Vec<&Value> = ours.iter().map(|row| &row["document_version"]).collect();
+    assert_eq!(versions, vec![&Value::from(1), &Value::from(2)]);
+    assert!(polled["data"].as_array().unwrap().iter().all(|row| row["id"] != cursor.to_string()));
+    assert!(!polled["newest_id"].is_null());
+
+    let (status, _) = send_json(&app, get(format!("/audit?since_id={}", Uuid::new_v4()), &admin_key)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn head_matches_get_headers_without_a_body_or_audit_row() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("headed"), None, &[b"first", b"the second version"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    for uri in [format!("/documents/{}/content", document.id), format!("/documents/{}/content?version=1", document.id)] {
+        let (status, head_headers, body) = send(&app, request("HEAD", &uri, &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_empty());
+        assert_eq!(audit_rows_by(&pool, &editor).await, 0, "HEAD was audited as a download");
+
+        let (status, get_headers, body) = send(&app, get(&uri, &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+        for name in ["content-type", "content-length", "content-disposition", "etag", "last-modified", "accept-ranges"] {
+            assert_eq!(head_headers.get(name), get_headers.get(name), "{} of {}", name, uri);
+        }
+        assert_eq!(head_headers["content-length"], body.len().to_string().as_str());
+        sqlx::query("DELETE FROM audit_logs WHERE user_id = $1")
+            .bind(editor.id.to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn head_answers_preconditions_and_ranges_exactly_like_get() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (document, _) = seed_document(&state, &editor, &unique("conditional"), None, &[b"0123456789"])
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+    let uri = format!("/documents/{}/content", document.id);
+
+    let cases = [
+        ("If-Match", "\"0000\"", StatusCode::PRECONDITION_FAILED),
+        ("X-Expected-Checksum", "0000", StatusCode::PRECONDITION_FAILED),
+        ("Range", "bytes=4-", StatusCode::PARTIAL_CONTENT),
+        ("Range", "bytes=20-", StatusCode::RANGE_NOT_SATISFIABLE),
+    ];
+    for (name, value, expected) in cases {
+        let conditional = |method: &str| {
+            Request::builder()
+                .method(method)
+                .uri(&uri)
+                .header("X-API-Key", &api_key)
+                .header(name, value)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let (head_status, head_headers, body) = send(&app, conditional("HEAD")).await;
+        assert!(body.is_empty());
+        let (get_status, get_headers, _) = send(&app, conditional("GET")).await;
+        assert_eq!((head_status, get_status), (expected, expected), "{}: {}", name, value);
+        for header in ["content-length", "content-range", "etag", "accept-ranges"] {
+            assert_eq!(head_headers.get(header), get_headers.get(header), "{} for {}: {}", header, name, value);
+        }
+    }
+    assert_eq!(audit_rows_by(&pool, &editor).await, 1, "only the ranged GET is audited");
+}
+
+#[tokio::test]
+async fn identical_reupload_is_rejected_when_duplicates_are_refused() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let app = rust_dms::routes::router(test_state_with(pool.clone(), |c| c.reject_duplicate_versions = true).expect("test state"));
+
+    let title = unique("duplicated");
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", &title)], "a.txt", b"same")).await;
+    assert_eq!(status, StatusCode::OK, "{}", uploaded);
+    let document_id = uploaded["document_id"].as_str().expect("document_id in response").to_string();
+
+    let fields = [("document_id", document_id.as_str())];
+    let (status, rejected) = send_json(&app, upload(&api_key, &fields, "a.txt", b"same")).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", rejected);
+    assert_eq!(rejected["code"], "DUPLICATE_VERSION");
+    assert!(rejected["error"].as_str().unwrap().ends_with("version 1"), "{}", rejected);
+
+    let (status, _) = send_json(&app, upload(&api_key, &fields, "a.txt", b"changed")).await;
+    assert_eq!(status, StatusCode::OK);
+    let versions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document_versions WHERE document_id = $1::uuid")
+        .bind(&document_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(versions, 2);
+
+    // Without the flag the same content is just another version
+    let app = rust_dms::routes::router(test_state(pool).expect("test state"));
+    let (status, _) = send_json(&app, upload(&api_key, &fields, "a.txt", b"changed")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_duplicate_uploads_never_delete_a_committed_object() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.reject_duplicate_versions = true).expect("test state");
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", &unique("raced"))], "a.txt", b"v1")).await;
+    assert_eq!(status, StatusCode::OK, "{}", uploaded);
+    let document_id = uploaded["document_id"].as_str().expect("document_id in response").to_string();
+
+    // Every upload after the first carrying the same bytes is a duplicate,
+    // and rejecting it must not remove the winner's object
+    let attempts: Vec<_> = (0..8)
+        .map(|_| {
+            let (app, document_id) = (app.clone(), document_id.clone());
+            let request = upload(&api_key, &[("document_id", &document_id)], "a.txt", b"v2");
+            tokio::spawn(async move { send(&app, request).await.0 })
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for attempt in attempts {
+        statuses.push(attempt.await.unwrap());
+    }
+    assert_eq!(statuses.iter().filter(|s| **s == StatusCode::OK).count(), 1, "{:?}", statuses);
+    assert!(statuses.iter().all(|s| *s == StatusCode::OK || *s == StatusCode::CONFLICT), "{:?}", statuses);
+
+    let paths: Vec<String> = sqlx::query_scalar("SELECT file_path FROM document_versions WHERE document_id = $1::uuid")
+        .bind(&document_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(paths.len(), 2);
+    for path in paths {
+        assert!(storage.stat(&path).await.is_ok(), "{} was deleted", path);
+    }
+}
+
+#[tokio::test]
+async fn listing_past_the_response_cap_is_refused_with_413() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state_with(pool.clone(), |c| c.max_response_bytes = 4096).expect("test state");
+    let category = unique("bulky");
+    seed_document(&state, &editor, &unique("small"), Some(&category), &[b"v1"]).await.expect("seed document");
+    let app = rust_dms::routes::router(state.clone());
+
+    let uri = format!("/documents?category={}", category);
+    let (status, listed) = send_json(&app, get(&uri, &api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", listed);
+
+    // Rows near the title limit, enough of them to pass the cap on one page
+    for _ in 0..20 {
+        let long_title = format!("{}-{}", unique("long"), "x".repeat(200));
+        seed_document(&state, &editor, &long_title, Some(&category), &[b"v1"]).await.expect("seed document");
+    }
+    let (status, refused) = send_json(&app, get(&uri, &api_key)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(refused["code"], "RESPONSE_TOO_LARGE");
+
+    // Zero turns the cap off
+    let app = rust_dms::routes::router(test_state_with(pool, |c| c.max_response_bytes = 0).expect("test state"));
+    let (status, listed) = send_json(&app, get(&uri, &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed["total"], 21);
+}
+
+#[tokio::test]
+async fn documents_listing_resolves_with_and_without_a_trailing_slash() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "viewer").await;
+    let router = rust_dms::routes::router(test_state(pool).expect("test state"));
+    let app = rust_dms::routes::trim_trailing_slash(router.clone());
+
+    for uri in ["/documents", "/documents/"] {
+        let response = app.clone().oneshot(get(uri, &api_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let listed: Value = serde_json::from_slice(&body).unwrap();
+        assert!(listed["data"].is_array(), "{} did not list documents", uri);
+    }
+
+    // Unwrapped, the slash is a different path
+    let (status, _, _) = send(&router, get("/documents/", &api_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// Count the facet `field` reports for `value`, if it lists it at all
+async fn facet_count(app: &Router, api_key: &str, field: &str, value: &str) -> Option<i64> {
+    let (status, facets) = send_json(app, get(format!("/facets?field={}&limit=1000", field), api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", facets);
+    facets["values"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|v| v["value"] == value)
+        .map(|v| v["document_count"].as_i64().unwrap())
+}
+
+#[tokio::test]
+async fn facets_count_live_documents_per_category_tag_and_metadata_value() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (shared_category, lone_category) = (unique("facet-shared"), unique("facet-lone"));
+    let mut documents = Vec::new();
+    for category in [&shared_category, &shared_category, &lone_category] {
+        let (document, _) = seed_document(&state, &editor, &unique("faceted"), Some(category), &[b"v1"])
+            .await
+            .expect("seed document");
+        documents.push(document.id);
+    }
+    let app = rust_dms::routes::router(state);
+
+    let (common_tag, rare_tag) = (unique("facet-common"), unique("facet-rare"));
+    let key = unique("facetkey");
+    for (n, document_id) in documents.iter().enumerate() {
+        let tags = if n == 0 { vec![&common_tag, &rare_tag] } else { vec![&common_tag] };
+        let body = serde_json::json!({"document_id": document_id, "tags": tags});
+        let (status, _) = send_json(&app, json_request("POST", "/tags", &api_key, body)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let value = if n < 2 { "finance" } else { "legal" };
+        let body = serde_json::json!({&key: value});
+        let patch = json_request("PATCH", format!("/documents/{}/metadata", document_id), &api_key, body);
+        let (status, _) = send_json(&app, patch).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    assert_eq!(facet_count(&app, &api_key, "category", &shared_category).await, Some(2));
+    assert_eq!(facet_count(&app, &api_key, "tag", &common_tag).await, Some(3));
+    assert_eq!(facet_count(&app, &api_key, "tag", &rare_tag).await, Some(1));
+    let meta_field = format!("meta:{}", key);
+    let (status, facets) = send_json(&app, get(format!("/facets?field={}", meta_field), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(facets["values"], serde_json::json!([
+        {"value": "finance", "document_count": 2},
+        {"value": "legal", "document_count": 1},
+    ]));
+    assert_eq!(facets["truncated"], false);
+    let (_, facets) = send_json(&app, get(format!("/facets?field={}&limit=1", meta_field), &api_key)).await;
+    assert_eq!(facets["values"], serde_json::json!([{"value": "finance", "document_count": 2}]));
+    assert_eq!(facets["truncated"], true);
+
+    // Deleted documents drop out of every facet
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(documents[2])
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(facet_count(&app, &api_key, "category", &lone_category).await, None);
+    assert_eq!(facet_count(&app, &api_key, "tag", &common_tag).await, Some(2));
+    let (_, facets) = send_json(&app, get(format!("/facets?field={}", meta_field), &api_key)).await;
+    assert_eq!(facets["values"], serde_json::json!([{"value": "finance", "document_count": 2}]));
+
+    let (status, _) = send_json(&app, get("/facets?field=owner", &api_key)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn upload_past_the_warning_threshold_carries_a_usage_header() {
+    let Some(pool) = database().await else { return };
+    let (_, api_key) = user(&pool, "editor").await;
+    let state = test_state_with(pool, |c| {
+        c.user_storage_quota_bytes = 1000;
+        c.storage_warn_pct = 80;
+    })
+    .expect("test state");
+    let app = rust_dms::routes::router(state);
+
+    let (status, headers, _) = send(&app, upload(&api_key, &[("title", &unique("half"))], "half.bin", &[0; 500])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("x-storage-usage-pct").is_none());
+    assert!(headers.get("warning").is_none());
+
+    // 850 of 1000 bytes: past the threshold but still under the quota
+    let (status, headers, _) = send(&app, upload(&api_key, &[("title", &unique("more"))], "more.bin", &[0; 350])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["x-storage-usage-pct"], "85");
+    assert_eq!(headers["warning"], "199 - \"user storage 85% full (850 of 1000 bytes)\"");
+}
+
+#[tokio::test]
+async fn fresh_upload_leads_the_recent_versions_feed() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (older, _) = seed_document(&state, &editor, &unique("older"), None, &[b"v1"]).await.expect("seed document");
+    let (deleted, _) = seed_document(&state, &editor, &unique("deleted"), None, &[b"v1"]).await.expect("seed document");
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(deleted.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let title = unique("fresh");
+    let (status, uploaded) = send_json(&app, upload(&api_key, &[("title", &title)], "fresh.txt", b"new")).await;
+    assert_eq!(status, StatusCode::OK);
+    let fresh = uploaded["document_id"].clone();
+
+    // Other tests upload concurrently, so check our rows' relative order
+    let mut feed = Vec::new();
+    for page in 1..=20 {
+        let (status, recent) = send_json(&app, get(format!("/versions/recent?page={}&page_size=100", page), &api_key)).await;
+        assert_eq!(status, StatusCode::OK, "{}", recent);
+        feed.extend(recent["data"].as_array().unwrap().iter().cloned());
+        if feed.iter().any(|v| v["document_id"] == older.id.to_string()) || recent["has_next"] == false {
+            break;
+        }
+    }
+    let position = |id: &Value| feed.iter().position(|v| &v["document_id"] == id);
+    let fresh_at = position(&fresh).expect("fresh upload in the feed");
+    let older_at = position(&Value::from(older.id.to_string())).expect("older version in the feed");
+    assert!(fresh_at < older_at, "fresh upload at {}, older at {}", fresh_at, older_at);
+    assert_eq!(feed[fresh_at]["title"], title.as_str());
+    assert_eq!(feed[fresh_at]["version_number"], 1);
+    assert_eq!(position(&Value::from(deleted.id.to_string())), None);
+}
+
+#[tokio::test]
+async fn bulk_restore_reports_each_documents_prior_state() {
+    let Some(pool) = database().await else { return };
+    let (admin, api_key) = user(&pool, "admin").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let mut deleted = Vec::new();
+    for _ in 0..2 {
+        let (document, _) = seed_document(&state, &admin, &unique("trashed"), None, &[b"v1"])
+            .await
+            .expect("seed document");
+        deleted.push(document.id);
+    }
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = ANY($1)")
+        .bind(&deleted)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (live, _) = seed_document(&state, &admin, &unique("live"), None, &[b"v1"]).await.expect("seed document");
+    let missing = Uuid::new_v4();
+    let app = rust_dms::routes::router(state);
+
+    let ids = serde_json::json!({"ids": [deleted[0], live.id, missing, deleted[1], deleted[0]]});
+    let (status, response) = send_json(&app, json_request("POST", "/documents/restore/bulk", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS, "{}", response);
+    assert_eq!(
+        batch_statuses(&response),
+        vec![
+            (deleted[0], "restored".to_string()),
+            (live.id, "not_deleted".to_string()),
+            (missing, "not_found".to_string()),
+            (deleted[1], "restored".to_string()),
+        ]
+    );
+    assert_eq!(response["restored"], 2);
+    assert_eq!(response["skipped"], 2);
+    for document_id in &deleted {
+        assert!(!is_soft_deleted(&pool, *document_id).await);
+    }
+    // One audit entry per document actually restored
+    assert_eq!(audit_rows_by(&pool, &admin).await, 2);
+
+    // Everything restored: a plain 200
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(live.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let ids = serde_json::json!({"ids": [live.id]});
+    let (status, response) = send_json(&app, json_request("POST", "/documents/restore/bulk", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+
+    let (_, editor_key) = user(&pool, "editor").await;
+    let ids = serde_json::json!({"ids": [live.id]});
+    let (status, _) = send_json(&app, json_request("POST", "/documents/restore/bulk", &editor_key, ids)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let empty = serde_json::json!({"ids": []});
+    let (status, _) = send_json(&app, json_request("POST", "/documents/restore/bulk", &api_key, empty)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Ids the documents listing returns for `category`
+async fn listed_ids(app: &Router, api_key: &str, category: &str, extra: &str) -> Vec<String> {
+    let (status, listed) = send_json(app, get(format!("/documents?category={}{}", category, extra), api_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", listed);
+    listed["data"].as_array().unwrap().iter().map(|d| d["id"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn template_instantiation_copies_content_metadata_and_tags() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = rust_dms::state::AppState {
+        storage: temp_dir_storage().expect("temp dir storage"),
+        ..test_state(pool).expect("test state")
+    };
+    let category = unique("templated");
+    let versions: [&[u8]; 2] = [b"draft", b"final layout"];
+    let (template, _) = seed_document(&state, &editor, &unique("invoice"), Some(&category), &versions)
+        .await
+        .expect("seed document");
+    let app = rust_dms::routes::router(state);
+
+    let tag = unique("letterhead");
+    let body = serde_json::json!({"document_id": template.id, "tags": [&tag]});
+    assert_eq!(send_json(&app, json_request("POST", "/tags", &api_key, body)).await.0, StatusCode::OK);
+    let metadata = serde_json::json!({"form": "A1"});
+    let patch = json_request("PATCH", format!("/documents/{}/metadata", template.id), &api_key, metadata);
+    assert_eq!(send_json(&app, patch).await.0, StatusCode::OK);
+
+    // Not a template yet
+    let instantiate = format!("/documents/from-template/{}", template.id);
+    let (status, _) = send_json(&app, json_request("POST", &instantiate, &api_key, serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let flag = serde_json::json!({"is_template": true});
+    let mark = json_request("POST", format!("/documents/{}/template", template.id), &api_key, flag);
+    assert_eq!(send_json(&app, mark).await.0, StatusCode::OK);
+
+    let title = serde_json::json!({"title": "March invoice"});
+    let (status, created) = send_json(&app, json_request("POST", &instantiate, &api_key, title)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    assert_eq!(created["document"]["title"], "March invoice");
+    assert_eq!(created["document"]["category"], category.as_str());
+    assert_eq!(created["version"]["version_number"], 1);
+    assert_eq!(created["metadata_copied"], 1);
+    assert_eq!(created["tags_copied"], 1);
+    let document_id = created["document"]["id"].as_str().unwrap().to_string();
+
+    let (status, _, body) = send(&app, get(format!("/documents/{}/content", document_id), &api_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(&body[..], b"final layout");
+    let (_, metadata) = send_json(&app, get(format!("/documents/{}/metadata", document_id), &api_key)).await;
+    assert_eq!(metadata["metadata"]["form"], "A1");
+
+    // The template is listed only on request; its copy is an ordinary document
+    assert_eq!(listed_ids(&app, &api_key, &category, "").await, vec![document_id.clone()]);
+    let mut all = listed_ids(&app, &api_key, &category, "&include_templates=true").await;
+    all.sort();
+    let mut expected = vec![document_id, template.id.to_string()];
+    expected.sort();
+    assert_eq!(all, expected);
+}
+
+fn le_u16(bytes: &[u8], at: usize) -> usize {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+}
+
+fn le_u32(bytes: &[u8], at: usize) -> usize {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize
+}
+
+/// Names and contents of a stored (uncompressed) ZIP without a comment,
+/// read through its central directory; each entry's CRC is checked
+fn zip_entries(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let end = zip.len() - 22;
+    assert_eq!(le_u32(zip, end), 0x0605_4b50, "no end of central directory record");
+    let (count, mut at) = (le_u16(zip, end + 10), le_u32(zip, end + 16));
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        assert_eq!(le_u32(zip, at), 0x0201_4b50, "bad central directory entry");
+        let (crc, size) = (le_u32(zip, at + 16), le_u32(zip, at + 24));
+        let (name_len, offset) = (le_u16(zip, at + 28), le_u32(zip, at + 42));
+        let name = String::from_utf8(zip[at + 46..at + 46 + name_len].to_vec()).unwrap();
+
+        assert_eq!(le_u32(zip, offset), 0x0403_4b50, "bad local header for {}", name);
+        let data_at = offset + 30 + le_u16(zip, offset + 26) + le_u16(zip, offset + 28);
+        let contents = zip[data_at..data_at + size].to_vec();
+        assert_eq!(crc32fast::hash(&contents) as usize, crc, "CRC of {}", name);
+
+        entries.push((name, contents));
+        at += 46 + name_len;
+    }
+    entries
+}
+
+#[tokio::test]
+async fn archive_holds_the_latest_version_of_each_document_named_by_title() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool.clone()).expect("test state");
+    let (shared, other) = (unique("shared"), unique("other"));
+    let deleted_title = unique("deleted");
+    let mut documents = Vec::new();
+    let seeds: [(&str, &[&[u8]]); 4] =
+        [(&shared, &[b"old", b"first latest"]), (&shared, &[b"second"]), (&other, &[b"third"]), (&deleted_title, &[b"gone"])];
+    for (title, versions) in seeds {
+        let (document, _) = seed_document(&state, &editor, title, None, versions).await.expect("seed document");
+        documents.push(document);
+    }
+    let [first, second, third, deleted]: [_; 4] = documents.try_into().unwrap();
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(deleted.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = rust_dms::routes::router(state);
+
+    let ids = serde_json::json!({"ids": [first.id, Uuid::new_v4(), second.id, deleted.id, third.id]});
+    let (status, headers, body) = send(&app, json_request("POST", "/documents/archive", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/zip");
+    assert_eq!(
+        zip_entries(&body),
+        vec![
+            (format!("{}.bin", shared), b"first latest".to_vec()),
+            (format!("{} (2).bin", shared), b"second".to_vec()),
+            (format!("{}.bin", other), b"third".to_vec()),
+        ]
+    );
+    // One download audit per included document
+    assert_eq!(audit_rows_by(&pool, &editor).await, 3);
+
+    let ids = serde_json::json!({"ids": [Uuid::new_v4(), deleted.id]});
+    let (status, _) = send_json(&app, json_request("POST", "/documents/archive", &api_key, ids)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn archive_aborts_when_an_object_changes_size_mid_stream() {
+    let Some(pool) = database().await else { return };
+    let (editor, api_key) = user(&pool, "editor").await;
+    let state = test_state(pool).expect("test state");
+    let mut ids = Vec::new();
+    let mut last_path = String::new();
+    for n in 0..6 {
+        let contents = format!("document {}", n);
+        let (document, versions) = seed_document(&state, &editor, &unique("zipped"), None, &[contents.as_bytes()])
+            .await
+            .expect("seed document");
+        ids.push(document.id);
+        last_path = versions[0].file_path.clone();
+    }
+    let storage = state.storage.clone();
+    let app = rust_dms::routes::router(state);
+
+    // The stream blocks on the unread body well before the last entry, so
+    // rewriting its object now lands after the size check and before the read
+    let request = json_request("POST", "/documents/archive", &api_key, serde_json::json!({"ids": ids}));
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    storage.write(&last_path, b"rewritten and longer".to_vec()).await.unwrap();
+
+    assert!(to_bytes(response.into_body(), usize::MAX).await.is_err());
+}
+
+/// Upload `contents` and return the id of the document it went to
+async fn uploaded_to(app: &Router, api_key: &str, fields: &[(&str, &str)], contents: &[u8]) -> String {
+    let (status, body) = send_json(app, upload(api_key, fields, "f.txt", contents)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["document_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn uploader_stats_aggregate_each_users_live_uploads() {
+    let Some(pool) = database().await else { return };
+    let (alice, alice_key) = user(&pool, "editor").await;
+    let (bob, bob_key) = user(&pool, "editor").await;
+    let (idle, _) = user(&pool, "editor").await;
+    let (_, admin_key) = user(&pool, "admin").await;
+    let app = rust_dms::routes::router(test_state(pool.clone()).expect("test state"));
+
+    let shared = uploaded_to(&app, &alice_key, &[("title", &unique("shared"))], b"aaa").await;
+    // A version on someone else's document counts for its uploader
+    uploaded_to(&app, &bob_key, &[("document_id", &shared)], b"bbbbb").await;
+    uploaded_to(&app, &bob_key, &[("title", &unique("own"))], b"cc").await;
+    let discarded = uploaded_to(&app, &alice_key, &[("title", &unique("discarded"))], b"dddddddd").await;
+    sqlx::query("UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE id = $1::uuid")
+        .bind(&discarded)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (status, stats) = send_json(&app, get("/stats/uploaders", &admin_key)).await;
+    assert_eq!(status, StatusCode::OK, "{}", stats);
+    // Other tests upload too; look only at these users
+    let of = |user: &User| {
+        let row = stats.as_array().unwrap().iter().find(|s| s["user_id"] == user.id.to_string())?;
+        Some([&row["document_count"], &row["version_count"], &row["total_bytes"]].map(|v| v.as_i64().unwrap()))
+    };
+    assert_eq!(of(&alice), Some([1, 1, 3]));
+    assert_eq!(of(&bob), Some([1, 2, 7]));
+    assert_eq!(of(&idle), None);
+
+    let (status, _) = send_json(&app, get("/stats/uploaders", &bob_key)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}